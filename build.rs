@@ -21,6 +21,7 @@ fn main() {
             "league_protocols_definitions/simulation/ssl_simulation_control.proto",
             "league_protocols_definitions/simulation/ssl_simulation_robot_control.proto",
             "league_protocols_definitions/simulation/ssl_simulation_robot_feedback.proto",
+            "league_protocols_definitions/simulation/ssl_simulation_synchronous.proto",
         ],
         &["league_protocols_definitions/simulation/"],
     );
@@ -31,6 +32,12 @@ fn main() {
         &["league_protocols_definitions/vision"],
     );
 
+    compile_packet(
+        "tracked_vision_packet",
+        &["league_protocols_definitions/vision/messages_robocup_ssl_wrapper_tracked.proto"],
+        &["league_protocols_definitions/vision"],
+    );
+
     compile_packet(
         "game_controller_packet",
         &["league_protocols_definitions/game_controller/ssl_gc_referee_message.proto"],
@@ -38,7 +38,9 @@ pub async fn strike_alone(world: &World, robot: &AllyRobot, ball: &Ball) -> Resu
     let mut interval = tokio::time::interval(Duration::from_secs(1));
     while robot.has_ball() {
         interval.tick().await;
-        robot.kick(Kick::Straight);
+        robot.kick(Kick::Straight {
+            speed: robot.get_specs().max_kick_speed,
+        });
     }
 
     Ok(())
@@ -72,7 +74,9 @@ pub async fn backwards_strike(world: &World, robot: &AllyRobot, ball: &Ball) {
                 if i.y < 0.5 && i.y > -0.5 {
                     // if the intersection to the ennemy side edge of the field is within their goal
                     println!("SHOOT!");
-                    robot.kick(Kick::Straight);
+                    robot.kick(Kick::Straight {
+                        speed: robot.get_specs().max_kick_speed,
+                    });
                     break;
                 }
             }
@@ -210,7 +214,9 @@ pub async fn three_attackers_attack(
         )
         .await;
     while chosen_striker.has_ball() {
-        chosen_striker.kick(crate::world::Kick::Straight);
+        chosen_striker.kick(Kick::Straight {
+            speed: chosen_striker.get_specs().max_kick_speed,
+        });
         sleep(Duration::from_secs(1)).await;
     }
 }
@@ -2,7 +2,7 @@ use crabe_async::{controllers::sim_controller::SimRobotController, world::TeamCo
 
 #[tokio::main]
 async fn main() {
-    let mut sim_controller = SimRobotController::new(TeamColor::Blue).await;
+    let mut sim_controller = SimRobotController::new(TeamColor::Blue, None, None).await;
     loop {
         match sim_controller.receive_feedback().await {
             Ok(feedback) => {
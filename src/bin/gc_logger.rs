@@ -2,7 +2,7 @@ use crabe_async::game_controller::GameController;
 
 #[tokio::main]
 async fn main() {
-    let mut gc = GameController::new(None, None);
+    let mut gc = GameController::new(None, None, None);
     loop {
         match gc.receive().await {
             Ok(feedback) => {
@@ -0,0 +1,26 @@
+use crabe_async::{
+    config::{AddressConfig, SimulatorConfig, DEFAULT_LOG_FILTER},
+    testing::simulator::{Simulator, SimulatorServer},
+};
+use tracing_subscriber::EnvFilter;
+
+/// robots of each team on the field
+const ROBOTS_PER_TEAM: u32 = 6;
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new(DEFAULT_LOG_FILTER))
+        .init();
+
+    let mut simulator = Simulator::new();
+    simulator.place_teams(ROBOTS_PER_TEAM);
+    SimulatorServer::new(
+        simulator,
+        &SimulatorConfig::default(),
+        &AddressConfig::default(),
+    )
+    .await
+    .run_forever()
+    .await;
+}
@@ -0,0 +1,71 @@
+use std::{path::PathBuf, str::FromStr};
+
+use clap::Parser;
+use crabe_async::{
+    config::Config,
+    game_controller::GameController,
+    log_file::{record_forever, LogWriter},
+    tracked_vision::TrackedVision,
+    vision::Vision,
+};
+use tokio::select;
+use tracing_subscriber::EnvFilter;
+
+/// Records the vision & referee messages in an SSL log file, to replay them with Coral, ssl-log-player or ssl-logtools.
+#[derive(Parser, Debug)]
+#[command(about)]
+struct Cli {
+    /// the log file, gzip-compressed if it ends with .gz
+    output: PathBuf,
+    /// path to a TOML config file, for the addresses of the vision & game controller
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// listen to the real vision's port
+    #[arg(long)]
+    real: bool,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let mut config = match &cli.config {
+        Some(path) => Config::load(path).expect("couldn't load the config file"),
+        None => Config::default(),
+    };
+    config.real |= cli.real;
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::from_str(&config.log_filter).expect("couldn't parse log filter"),
+        )
+        .init();
+
+    let interface = config.network.multicast_interface;
+    let (vision, tracked_vision) = if config.tracked_vision.enabled {
+        let tracked_vision = TrackedVision::new(
+            config.tracked_vision.ip,
+            config.tracked_vision.port,
+            interface,
+        );
+        (None, Some(tracked_vision))
+    } else {
+        let vision = Vision::new(config.vision.ip, config.vision.port, interface, config.real);
+        (Some(vision), None)
+    };
+    let gc = GameController::new(
+        config.game_controller.ip,
+        config.game_controller.port,
+        interface,
+    );
+    let writer = LogWriter::create(&cli.output).expect("couldn't create the log file");
+
+    // the log file is completed when the writer is dropped
+    select! {
+        e = record_forever(writer, vision, tracked_vision, gc) => {
+            eprintln!("couldn't write the log file: {:?}", e);
+        }
+        r = tokio::signal::ctrl_c() => {
+            r.expect("failed to listen for event");
+            println!("recording saved to {}", cli.output.display());
+        }
+    }
+}
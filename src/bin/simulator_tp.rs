@@ -4,7 +4,7 @@ use crabe_async::{
 
 #[tokio::main]
 async fn main() {
-    let mut sim_controller = SimulationController::new().await;
+    let mut sim_controller = SimulationController::new(None, None).await;
 
     sim_controller
         .tp_robot(
@@ -1,17 +1,22 @@
 use crabe_async::{
-    controllers::{sim_controller::SimRobotController, RobotController},
+    controllers::{sim_controller::SimRobotController, ControllerError, RobotController},
     world::{AllyRobot, TeamColor},
 };
 
 #[tokio::main]
 async fn main() {
     let team_color = TeamColor::Blue;
-    let mut sim_controller = SimRobotController::new(team_color).await;
+    let mut sim_controller = SimRobotController::new(team_color, None, None).await;
 
     let robot = AllyRobot::default_with_id(0, team_color);
     robot.set_target_angular_vel(1.);
-    sim_controller
+    match sim_controller
         .send_proper_command_for(vec![robot].into_iter())
         .await
-        .expect("couldn't send the command to the simulator");
+    {
+        Ok(_) => {}
+        // e.g. the feedback timed out, the command was still sent
+        Err(e) if !e.is_fatal() => eprintln!("error while sending the command: {:?}", e),
+        Err(e) => panic!("couldn't send the command to the simulator: {:?}", e),
+    }
 }
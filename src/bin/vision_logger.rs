@@ -2,7 +2,7 @@ use crabe_async::vision::Vision;
 
 #[tokio::main]
 async fn main() {
-    let mut vision = Vision::new(None, None, false);
+    let mut vision = Vision::new(None, None, None, false);
     loop {
         match vision.receive().await {
             Ok(feedback) => {
@@ -0,0 +1,177 @@
+//! Coral's configuration, loaded from a TOML file.
+//!
+//! Every value is optional, the defaults are the league's default addresses & ports.
+//!
+//! # Example
+//! ```toml
+//! color = "yellow"
+//! real = true
+//! field_side = "negative"
+//! record = "final.log.gz"
+//!
+//! [vision]
+//! port = 10006
+//!
+//! [game_controller]
+//! ip = "224.5.23.1"
+//!
+//! [network]
+//! multicast_interface = "192.168.1.42"
+//!
+//! [robots.default.limits]
+//! max_vel = 3.5
+//!
+//! [[robots.profiles]]
+//! ids = [0, 1, 2]
+//! specs = { radius = 0.085, limits = { max_vel = 2.5, max_acc = 3.0 } }
+//! ```
+
+use std::{
+    fs, io,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+};
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{
+    replay::check_speed,
+    world::{FieldSide, FleetSpecs, TeamColor},
+};
+
+/// default log filter: >=warn OR >=info for viewer OR >=debug for this crate
+pub const DEFAULT_LOG_FILTER: &str = "warn,crabe_async::viewer=info,crabe_async=debug";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    ReadError(io::Error),
+    ParseError(toml::de::Error),
+}
+
+/// Address of a service, `None` means the service's default.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AddressConfig {
+    pub ip: Option<Ipv4Addr>,
+    pub port: Option<u16>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackedVisionConfig {
+    /// use a league tracker instead of our own vision filters, the vision is still used for the geometry
+    pub enabled: bool,
+    pub ip: Option<Ipv4Addr>,
+    pub port: Option<u16>,
+    /// only use the frames of this tracker (e.g. "TIGERs AutoRef")
+    pub source_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulatorConfig {
+    /// run the built-in simulator instead of connecting to grSim or ER-Force's simulator
+    pub local: bool,
+    pub ip: Option<Ipv4Addr>,
+    /// port of the simulator control (teleportations...)
+    pub control_port: Option<u16>,
+    /// port of the blue team's robot control
+    pub blue_port: Option<u16>,
+    /// port of the yellow team's robot control
+    pub yellow_port: Option<u16>,
+}
+
+impl SimulatorConfig {
+    pub fn get_robot_control_port(&self, color: TeamColor) -> Option<u16> {
+        match color {
+            TeamColor::Blue => self.blue_port,
+            TeamColor::Yellow => self.yellow_port,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// interface on which the multicast groups are joined (vision, game controller), the OS chooses if `None`
+    pub multicast_interface: Option<Ipv4Addr>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ViewerConfig {
+    pub port: Option<u16>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplayConfig {
+    /// SSL log file replayed instead of listening to the vision & game controller
+    pub file: Option<PathBuf>,
+    /// times faster than the recording, real time if `None`
+    #[serde(deserialize_with = "deserialize_speed")]
+    pub speed: Option<f64>,
+    /// replays a vision frame each time Enter is pressed instead (the speed is ignored)
+    pub step: bool,
+}
+
+fn deserialize_speed<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    let speed = f64::deserialize(deserializer)?;
+    check_speed(speed).map(Some).map_err(de::Error::custom)
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub color: TeamColor,
+    /// real robots, or simulated ones
+    pub real: bool,
+    /// half of the field where our goal is, until the game controller tells us
+    pub field_side: Option<FieldSide>,
+    /// `tracing_subscriber::EnvFilter` directives
+    pub log_filter: String,
+    pub vision: AddressConfig,
+    pub tracked_vision: TrackedVisionConfig,
+    pub game_controller: AddressConfig,
+    pub simulator: SimulatorConfig,
+    pub base_station: AddressConfig,
+    pub network: NetworkConfig,
+    pub viewer: ViewerConfig,
+    /// SSL log file recording the vision & referee messages, gzip-compressed if it ends with `.gz`
+    pub record: Option<PathBuf>,
+    pub replay: ReplayConfig,
+    /// specs of our robots, per hardware generation
+    pub robots: FleetSpecs,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            color: TeamColor::Blue,
+            real: false,
+            field_side: None,
+            log_filter: DEFAULT_LOG_FILTER.to_string(),
+            vision: Default::default(),
+            tracked_vision: Default::default(),
+            game_controller: Default::default(),
+            simulator: Default::default(),
+            base_station: Default::default(),
+            network: Default::default(),
+            viewer: Default::default(),
+            record: None,
+            replay: Default::default(),
+            robots: Default::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        toml::from_str(s).map_err(ConfigError::ParseError)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(ConfigError::ReadError)?;
+        Self::from_toml(&content)
+    }
+}
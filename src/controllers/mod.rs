@@ -2,8 +2,18 @@ use std::{fmt::Debug, future::Future};
 
 use crate::world::AllyRobot;
 
+pub mod real_controller;
 pub mod sim_controller;
 
+/// robots are stopped by id, without knowing which ones are on the field
+pub const MAX_ROBOTS: u32 = 16;
+
+/// Errors of a `RobotController`.
+pub trait ControllerError: Debug {
+    /// A fatal error stops the control loop, the others are retried at the next control period.
+    fn is_fatal(&self) -> bool;
+}
+
 pub trait RobotController<R, E>
 where
     E: Debug,
@@ -13,5 +23,17 @@ where
         robots: impl Iterator<Item = AllyRobot>,
     ) -> impl Future<Output = Result<R, E>> + Send;
 
-    fn close(self) -> impl std::future::Future<Output = Result<(), E>> + Send;
+    /// Stops every robot (ids `0..MAX_ROBOTS`): no movement, no dribbling and no kick.
+    fn stop_all(&mut self) -> impl Future<Output = Result<(), E>> + Send;
+
+    /// Same as `stop_all` without waiting, for when it can't be awaited (e.g. the runtime is shutting down).
+    fn try_stop_all(&mut self) -> Result<(), E>;
+
+    // workaround for async Drop, to be replaced when std::future::AsyncDrop is stabilized
+    fn close(mut self) -> impl Future<Output = Result<(), E>> + Send
+    where
+        Self: Sized + Send,
+    {
+        async move { self.stop_all().await }
+    }
 }
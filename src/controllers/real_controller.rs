@@ -0,0 +1,184 @@
+use std::{collections::HashMap, future::Future, net::Ipv4Addr, time::Duration};
+
+use tokio::time::timeout;
+use tracing::{debug, trace};
+
+use crate::{
+    league_protocols::robot_packet::{BaseCommand, BaseFeedback, BaseToPc, Kicker, PcToBase},
+    net::{udp_transceiver::UdpTransceiver, ReceiveError, SendError},
+    world::{AllyRobot, Kick, RobotFeedback, RobotId},
+};
+
+use super::{ControllerError, RobotController, MAX_ROBOTS};
+
+const DEFAULT_BASE_STATION_IP: Ipv4Addr = Ipv4Addr::LOCALHOST;
+const DEFAULT_BASE_STATION_PORT: u16 = 10100;
+
+/// the base station doesn't always answer, we don't wait for its feedback longer than this
+const FEEDBACK_TIMEOUT: Duration = Duration::from_millis(5);
+
+/// dribbler speed, as a fraction of its max speed
+const DRIBBLER_SPEED: f32 = 1.;
+
+/// Controls the real robots through the base station, which relays the commands over radio.
+pub struct RealRobotController {
+    socket: UdpTransceiver,
+}
+
+#[derive(Debug)]
+pub enum RealRobotControllerError {
+    SendCommandsError(SendError),
+    ReceiveFeedbackError(ReceiveError),
+}
+
+impl ControllerError for RealRobotControllerError {
+    fn is_fatal(&self) -> bool {
+        match self {
+            RealRobotControllerError::SendCommandsError(e) => e.is_fatal(),
+            RealRobotControllerError::ReceiveFeedbackError(e) => e.is_fatal(),
+        }
+    }
+}
+
+impl From<BaseFeedback> for RobotFeedback {
+    fn from(feedback: BaseFeedback) -> Self {
+        Self {
+            has_ball: feedback.ir,
+            battery_voltage: Some(feedback.voltage as f64),
+            wheel_speeds: Some(
+                [
+                    feedback.motor_1_speed,
+                    feedback.motor_2_speed,
+                    feedback.motor_3_speed,
+                    feedback.motor_4_speed,
+                ]
+                .map(f64::from),
+            ),
+            ..RobotFeedback::now()
+        }
+    }
+}
+
+impl RealRobotController {
+    pub async fn new(custom_ip: Option<Ipv4Addr>, custom_port: Option<u16>) -> Self {
+        Self {
+            socket: UdpTransceiver::new(
+                custom_ip.unwrap_or(DEFAULT_BASE_STATION_IP),
+                custom_port.unwrap_or(DEFAULT_BASE_STATION_PORT),
+            )
+            .await
+            .expect("Failed to setup base station controller."),
+        }
+    }
+
+    pub async fn receive_feedback(&mut self) -> Result<BaseToPc, ReceiveError> {
+        self.socket.receive::<BaseToPc>().await
+    }
+
+    /// Waits up to `FEEDBACK_TIMEOUT` for the base station's feedback,
+    /// then reads the rest of the queued feedback (the latest one of each robot is kept).
+    async fn try_receive_feedback(
+        &mut self,
+    ) -> Result<HashMap<RobotId, RobotFeedback>, RealRobotControllerError> {
+        let mut feedback_per_robot = HashMap::new();
+        let mut packet = match timeout(FEEDBACK_TIMEOUT, self.receive_feedback()).await {
+            Ok(packet) => Some(packet.map_err(RealRobotControllerError::ReceiveFeedbackError)?),
+            Err(_) => {
+                trace!("no feedback from the base station");
+                None
+            }
+        };
+        while let Some(BaseToPc { feedbacks }) = packet {
+            for feedback in feedbacks {
+                feedback_per_robot.insert(feedback.robot_id as RobotId, feedback.into());
+            }
+            packet = self
+                .socket
+                .try_receive::<BaseToPc>()
+                .map_err(RealRobotControllerError::ReceiveFeedbackError)?;
+        }
+        Ok(feedback_per_robot)
+    }
+}
+
+fn make_command(robot: &AllyRobot) -> BaseCommand {
+    let max_kick_speed = robot.get_specs().max_kick_speed;
+    let (kick, kick_power) = match robot.take_should_kick() {
+        Some(kick @ Kick::Straight { .. }) => {
+            (Kicker::Flat, kick.get_speed().min(max_kick_speed) as f32)
+        }
+        Some(kick @ Kick::Chip { .. }) => {
+            (Kicker::Chip, kick.get_speed().min(max_kick_speed) as f32)
+        }
+        None => (Kicker::NoKick, 0.),
+    };
+    let target_vel = robot.get_target_vel();
+    BaseCommand {
+        robot_id: robot.get_id() as u32,
+        normal_velocity: target_vel.x as f32,
+        tangential_velocity: target_vel.y as f32,
+        angular_velocity: robot.get_target_angular_vel() as f32,
+        kick: kick.into(),
+        kick_power,
+        charge: true,
+        dribbler: if robot.should_dribble() {
+            DRIBBLER_SPEED
+        } else {
+            0.
+        },
+    }
+}
+
+impl RobotController<HashMap<RobotId, RobotFeedback>, RealRobotControllerError>
+    for RealRobotController
+{
+    fn send_proper_command_for(
+        &mut self,
+        robots: impl Iterator<Item = AllyRobot>,
+    ) -> impl Future<Output = Result<HashMap<RobotId, RobotFeedback>, RealRobotControllerError>> + Send
+    {
+        let packet = PcToBase {
+            commands: robots.map(|robot| make_command(&robot)).collect(),
+        };
+        trace!(?packet, "sending packet");
+
+        async {
+            self.socket
+                .send(packet)
+                .await
+                .map_err(RealRobotControllerError::SendCommandsError)?;
+            self.try_receive_feedback().await
+        }
+    }
+
+    async fn stop_all(&mut self) -> Result<(), RealRobotControllerError> {
+        debug!("stopping robots..");
+        self.socket
+            .send(stop_packet())
+            .await
+            .map(|_| ())
+            .map_err(RealRobotControllerError::SendCommandsError)
+    }
+
+    fn try_stop_all(&mut self) -> Result<(), RealRobotControllerError> {
+        debug!("stopping robots without waiting..");
+        self.socket
+            .try_send(stop_packet())
+            .map(|_| ())
+            .map_err(RealRobotControllerError::SendCommandsError)
+    }
+}
+
+/// stops every robot: no movement, no dribbling, no kick & no charge
+fn stop_packet() -> PcToBase {
+    PcToBase {
+        commands: (0..MAX_ROBOTS)
+            .map(|rid| BaseCommand {
+                robot_id: rid,
+                kick: Kicker::NoKick.into(),
+                charge: false,
+                ..Default::default()
+            })
+            .collect(),
+    }
+}
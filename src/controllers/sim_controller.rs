@@ -1,30 +1,43 @@
-use std::{collections::HashMap, future::Future, net::Ipv4Addr};
+use std::{collections::HashMap, future::Future, net::Ipv4Addr, time::Duration};
 
+use tokio::time::timeout;
 use tracing::{debug, trace};
 
 use crate::{
     league_protocols::simulation_packet::{
-        robot_move_command, MoveLocalVelocity, MoveWheelVelocity, RobotCommand, RobotControl,
-        RobotControlResponse, RobotFeedback, RobotMoveCommand,
+        self, robot_move_command, MoveLocalVelocity, MoveWheelVelocity, RobotCommand, RobotControl,
+        RobotControlResponse, RobotMoveCommand,
     },
     net::{udp_transceiver::UdpTransceiver, ReceiveError, SendError},
-    world::{AllyRobot, Kick, RobotId, TeamColor},
+    world::{AllyRobot, Kick, RobotFeedback, RobotId, TeamColor},
 };
 
-use super::RobotController;
+use super::{ControllerError, RobotController, MAX_ROBOTS};
+
+const DEFAULT_SIM_IP: Ipv4Addr = Ipv4Addr::LOCALHOST;
+const DEFAULT_SIM_BLUE_PORT: u16 = 10301;
+const DEFAULT_SIM_YELLOW_PORT: u16 = 10302;
+
+/// a lost or late feedback doesn't delay the next commands
+const FEEDBACK_TIMEOUT: Duration = Duration::from_millis(5);
 
 pub struct SimRobotController {
     socket: UdpTransceiver,
 }
 
 impl SimRobotController {
-    pub async fn new(color: TeamColor) -> Self {
-        let port: u16 = match color {
-            TeamColor::Blue => 10301,
-            TeamColor::Yellow => 10302,
-        };
+    pub async fn new(
+        color: TeamColor,
+        custom_ip: Option<Ipv4Addr>,
+        custom_port: Option<u16>,
+    ) -> Self {
+        let ip = custom_ip.unwrap_or(DEFAULT_SIM_IP);
+        let port = custom_port.unwrap_or(match color {
+            TeamColor::Blue => DEFAULT_SIM_BLUE_PORT,
+            TeamColor::Yellow => DEFAULT_SIM_YELLOW_PORT,
+        });
         Self {
-            socket: UdpTransceiver::new(Ipv4Addr::LOCALHOST, port)
+            socket: UdpTransceiver::new(ip, port)
                 .await
                 .expect("Failed to setup simulator robot controller."),
         }
@@ -35,10 +48,88 @@ impl SimRobotController {
     }
 }
 
+impl From<simulation_packet::RobotFeedback> for RobotFeedback {
+    fn from(feedback: simulation_packet::RobotFeedback) -> Self {
+        Self {
+            has_ball: feedback.dribbler_ball_contact(),
+            ..RobotFeedback::now()
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum SimRobotControllerError {
     SendCommandsError(SendError),
     ReceiveFeedbackError(ReceiveError),
+    /// the simulator didn't answer within `FEEDBACK_TIMEOUT`
+    FeedbackTimeout,
+}
+
+impl ControllerError for SimRobotControllerError {
+    fn is_fatal(&self) -> bool {
+        match self {
+            SimRobotControllerError::SendCommandsError(e) => e.is_fatal(),
+            SimRobotControllerError::ReceiveFeedbackError(e) => e.is_fatal(),
+            SimRobotControllerError::FeedbackTimeout => false,
+        }
+    }
+}
+
+/// Commands for the simulated robots, from their targets.
+pub(crate) fn make_robot_control(robots: impl Iterator<Item = AllyRobot>) -> RobotControl {
+    let mut packet = RobotControl::default();
+
+    for robot in robots {
+        let max_kick_speed = robot.get_specs().max_kick_speed;
+        let (kick_speed, kick_angle) = match robot.take_should_kick() {
+            Some(kick @ Kick::Chip { .. }) => (
+                Some(kick.get_speed().min(max_kick_speed) as f32),
+                Some(45.0),
+            ),
+            Some(kick @ Kick::Straight { .. }) => {
+                (Some(kick.get_speed().min(max_kick_speed) as f32), Some(0.0))
+            }
+            None => (None, None),
+        };
+
+        let target_vel = robot.get_target_vel();
+
+        let dribbler_speed = if robot.should_dribble() {
+            Some(1500.) // RPM ?
+        } else {
+            Some(0.)
+        };
+
+        let robot_command = RobotCommand {
+            id: robot.get_id() as u32,
+            move_command: Some(RobotMoveCommand {
+                command: Some(robot_move_command::Command::LocalVelocity(
+                    MoveLocalVelocity {
+                        forward: target_vel.x as f32,
+                        left: target_vel.y as f32,
+                        angular: robot.get_target_angular_vel() as f32,
+                    },
+                )),
+            }),
+            kick_speed,
+            kick_angle,
+            dribbler_speed,
+        };
+
+        packet.robot_commands.push(robot_command);
+    }
+    packet
+}
+
+/// Feedback of each robot, indexed by id.
+pub(crate) fn feedback_per_robot(
+    response: RobotControlResponse,
+) -> HashMap<RobotId, RobotFeedback> {
+    response
+        .feedback
+        .into_iter()
+        .map(|feedback| (feedback.id as RobotId, feedback.into()))
+        .collect()
 }
 
 impl RobotController<HashMap<RobotId, RobotFeedback>, SimRobotControllerError>
@@ -49,47 +140,7 @@ impl RobotController<HashMap<RobotId, RobotFeedback>, SimRobotControllerError>
         robots: impl Iterator<Item = AllyRobot>,
     ) -> impl Future<Output = Result<HashMap<RobotId, RobotFeedback>, SimRobotControllerError>> + Send
     {
-        let mut packet = RobotControl::default();
-
-        for robot in robots {
-            // let (kick_speed, kick_angle) = match &command.kick {
-            //     None => (0.0, 0.0),
-            //     Some(Kick::StraightKick { power }) => (*power, 0.0),
-            //     Some(Kick::ChipKick { power }) => (*power, 45.0),
-            // };
-
-            let (kick_speed, kick_angle) = match robot.take_should_kick() {
-                Some(Kick::Chip) => (Some(5.), Some(45.0)),
-                Some(Kick::Straight) => (Some(5.), Some(0.0)),
-                None => (None, None),
-            };
-
-            let target_vel = robot.get_target_vel();
-
-            let dribbler_speed = if robot.should_dribble() {
-                Some(1500.) // RPM ?
-            } else {
-                Some(0.)
-            };
-
-            let robot_command = RobotCommand {
-                id: robot.get_id() as u32,
-                move_command: Some(RobotMoveCommand {
-                    command: Some(robot_move_command::Command::LocalVelocity(
-                        MoveLocalVelocity {
-                            forward: target_vel.x as f32,
-                            left: target_vel.y as f32,
-                            angular: robot.get_target_angular_vel() as f32,
-                        },
-                    )),
-                }),
-                kick_speed,
-                kick_angle,
-                dribbler_speed,
-            };
-
-            packet.robot_commands.push(robot_command);
-        }
+        let packet = make_robot_control(robots);
         trace!(?packet, "sending packet");
 
         async {
@@ -97,45 +148,53 @@ impl RobotController<HashMap<RobotId, RobotFeedback>, SimRobotControllerError>
                 .send(packet)
                 .await
                 .map_err(SimRobotControllerError::SendCommandsError)?;
-            let mut feedback_per_robot = HashMap::new();
-            for feedback in self
-                .receive_feedback()
-                .await
-                .map_err(SimRobotControllerError::ReceiveFeedbackError)?
-                .feedback
-            {
-                feedback_per_robot.insert(feedback.id as RobotId, feedback);
+            match timeout(FEEDBACK_TIMEOUT, self.receive_feedback()).await {
+                Ok(response) => response
+                    .map(feedback_per_robot)
+                    .map_err(SimRobotControllerError::ReceiveFeedbackError),
+                Err(_) => Err(SimRobotControllerError::FeedbackTimeout),
             }
-            Ok(feedback_per_robot)
         }
     }
 
-    // workaround for async Drop, to be replaced when std::future::AsyncDrop is stabilized
-    async fn close(self) -> Result<(), SimRobotControllerError> {
-        let mut packet = RobotControl::default();
-        for rid in 0..16 {
-            packet.robot_commands.push(RobotCommand {
-                id: rid,
-                move_command: Some(RobotMoveCommand {
-                    command: Some(robot_move_command::Command::WheelVelocity(
-                        MoveWheelVelocity {
-                            front_right: 0.,
-                            back_right: 0.,
-                            back_left: 0.,
-                            front_left: 0.,
-                        },
-                    )),
-                }),
-                kick_speed: Some(5.),
-                kick_angle: Some(0.),
-                dribbler_speed: Some(0.),
-            });
-        }
+    async fn stop_all(&mut self) -> Result<(), SimRobotControllerError> {
         debug!("stopping robots..");
         self.socket
-            .send(packet)
+            .send(stop_packet())
             .await
             .map(|_| ())
             .map_err(SimRobotControllerError::SendCommandsError)
     }
+
+    fn try_stop_all(&mut self) -> Result<(), SimRobotControllerError> {
+        debug!("stopping robots without waiting..");
+        self.socket
+            .try_send(stop_packet())
+            .map(|_| ())
+            .map_err(SimRobotControllerError::SendCommandsError)
+    }
+}
+
+/// stops every robot: no movement, no dribbling & no kick
+fn stop_packet() -> RobotControl {
+    let mut packet = RobotControl::default();
+    for rid in 0..MAX_ROBOTS {
+        packet.robot_commands.push(RobotCommand {
+            id: rid,
+            move_command: Some(RobotMoveCommand {
+                command: Some(robot_move_command::Command::WheelVelocity(
+                    MoveWheelVelocity {
+                        front_right: 0.,
+                        back_right: 0.,
+                        back_left: 0.,
+                        front_left: 0.,
+                    },
+                )),
+            }),
+            kick_speed: Some(0.),
+            kick_angle: Some(0.),
+            dribbler_speed: Some(0.),
+        });
+    }
+    packet
 }
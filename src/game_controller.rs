@@ -1,32 +1,42 @@
 use crate::league_protocols::game_controller_packet::Referee;
 use crate::net::multicast_receiver::MulticastUdpReceiver;
 use crate::net::ReceiveError;
+use crate::replay::PacketSource;
 use std::net::Ipv4Addr;
 
 const DEFAULT_GC_IP: Ipv4Addr = Ipv4Addr::new(224, 5, 23, 1);
-const DEFAUL_TGC_PORT: u16 = 10003;
+const DEFAULT_GC_PORT: u16 = 10003;
 
 pub struct GameController {
-    socket: MulticastUdpReceiver,
+    source: PacketSource<Referee>,
 }
 
 impl GameController {
-    pub fn new(custom_gc_ip: Option<Ipv4Addr>, custom_gc_port: Option<u16>) -> Self {
+    pub fn new(
+        custom_gc_ip: Option<Ipv4Addr>,
+        custom_gc_port: Option<u16>,
+        custom_interface: Option<Ipv4Addr>,
+    ) -> Self {
         let ip = match custom_gc_ip {
             Some(custom_ip) => custom_ip,
             None => DEFAULT_GC_IP,
         };
         let port = match custom_gc_port {
             Some(custom_port) => custom_port,
-            None => DEFAUL_TGC_PORT,
+            None => DEFAULT_GC_PORT,
         };
 
-        Self {
-            socket: MulticastUdpReceiver::new(ip, port).expect("Failed to create GC receiver"),
-        }
+        Self::from_source(PacketSource::Network(Box::new(
+            MulticastUdpReceiver::new(ip, port, custom_interface.unwrap_or(Ipv4Addr::UNSPECIFIED))
+                .expect("Failed to create GC receiver"),
+        )))
+    }
+
+    pub(crate) fn from_source(source: PacketSource<Referee>) -> Self {
+        Self { source }
     }
 
     pub async fn receive(&mut self) -> Result<Referee, ReceiveError> {
-        self.socket.receive::<Referee>().await
+        self.source.receive().await
     }
 }
@@ -0,0 +1,357 @@
+//! Team-relative game state, driven by the referee commands sent by the game controller.
+//!
+//! The state machine follows the SSL rules: the game controller only sends "coarse" commands
+//! (`STOP`, `PREPARE_KICKOFF_BLUE`, `NORMAL_START`, ...), we translate them relative to our
+//! team color and handle the implicit transitions (ball moved after a kickoff, free kick timed out).
+
+use tracing::{debug, warn};
+
+use crate::{league_protocols::game_controller_packet::referee::Command, world::TeamColor};
+
+/// distance in [m] the ball has to travel for a kickoff/free kick to be considered taken
+pub const BALL_MOVED_DISTANCE: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltedState {
+    Halt,
+    Timeout,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoppedState {
+    Stop,
+    PrepareKickoffUs,
+    PrepareKickoffThem,
+    BallPlacementUs,
+    BallPlacementThem,
+    PreparePenaltyUs,
+    PreparePenaltyThem,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunningState {
+    KickoffUs,
+    KickoffThem,
+    FreeKickUs,
+    FreeKickThem,
+    PenaltyUs,
+    PenaltyThem,
+    Run,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameState {
+    Halted(HaltedState),
+    Stopped(StoppedState),
+    Running(RunningState),
+}
+
+impl Default for GameState {
+    /// robots must not move until the game controller tells us otherwise
+    fn default() -> Self {
+        GameState::Halted(HaltedState::Halt)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEvent {
+    /// a new command was issued by the game controller
+    RefereeCommand(Command),
+    /// the ball moved at least `BALL_MOVED_DISTANCE` since the last command
+    BallMoved,
+    /// the time given to take the current kickoff/free kick ran out
+    ActionTimedOut,
+}
+
+/// Returns `us` if the command's team is our team, `them` otherwise.
+fn relative<T>(command_team: TeamColor, color: TeamColor, us: T, them: T) -> T {
+    if command_team == color {
+        us
+    } else {
+        them
+    }
+}
+
+impl GameState {
+    pub fn update(self, event: GameEvent, color: TeamColor) -> Self {
+        use GameEvent::*;
+        use TeamColor::*;
+
+        let new_state = match (self, event) {
+            // (from any state) referee commands that don't depend on the current state
+            (_, RefereeCommand(Command::Halt)) => GameState::Halted(HaltedState::Halt),
+            (_, RefereeCommand(Command::Stop)) => GameState::Stopped(StoppedState::Stop),
+            (_, RefereeCommand(Command::TimeoutBlue | Command::TimeoutYellow)) => {
+                GameState::Halted(HaltedState::Timeout)
+            }
+            (_, RefereeCommand(Command::ForceStart)) => GameState::Running(RunningState::Run),
+            (_, RefereeCommand(Command::PrepareKickoffBlue)) => GameState::Stopped(relative(
+                Blue,
+                color,
+                StoppedState::PrepareKickoffUs,
+                StoppedState::PrepareKickoffThem,
+            )),
+            (_, RefereeCommand(Command::PrepareKickoffYellow)) => GameState::Stopped(relative(
+                Yellow,
+                color,
+                StoppedState::PrepareKickoffUs,
+                StoppedState::PrepareKickoffThem,
+            )),
+            (_, RefereeCommand(Command::PreparePenaltyBlue)) => GameState::Stopped(relative(
+                Blue,
+                color,
+                StoppedState::PreparePenaltyUs,
+                StoppedState::PreparePenaltyThem,
+            )),
+            (_, RefereeCommand(Command::PreparePenaltyYellow)) => GameState::Stopped(relative(
+                Yellow,
+                color,
+                StoppedState::PreparePenaltyUs,
+                StoppedState::PreparePenaltyThem,
+            )),
+            (_, RefereeCommand(Command::BallPlacementBlue)) => GameState::Stopped(relative(
+                Blue,
+                color,
+                StoppedState::BallPlacementUs,
+                StoppedState::BallPlacementThem,
+            )),
+            (_, RefereeCommand(Command::BallPlacementYellow)) => GameState::Stopped(relative(
+                Yellow,
+                color,
+                StoppedState::BallPlacementUs,
+                StoppedState::BallPlacementThem,
+            )),
+            // FREE KICKS (indirect free kicks only differ by the fact that you can't score directly)
+            (_, RefereeCommand(Command::DirectFreeBlue | Command::IndirectFreeBlue)) => {
+                GameState::Running(relative(
+                    Blue,
+                    color,
+                    RunningState::FreeKickUs,
+                    RunningState::FreeKickThem,
+                ))
+            }
+            (_, RefereeCommand(Command::DirectFreeYellow | Command::IndirectFreeYellow)) => {
+                GameState::Running(relative(
+                    Yellow,
+                    color,
+                    RunningState::FreeKickUs,
+                    RunningState::FreeKickThem,
+                ))
+            }
+            // NORMAL START (only meaningful after a prepare command)
+            (
+                GameState::Stopped(StoppedState::PrepareKickoffUs),
+                RefereeCommand(Command::NormalStart),
+            ) => GameState::Running(RunningState::KickoffUs),
+            (
+                GameState::Stopped(StoppedState::PrepareKickoffThem),
+                RefereeCommand(Command::NormalStart),
+            ) => GameState::Running(RunningState::KickoffThem),
+            (
+                GameState::Stopped(StoppedState::PreparePenaltyUs),
+                RefereeCommand(Command::NormalStart),
+            ) => GameState::Running(RunningState::PenaltyUs),
+            (
+                GameState::Stopped(StoppedState::PreparePenaltyThem),
+                RefereeCommand(Command::NormalStart),
+            ) => GameState::Running(RunningState::PenaltyThem),
+            // goal commands are deprecated and for information only, the game controller will send a STOP
+            (s, RefereeCommand(Command::GoalBlue | Command::GoalYellow)) => s,
+            // Running
+            (
+                GameState::Running(
+                    RunningState::KickoffUs
+                    | RunningState::KickoffThem
+                    | RunningState::FreeKickUs
+                    | RunningState::FreeKickThem,
+                ),
+                BallMoved | ActionTimedOut,
+            ) => GameState::Running(RunningState::Run),
+            // a penalty running out of time is decided by the game controller
+            (
+                GameState::Running(RunningState::PenaltyUs | RunningState::PenaltyThem),
+                BallMoved,
+            ) => GameState::Running(RunningState::Run),
+            // the ball moving is only a transition for kickoffs, free kicks and penalties
+            (s, BallMoved | ActionTimedOut) => s,
+            (s, e) => {
+                warn!(state = ?s, event = ?e, "unexpected game state and event combination");
+                s
+            }
+        };
+
+        if new_state != self {
+            debug!(from = ?self, to = ?new_state, event = ?event, "game state transition");
+        }
+        new_state
+    }
+
+    pub fn is_halted(&self) -> bool {
+        matches!(self, GameState::Halted(_))
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        matches!(self, GameState::Stopped(_))
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self, GameState::Running(_))
+    }
+
+    /// true while our robots must stay under `STOP_MAX_VEL` (during a STOP & the ennemies' ball placement)
+    pub fn is_speed_limited(&self) -> bool {
+        matches!(
+            self,
+            GameState::Stopped(StoppedState::Stop | StoppedState::BallPlacementThem)
+        )
+    }
+
+    /// true while the ball has been given to a team and must be put back in play (kickoff, free kick, penalty)
+    pub fn is_waiting_for_ball_to_move(&self) -> bool {
+        matches!(
+            self,
+            GameState::Running(
+                RunningState::KickoffUs
+                    | RunningState::KickoffThem
+                    | RunningState::FreeKickUs
+                    | RunningState::FreeKickThem
+                    | RunningState::PenaltyUs
+                    | RunningState::PenaltyThem
+            )
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use GameEvent::*;
+
+    const HALT: GameState = GameState::Halted(HaltedState::Halt);
+    const STOP: GameState = GameState::Stopped(StoppedState::Stop);
+    const RUN: GameState = GameState::Running(RunningState::Run);
+
+    /// (from, event, to) for the blue team
+    const TRANSITIONS: &[(GameState, GameEvent, GameState)] = &[
+        (HALT, RefereeCommand(Command::Stop), STOP),
+        (RUN, RefereeCommand(Command::Halt), HALT),
+        (
+            RUN,
+            RefereeCommand(Command::TimeoutYellow),
+            GameState::Halted(HaltedState::Timeout),
+        ),
+        (STOP, RefereeCommand(Command::ForceStart), RUN),
+        (
+            STOP,
+            RefereeCommand(Command::PrepareKickoffBlue),
+            GameState::Stopped(StoppedState::PrepareKickoffUs),
+        ),
+        (
+            STOP,
+            RefereeCommand(Command::PrepareKickoffYellow),
+            GameState::Stopped(StoppedState::PrepareKickoffThem),
+        ),
+        (
+            GameState::Stopped(StoppedState::PrepareKickoffUs),
+            RefereeCommand(Command::NormalStart),
+            GameState::Running(RunningState::KickoffUs),
+        ),
+        (
+            GameState::Stopped(StoppedState::PrepareKickoffThem),
+            RefereeCommand(Command::NormalStart),
+            GameState::Running(RunningState::KickoffThem),
+        ),
+        (
+            GameState::Stopped(StoppedState::PreparePenaltyUs),
+            RefereeCommand(Command::NormalStart),
+            GameState::Running(RunningState::PenaltyUs),
+        ),
+        (
+            GameState::Stopped(StoppedState::PreparePenaltyThem),
+            RefereeCommand(Command::NormalStart),
+            GameState::Running(RunningState::PenaltyThem),
+        ),
+        (
+            STOP,
+            RefereeCommand(Command::BallPlacementYellow),
+            GameState::Stopped(StoppedState::BallPlacementThem),
+        ),
+        (
+            STOP,
+            RefereeCommand(Command::IndirectFreeBlue),
+            GameState::Running(RunningState::FreeKickUs),
+        ),
+        (
+            STOP,
+            RefereeCommand(Command::DirectFreeYellow),
+            GameState::Running(RunningState::FreeKickThem),
+        ),
+        // ball moved
+        (GameState::Running(RunningState::KickoffUs), BallMoved, RUN),
+        (
+            GameState::Running(RunningState::KickoffThem),
+            BallMoved,
+            RUN,
+        ),
+        (
+            GameState::Running(RunningState::FreeKickThem),
+            BallMoved,
+            RUN,
+        ),
+        (GameState::Running(RunningState::PenaltyUs), BallMoved, RUN),
+        (
+            GameState::Running(RunningState::PenaltyThem),
+            BallMoved,
+            RUN,
+        ),
+        (STOP, BallMoved, STOP),
+        (
+            GameState::Stopped(StoppedState::PrepareKickoffUs),
+            BallMoved,
+            GameState::Stopped(StoppedState::PrepareKickoffUs),
+        ),
+        // action timed out
+        (
+            GameState::Running(RunningState::FreeKickUs),
+            ActionTimedOut,
+            RUN,
+        ),
+        (
+            GameState::Running(RunningState::PenaltyUs),
+            ActionTimedOut,
+            GameState::Running(RunningState::PenaltyUs),
+        ),
+        // ignored
+        (STOP, RefereeCommand(Command::NormalStart), STOP),
+        (RUN, RefereeCommand(Command::GoalBlue), RUN),
+    ];
+
+    #[test]
+    fn transitions() {
+        for &(from, event, to) in TRANSITIONS {
+            assert_eq!(
+                from.update(event, TeamColor::Blue),
+                to,
+                "{from:?} + {event:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn commands_are_relative_to_our_color() {
+        assert_eq!(
+            STOP.update(
+                RefereeCommand(Command::PrepareKickoffBlue),
+                TeamColor::Yellow
+            ),
+            GameState::Stopped(StoppedState::PrepareKickoffThem)
+        );
+        assert_eq!(
+            STOP.update(
+                RefereeCommand(Command::BallPlacementYellow),
+                TeamColor::Yellow
+            ),
+            GameState::Stopped(StoppedState::BallPlacementUs)
+        );
+    }
+}
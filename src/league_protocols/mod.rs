@@ -2,4 +2,11 @@
 pub mod game_controller_packet;
 pub mod robot_packet;
 pub mod simulation_packet;
+pub mod tracked_vision_packet;
 pub mod vision_packet;
+
+/// Converts a message to its copy in another package (e.g. the simulation's detection frame to the vision's one).
+pub(crate) fn convert<T: prost::Message, U: prost::Message + Default>(message: &T) -> U {
+    U::decode(message.encode_to_vec().as_slice())
+        .expect("copies of a message should have the same definition")
+}
@@ -1,34 +1,63 @@
 #![deny(clippy::unwrap_used)]
 #![allow(async_fn_in_trait)]
 pub mod actions;
+pub mod config;
 pub mod controllers;
 pub mod game_controller;
+pub mod game_state;
 pub mod league_protocols;
+pub mod log_file;
 pub mod math;
+pub mod motion;
 pub mod net;
+pub mod obstacles;
+pub mod replay;
 pub mod testing;
+pub mod tracked_vision;
+pub mod tracking;
 pub mod trajectories;
 pub mod viewer;
 pub mod vision;
 pub mod world;
 
-use std::{collections::HashMap, fmt::Debug, sync::LockResult, time::Duration};
+use std::{
+    collections::HashMap, fmt::Debug, marker::PhantomData, panic::AssertUnwindSafe,
+    sync::LockResult, time::Duration,
+};
+
+use futures_util::FutureExt;
 
-use controllers::RobotController;
-use league_protocols::simulation_packet::RobotFeedback;
-use math::Point2;
+use controllers::{ControllerError, RobotController};
+use game_controller::GameController;
+use game_state::{GameEvent, BALL_MOVED_DISTANCE};
+use league_protocols::{
+    tracked_vision_packet::{self, TrackedFrame},
+    vision_packet::SslDetectionFrame,
+};
+use math::{Point2, ReactivePoint2Ext, Vec2};
+use motion::MotionControl;
 use tokio::{
     select,
-    sync::oneshot::{self, Sender},
+    sync::{
+        oneshot::{self, Sender},
+        watch,
+    },
     task::JoinHandle,
+    time::MissedTickBehavior,
 };
-use tracing::{debug, info, warn};
-use viewer::ViewerObject;
+use tracing::{debug, error, info, warn};
+use tracked_vision::{TrackedVision, TrackerSelection};
+use tracking::{BallTracker, CameraFusion, FusedFrame};
+use viewer::{ViewerObject, ViewerObjectGuard};
 use vision::Vision;
-use world::{AllyRobot, EnnemyRobot, RobotId, TeamColor, World};
+use world::{AllyRobot, BallKick, EnnemyRobot, RobotFeedback, RobotId, TeamColor, World};
 
 pub const CONTROL_PERIOD: Duration = Duration::from_millis(10);
 pub const DETECTION_SCALING_FACTOR: f64 = 1000.;
+/// robots whose target velocities weren't set for this long are stopped by the control loop
+pub const COMMAND_TIMEOUT: Duration = Duration::from_millis(200);
+/// period of the visibility checks made without vision frames, see `update_visibility_forever`
+pub const VISIBILITY_CHECK_PERIOD: Duration = Duration::from_millis(100);
 
 pub trait IgnoreMutexErr<T> {
     fn unwrap_ignore_poison(self) -> T;
@@ -48,14 +77,55 @@ impl<T> IgnoreMutexErr<T> for LockResult<T> {
     }
 }
 
+/// Stops the robots whose commands are stale, see `COMMAND_TIMEOUT`.
+pub(crate) fn apply_command_watchdog(robots: &[AllyRobot]) {
+    for robot in robots {
+        if robot.apply_command_watchdog(COMMAND_TIMEOUT) {
+            warn!(
+                robot_id = robot.get_id(),
+                "target velocities weren't refreshed, stopping the robot"
+            );
+        }
+    }
+}
+
+/// consecutive failed control periods after which the controller is reported unreachable
+pub const UNREACHABLE_AFTER_FAILURES: u32 = 100;
+
+/// Health of the control loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlHealth {
+    /// the last commands were sent
+    Healthy,
+    /// the last control periods failed (commands not sent or no feedback), they're still retried
+    Degraded { consecutive_failures: u32 },
+    /// the control loop stopped (fatal controller error, panic or stop)
+    Stopped,
+}
+
+/// only wakes up the health's subscribers when it changes
+fn set_health(health: &watch::Sender<ControlHealth>, new_health: ControlHealth) {
+    health.send_if_modified(|current_health| {
+        let modified = *current_health != new_health;
+        *current_health = new_health;
+        modified
+    });
+}
+
+/// Sends the commands every `CONTROL_PERIOD`, until a fatal controller error (which is returned).
 async fn control_loop<
-    E: Debug,
+    E: ControllerError,
     C: RobotController<HashMap<RobotId, RobotFeedback>, E> + Send + 'static,
 >(
     world: World,
     controller: &mut C,
-) {
+    health: &watch::Sender<ControlHealth>,
+) -> E {
     let mut interval = tokio::time::interval(CONTROL_PERIOD);
+    // a late period is skipped instead of sending bursts of commands
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut consecutive_failures = 0;
+    let mut motion_control = MotionControl::new();
     loop {
         interval.tick().await; // first tick ticks immediately that's why it's at the beginning
 
@@ -65,124 +135,418 @@ async fn control_loop<
             .lock()
             .unwrap_ignore_poison()
             .values()
+            .filter(|r| r.is_active()) // robots which left the field
             .cloned()
             .collect::<Vec<AllyRobot>>();
-        let feedback_per_robot = controller
-            .send_proper_command_for(robots.into_iter())
-            .await
-            .expect("couldn't send commands to robots");
-        for (rid, feedback) in feedback_per_robot {
-            if let Some(robot) = world.team.lock().unwrap_ignore_poison().get_mut(&rid) {
-                robot.set_has_ball(feedback.dribbler_ball_contact());
+        apply_command_watchdog(&robots);
+        motion_control.update(&robots);
+        match controller.send_proper_command_for(robots.into_iter()).await {
+            Ok(feedback_per_robot) => {
+                if consecutive_failures > 0 {
+                    info!(consecutive_failures, "controller recovered");
+                    consecutive_failures = 0;
+                }
+                set_health(health, ControlHealth::Healthy);
+                for (rid, feedback) in feedback_per_robot {
+                    if let Some(robot) = world.team.lock().unwrap_ignore_poison().get_mut(&rid) {
+                        robot.set_feedback(feedback);
+                    }
+                }
+            }
+            Err(e) if e.is_fatal() => return e,
+            Err(e) => {
+                consecutive_failures += 1;
+                match consecutive_failures {
+                    1 => warn!(?e, "couldn't control the robots, retrying"),
+                    UNREACHABLE_AFTER_FAILURES => {
+                        error!(
+                            ?e,
+                            consecutive_failures, "controller unreachable, still retrying"
+                        )
+                    }
+                    _ => debug!(?e, consecutive_failures, "couldn't control the robots"),
+                }
+                set_health(
+                    health,
+                    ControlHealth::Degraded {
+                        consecutive_failures,
+                    },
+                );
             }
         }
     }
 }
 
+/// Stops the control thread (and the robots) when stopped or dropped.
 pub struct ControlThreadHandle {
     stop_sender: Sender<()>,
     handle: JoinHandle<()>,
+    health: watch::Receiver<ControlHealth>,
 }
 
 impl ControlThreadHandle {
+    pub fn get_health(&self) -> ControlHealth {
+        *self.health.borrow()
+    }
+
+    /// Notified at each change of the health.
+    pub fn subscribe_to_health(&self) -> watch::Receiver<ControlHealth> {
+        self.health.clone()
+    }
+
     pub async fn stop(self) {
-        self.stop_sender
-            .send(())
-            .expect("couldn't stop control thread"); // ask for stop
+        // the control thread may have already stopped the robots (panic, fatal error)
+        let _ = self.stop_sender.send(()); // ask for stop
         self.handle
             .await
             .expect("failed to stop control loop thread!"); // wait done stopping
     }
 }
 
-pub fn launch_control_thread<E: Debug>(
+/// Stops the robots without waiting if the control thread is dropped before closing its controller
+/// (e.g. the runtime shuts down because the main task panicked).
+struct StopOnDrop<C, E>
+where
+    C: RobotController<HashMap<RobotId, RobotFeedback>, E>,
+    E: ControllerError,
+{
+    controller: Option<C>,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<C, E> Drop for StopOnDrop<C, E>
+where
+    C: RobotController<HashMap<RobotId, RobotFeedback>, E>,
+    E: ControllerError,
+{
+    fn drop(&mut self) {
+        if let Some(controller) = &mut self.controller {
+            if let Err(e) = controller.try_stop_all() {
+                error!(?e, "couldn't stop the robots");
+            }
+        }
+    }
+}
+
+pub fn launch_control_thread<E: ControllerError>(
     world: World,
-    mut controller: impl RobotController<HashMap<RobotId, RobotFeedback>, E> + Send + 'static,
+    controller: impl RobotController<HashMap<RobotId, RobotFeedback>, E> + Send + 'static,
 ) -> ControlThreadHandle {
     let (stop_sender, stop_receiver) = oneshot::channel();
+    let (health_sender, health) = watch::channel(ControlHealth::Healthy);
     let handle = tokio::spawn(async move {
+        let mut guard = StopOnDrop {
+            controller: Some(controller),
+            _error: PhantomData,
+        };
+        let controller = guard
+            .controller
+            .as_mut()
+            .expect("the guard holds the controller until it's closed");
+        // whatever happens, the robots are stopped before the thread ends
         select! {
-            _ = control_loop(world, &mut controller) => {}
+            r = AssertUnwindSafe(control_loop(world, controller, &health_sender)).catch_unwind() => {
+                match r {
+                    Ok(e) => error!(?e, "fatal controller error"),
+                    Err(_) => error!("control loop panicked"),
+                }
+            }
+            // also when the handle is dropped
             _ = stop_receiver => {
                 info!("control thread received stop signal")
             }
         };
+        set_health(&health_sender, ControlHealth::Stopped);
 
-        controller.close().await.expect("couldn't close controller");
+        let controller = guard
+            .controller
+            .take()
+            .expect("the guard holds the controller until it's closed");
+        if let Err(e) = controller.close().await {
+            error!(?e, "couldn't stop the robots");
+        }
     });
     ControlThreadHandle {
         stop_sender,
         handle,
+        health,
     }
 }
 
-pub async fn update_world_with_vision_forever(mut world: World, real: bool) {
-    let mut vision = Vision::new(None, None, real);
-    let mut ball_drawing = viewer::start_drawing(ViewerObject::Point {
-        color: "orange",
-        pos: world.ball.get_pos(),
-    });
-    let update_notifier = world.get_update_notifier();
+fn update_world_with_detection(
+    world: &World,
+    detection: FusedFrame,
+    ball_tracker: &mut BallTracker,
+    ball_drawing: &mut ViewerObjectGuard,
+) {
+    let mut ally_team = world.team.lock().unwrap_ignore_poison();
+    let mut ennemy_team = world.ennemies.lock().unwrap_ignore_poison();
+    let ball = world.ball.clone();
+    let detection_time = detection.t_capture;
+    if let Some((pos, vel)) = ball_tracker.update(&detection.balls, detection_time) {
+        ball.set_pos(pos);
+        ball.set_vel(vel);
+        // the tracker keeps predicting the ball when it's not detected
+        ball.set_last_update(ball_tracker.get_last_accepted_t().unwrap_or(detection_time));
+        ball_drawing.update(ViewerObject::Point {
+            color: "orange",
+            pos,
+        });
+    }
+
+    let (allies, ennemies) = match world.team_color {
+        TeamColor::Blue => (detection.robots_blue, detection.robots_yellow),
+        TeamColor::Yellow => (detection.robots_yellow, detection.robots_blue),
+    };
+    for ally_detection in allies {
+        let rid = ally_detection.robot_id() as u8;
+        if ally_team.get_mut(&rid).is_none() {
+            debug!("added ally {} to the team!", rid);
+            ally_team.insert(rid, world.new_ally(rid));
+        }
+        // SAFETY: if the robot wasn't present, we inserted it & we hold the lock. Therefore it MUST be in the map
+        let r = ally_team
+            .get_mut(&rid)
+            .expect("pre inserted robot MUST be present");
+        r.update_from_packet(ally_detection, &ball, detection_time);
+    }
+
+    for ennemy_detection in ennemies {
+        let rid = ennemy_detection.robot_id() as u8;
+        if ennemy_team.get_mut(&rid).is_none() {
+            debug!("added ennemy {} to the ennemies!", rid);
+            let r = EnnemyRobot::default_with_id(rid, world.team_color.opposite());
+            ennemy_team.insert(rid, r);
+        }
+        // SAFETY: if the robot wasn't present, we inserted it & we hold the lock. Therefore it MUST be in the map
+        let r = ennemy_team
+            .get_mut(&rid)
+            .expect("pre inserted robot MUST be present");
+        r.update_from_packet(ennemy_detection, &ball, detection_time);
+    }
+}
+
+/// Fuses the detection frames of the cameras, filters them and updates the world with the result.
+pub(crate) struct DetectionPipeline {
+    // each camera sends its own frame, they're merged before being used
+    camera_fusion: CameraFusion,
+    ball_tracker: BallTracker,
+    ball_drawing: ViewerObjectGuard,
+}
+
+impl DetectionPipeline {
+    pub(crate) fn new(world: &World) -> Self {
+        Self {
+            camera_fusion: CameraFusion::new(),
+            ball_tracker: BallTracker::new(),
+            ball_drawing: viewer::start_drawing(ViewerObject::Point {
+                color: "orange",
+                pos: world.ball.get_pos(),
+            }),
+        }
+    }
+
+    /// Adds the frame of a camera, the world is updated once the frames of all the cameras are received.
+    pub(crate) fn add_frame(&mut self, world: &World, frame: SslDetectionFrame) {
+        for fused_frame in self.camera_fusion.add_frame(frame) {
+            self.update_world(world, fused_frame);
+        }
+    }
+
+    /// Updates the world with the frames received so far, without waiting for the other cameras.
+    pub(crate) fn flush(&mut self, world: &World) {
+        if let Some(fused_frame) = self.camera_fusion.flush() {
+            self.update_world(world, fused_frame);
+        }
+    }
+
+    fn update_world(&mut self, world: &World, fused_frame: FusedFrame) {
+        let t_capture = fused_frame.t_capture;
+        update_world_with_detection(
+            world,
+            fused_frame,
+            &mut self.ball_tracker,
+            &mut self.ball_drawing,
+        );
+        world.update_visibility(t_capture);
+        for robot in world.team.lock().unwrap_ignore_poison().values() {
+            self.camera_fusion.set_robot_vel(
+                robot.get_color(),
+                robot.get_id() as u32,
+                robot.get_vel(),
+            );
+        }
+        for robot in world.ennemies.lock().unwrap_ignore_poison().values() {
+            self.camera_fusion.set_robot_vel(
+                robot.get_color(),
+                robot.get_id() as u32,
+                robot.get_vel(),
+            );
+        }
+        world.get_update_notifier().notify_waiters();
+    }
+}
+
+pub async fn update_world_with_vision_forever(mut world: World, mut vision: Vision) {
+    let mut detection_pipeline = DetectionPipeline::new(&world);
     loop {
         while let Ok(packet) = vision.receive().await {
-            let mut ally_team = world.team.lock().unwrap_ignore_poison();
-            let mut ennemy_team = world.ennemies.lock().unwrap_ignore_poison();
-            let ball = world.ball.clone();
             if let Some(detection) = packet.detection {
-                // println!("NEW CAM PACKET!");
-                let detection_time = detection.t_capture;
-                if let Some(ball_detection) = detection.balls.first() {
-                    let detected_pos = Point2::new(
-                        ball_detection.x as f64 / DETECTION_SCALING_FACTOR,
-                        ball_detection.y as f64 / DETECTION_SCALING_FACTOR,
-                    );
-                    if let Some(last_t) = ball.get_last_update() {
-                        let dt = detection_time - last_t;
-                        ball.set_vel((detected_pos - ball.get_pos()) / dt);
-                    }
-                    // println!("{:?}", detected_pos);
-                    ball.set_pos(detected_pos);
-                    ball_drawing.update(ViewerObject::Point {
-                        color: "orange",
-                        pos: detected_pos,
-                    });
-                }
+                detection_pipeline.add_frame(&world, detection);
+            }
+            if let Some(geometry) = packet.geometry {
+                world.update_from_geometry(geometry);
+            }
+        }
+    }
+}
 
-                let (allies, ennemies) = match world.team_color {
-                    TeamColor::Blue => (detection.robots_blue, detection.robots_yellow),
-                    TeamColor::Yellow => (detection.robots_yellow, detection.robots_blue),
-                };
-                for ally_detection in allies {
-                    let rid = ally_detection.robot_id() as u8;
-                    if ally_team.get_mut(&rid).is_none() {
-                        debug!("added ally {} to the team!", rid);
-                        let r = AllyRobot::default_with_id(rid, world.team_color);
-                        ally_team.insert(rid, r);
-                    }
-                    // SAFETY: if the robot wasn't present, we inserted it & we hold the lock. Therefore it MUST be in the map
-                    let r = ally_team
-                        .get_mut(&rid)
-                        .expect("pre inserted robot MUST be present");
-                    r.update_from_packet(ally_detection, &ball, detection_time);
-                }
+/// Loses the robots & the ball which aren't detected anymore, even if the vision stops sending frames.
+pub async fn update_visibility_forever(world: World) {
+    let mut interval = tokio::time::interval(VISIBILITY_CHECK_PERIOD);
+    loop {
+        interval.tick().await;
+        world.update_visibility_from_clock();
+    }
+}
+
+/// Keeps `world`'s field, cameras & ball models up to date, ignoring the detections.
+/// The league trackers don't send the geometry, it's needed alongside `update_world_with_tracked_vision_forever`.
+pub async fn update_world_with_geometry_forever(mut world: World, mut vision: Vision) {
+    loop {
+        while let Ok(packet) = vision.receive().await {
+            if let Some(geometry) = packet.geometry {
+                world.update_from_geometry(geometry);
+            }
+        }
+    }
+}
+
+fn team_color_from_tracked(color: tracked_vision_packet::TeamColor) -> Option<TeamColor> {
+    match color {
+        tracked_vision_packet::TeamColor::Blue => Some(TeamColor::Blue),
+        tracked_vision_packet::TeamColor::Yellow => Some(TeamColor::Yellow),
+        tracked_vision_packet::TeamColor::Unknown => None,
+    }
+}
+
+fn update_world_with_tracked_frame(world: &World, frame: TrackedFrame) {
+    let mut ally_team = world.team.lock().unwrap_ignore_poison();
+    let mut ennemy_team = world.ennemies.lock().unwrap_ignore_poison();
+    let timestamp = frame.timestamp;
+
+    // the first ball is the primary one
+    if let Some(tracked_ball) = frame.balls.first() {
+        let ball = &world.ball;
+        ball.set_pos(Point2::new(
+            tracked_ball.pos.x as f64,
+            tracked_ball.pos.y as f64,
+        ));
+        if let Some(vel) = tracked_ball.vel {
+            ball.set_vel(Vec2::new(vel.x as f64, vel.y as f64));
+        }
+        ball.set_last_update(timestamp);
+    }
+    world.ball.set_kick(frame.kicked_ball.map(|kick| BallKick {
+        start_pos: Point2::new(kick.pos.x as f64, kick.pos.y as f64),
+        start_vel: Vec2::new(kick.vel.x as f64, kick.vel.y as f64),
+        start_vel_z: kick.vel.z as f64,
+        start_time: kick.start_timestamp,
+        stop_time: kick.stop_timestamp,
+        stop_pos: kick.stop_pos.map(|p| Point2::new(p.x as f64, p.y as f64)),
+        kicker: kick.robot_id.and_then(|rid| {
+            team_color_from_tracked(rid.team_color()).map(|color| (color, rid.id as RobotId))
+        }),
+    }));
 
-                for ennemy_detection in ennemies {
-                    let rid = ennemy_detection.robot_id() as u8;
-                    if ennemy_team.get_mut(&rid).is_none() {
-                        debug!("added ennemy {} to the ennemies!", rid);
-                        let r = EnnemyRobot::default_with_id(rid, world.team_color.opposite());
-                        ennemy_team.insert(rid, r);
+    for tracked_robot in frame.robots {
+        let rid = tracked_robot.robot_id.id as RobotId;
+        match team_color_from_tracked(tracked_robot.robot_id.team_color()) {
+            Some(color) if color == world.team_color => ally_team
+                .entry(rid)
+                .or_insert_with(|| {
+                    debug!("added ally {} to the team!", rid);
+                    world.new_ally(rid)
+                })
+                .update_from_tracked_packet(tracked_robot, timestamp),
+            Some(color) => ennemy_team
+                .entry(rid)
+                .or_insert_with(|| {
+                    debug!("added ennemy {} to the ennemies!", rid);
+                    EnnemyRobot::default_with_id(rid, color)
+                })
+                .update_from_tracked_packet(tracked_robot, timestamp),
+            None => warn!(rid, "tracked robot without team color"),
+        }
+    }
+}
+
+/// Same as `update_world_with_vision_forever`, but using the frames of a league tracker instead of our own filters.
+/// The geometry isn't part of the tracked frames, run `update_world_with_geometry_forever` too.
+/// When multiple trackers are sending on the network, only the frames of the one named `source_name` are used
+/// (or of any one if `source_name` is `None`), see `TrackerSelection`.
+pub async fn update_world_with_tracked_vision_forever(
+    world: World,
+    mut tracked_vision: TrackedVision,
+    source_name: Option<String>,
+) {
+    let mut selection = TrackerSelection::new(source_name);
+    let update_notifier = world.get_update_notifier();
+    loop {
+        let packet = match tracked_vision.receive().await {
+            Ok(packet) => packet,
+            Err(e) => {
+                warn!(?e, "couldn't receive tracked vision packet");
+                continue;
+            }
+        };
+
+        if !selection.accepts(&packet, tokio::time::Instant::now()) {
+            continue;
+        }
+
+        if let Some(frame) = packet.tracked_frame {
+            let timestamp = frame.timestamp;
+            update_world_with_tracked_frame(&world, frame);
+            world.update_visibility(timestamp);
+            update_notifier.notify_waiters();
+        }
+    }
+}
+
+/// Keeps `world`'s game state in sync with the commands sent by the game controller.
+pub async fn update_world_with_game_controller_forever(world: World, mut gc: GameController) {
+    let mut last_command_counter = None;
+    let mut ball_pos_at_last_command = world.ball.get_pos();
+    loop {
+        select! {
+            r = gc.receive() => {
+                let referee = match r {
+                    Ok(referee) => referee,
+                    Err(e) => {
+                        warn!(?e, "couldn't receive referee packet");
+                        continue;
                     }
-                    // SAFETY: if the robot wasn't present, we inserted it & we hold the lock. Therefore it MUST be in the map
-                    let r = ennemy_team
-                        .get_mut(&rid)
-                        .expect("pre inserted robot MUST be present");
-                    r.update_from_packet(ennemy_detection, &ball, detection_time);
+                };
+                // the game controller repeats the current command in every packet
+                if last_command_counter != Some(referee.command_counter) {
+                    last_command_counter = Some(referee.command_counter);
+                    ball_pos_at_last_command = world.ball.get_pos();
+                    world.apply_game_event(GameEvent::RefereeCommand(referee.command()));
                 }
-                update_notifier.notify_waiters();
+                if world.get_game_state().is_waiting_for_ball_to_move()
+                    && referee.current_action_time_remaining.is_some_and(|t| t < 0)
+                {
+                    world.apply_game_event(GameEvent::ActionTimedOut);
+                }
+                world.set_last_referee(referee);
             }
-            if let Some(geometry) = packet.geometry {
-                world.field.update_from_packet(geometry.field);
+            _ = world.next_update() => {
+                if world.get_game_state().is_waiting_for_ball_to_move()
+                    && world.ball.distance_to(&ball_pos_at_last_command) > BALL_MOVED_DISTANCE
+                {
+                    world.apply_game_event(GameEvent::BallMoved);
+                }
             }
         }
     }
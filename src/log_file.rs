@@ -0,0 +1,298 @@
+//! SSL log files, the format of ssl-logtools & ssl-log-player (and of the league's official match logs).
+//!
+//! A log file starts with the `SSL_LOG_FILE` header and its version, then holds messages,
+//! each made of (big endian):
+//! - the receive timestamp in [ns] since the UNIX epoch (i64)
+//! - the message type (i32), see `LogMessageType`
+//! - the size of the protobuf message (i32)
+//! - the protobuf message
+//!
+//! Files whose name ends with `.gz` are gzip-compressed when written,
+//! compressed files are detected when read.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{bufread::MultiGzDecoder, write::GzEncoder, Compression};
+use prost::{DecodeError, Message};
+use tokio::select;
+use tracing::{info, warn};
+
+use crate::{
+    game_controller::GameController,
+    league_protocols::{
+        game_controller_packet::Referee, tracked_vision_packet::TrackerWrapperPacket,
+        vision_packet::SslWrapperPacket,
+    },
+    tracked_vision::TrackedVision,
+    vision::Vision,
+};
+
+pub const LOG_FILE_HEADER: &[u8; 12] = b"SSL_LOG_FILE";
+pub const LOG_FILE_VERSION: i32 = 1;
+
+/// first bytes of gzip-compressed files
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug)]
+pub enum LogFileError {
+    IoError(io::Error),
+    NotALogFile,
+    UnsupportedVersion(i32),
+    DecodeError(DecodeError),
+}
+
+/// Type of a message of a log file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogMessageType {
+    Blank = 0,
+    Unknown = 1,
+    SslVision2010 = 2,
+    SslRefbox2013 = 3,
+    SslVision2014 = 4,
+    SslVisionTracker2020 = 5,
+    SslIndex2021 = 6,
+}
+
+impl LogMessageType {
+    pub fn from_i32(message_type: i32) -> Option<Self> {
+        match message_type {
+            0 => Some(Self::Blank),
+            1 => Some(Self::Unknown),
+            2 => Some(Self::SslVision2010),
+            3 => Some(Self::SslRefbox2013),
+            4 => Some(Self::SslVision2014),
+            5 => Some(Self::SslVisionTracker2020),
+            6 => Some(Self::SslIndex2021),
+            _ => None,
+        }
+    }
+}
+
+/// A typed message of a log file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogMessage {
+    Vision(SslWrapperPacket),
+    Referee(Referee),
+    Tracker(TrackerWrapperPacket),
+}
+
+impl LogMessage {
+    pub fn get_type(&self) -> LogMessageType {
+        match self {
+            LogMessage::Vision(_) => LogMessageType::SslVision2014,
+            LogMessage::Referee(_) => LogMessageType::SslRefbox2013,
+            LogMessage::Tracker(_) => LogMessageType::SslVisionTracker2020,
+        }
+    }
+
+    fn encode_to_vec(&self) -> Vec<u8> {
+        match self {
+            LogMessage::Vision(packet) => packet.encode_to_vec(),
+            LogMessage::Referee(packet) => packet.encode_to_vec(),
+            LogMessage::Tracker(packet) => packet.encode_to_vec(),
+        }
+    }
+}
+
+/// Nanoseconds since the UNIX epoch, the timestamps of the log files.
+pub fn timestamp_ns(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as i64)
+        .unwrap_or(0)
+}
+
+/// Writes messages in the log file format.
+pub struct LogWriter<W: Write> {
+    writer: W,
+}
+
+impl LogWriter<Box<dyn Write + Send>> {
+    /// Creates the log file at `path`, gzip-compressed if the name ends with `.gz`.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = BufWriter::new(File::create(path)?);
+        let writer: Box<dyn Write + Send> = if path.extension().is_some_and(|e| e == "gz") {
+            Box::new(GzEncoder::new(file, Compression::default()))
+        } else {
+            Box::new(file)
+        };
+        Self::new(writer)
+    }
+}
+
+impl<W: Write> LogWriter<W> {
+    /// Writes the header.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(LOG_FILE_HEADER)?;
+        writer.write_all(&LOG_FILE_VERSION.to_be_bytes())?;
+        Ok(Self { writer })
+    }
+
+    /// `timestamp` in [ns] since the UNIX epoch, see `timestamp_ns`.
+    pub fn write(&mut self, timestamp: i64, message: &LogMessage) -> io::Result<()> {
+        let data = message.encode_to_vec();
+        self.writer.write_all(&timestamp.to_be_bytes())?;
+        self.writer
+            .write_all(&(message.get_type() as i32).to_be_bytes())?;
+        self.writer.write_all(&(data.len() as i32).to_be_bytes())?;
+        self.writer.write_all(&data)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// The underlying writer, e.g. to finish a gzip stream.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// A message of a log file, not decoded yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    /// receive time in [ns] since the UNIX epoch
+    pub timestamp: i64,
+    pub message_type: i32,
+    pub data: Vec<u8>,
+}
+
+impl LogRecord {
+    /// `None` for the types without a `LogMessage` (blank, unknown, old vision & index messages).
+    pub fn decode(&self) -> Result<Option<LogMessage>, DecodeError> {
+        let data = self.data.as_slice();
+        Ok(match LogMessageType::from_i32(self.message_type) {
+            Some(LogMessageType::SslVision2014) => {
+                Some(LogMessage::Vision(SslWrapperPacket::decode(data)?))
+            }
+            Some(LogMessageType::SslRefbox2013) => {
+                Some(LogMessage::Referee(Referee::decode(data)?))
+            }
+            Some(LogMessageType::SslVisionTracker2020) => {
+                Some(LogMessage::Tracker(TrackerWrapperPacket::decode(data)?))
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Reads the messages of a log file.
+pub struct LogReader<R: Read> {
+    reader: R,
+}
+
+impl LogReader<Box<dyn Read + Send>> {
+    /// Opens the log file at `path`, compressed or not.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, LogFileError> {
+        let mut file = BufReader::new(File::open(path).map_err(LogFileError::IoError)?);
+        let is_compressed = file
+            .fill_buf()
+            .map_err(LogFileError::IoError)?
+            .starts_with(&GZIP_MAGIC);
+        let reader: Box<dyn Read + Send> = if is_compressed {
+            Box::new(BufReader::new(MultiGzDecoder::new(file)))
+        } else {
+            Box::new(file)
+        };
+        Self::new(reader)
+    }
+}
+
+impl<R: Read> LogReader<R> {
+    /// Checks the header.
+    pub fn new(mut reader: R) -> Result<Self, LogFileError> {
+        let mut header = [0; 12];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| LogFileError::NotALogFile)?;
+        if &header != LOG_FILE_HEADER {
+            return Err(LogFileError::NotALogFile);
+        }
+        let version = read_i32(&mut reader).map_err(LogFileError::IoError)?;
+        if version != LOG_FILE_VERSION {
+            return Err(LogFileError::UnsupportedVersion(version));
+        }
+        Ok(Self { reader })
+    }
+
+    /// The next message, `None` at the end of the file.
+    pub fn read_record(&mut self) -> Result<Option<LogRecord>, LogFileError> {
+        match self.read_record_fields() {
+            Ok(record) => Ok(Some(record)),
+            // the end of the file, or a truncated last message (e.g. an interrupted recording)
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(LogFileError::IoError(e)),
+        }
+    }
+
+    fn read_record_fields(&mut self) -> io::Result<LogRecord> {
+        let mut timestamp = [0; 8];
+        self.reader.read_exact(&mut timestamp)?;
+        let message_type = read_i32(&mut self.reader)?;
+        let size = read_i32(&mut self.reader)?;
+        let mut data = vec![0; size.max(0) as usize];
+        self.reader.read_exact(&mut data)?;
+        Ok(LogRecord {
+            timestamp: i64::from_be_bytes(timestamp),
+            message_type,
+            data,
+        })
+    }
+
+    /// The next decoded message with its timestamp, skipping the messages without a `LogMessage`.
+    pub fn read(&mut self) -> Result<Option<(i64, LogMessage)>, LogFileError> {
+        while let Some(record) = self.read_record()? {
+            if let Some(message) = record.decode().map_err(LogFileError::DecodeError)? {
+                return Ok(Some((record.timestamp, message)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn read_i32(reader: &mut impl Read) -> io::Result<i32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_be_bytes(bytes))
+}
+
+/// Records the vision (or tracker) & referee messages with their receive time, until writing fails.
+/// The log is flushed at each referee message (i.e. about every 100ms during a game).
+pub async fn record_forever<W: Write>(
+    mut writer: LogWriter<W>,
+    mut vision: Option<Vision>,
+    mut tracked_vision: Option<TrackedVision>,
+    mut gc: GameController,
+) -> io::Error {
+    info!("recording");
+    loop {
+        let received = select! {
+            received = async { vision.as_mut().expect("guarded by the select").receive().await }, if vision.is_some() => {
+                received.map(LogMessage::Vision)
+            }
+            received = async { tracked_vision.as_mut().expect("guarded by the select").receive().await }, if tracked_vision.is_some() => {
+                received.map(LogMessage::Tracker)
+            }
+            received = gc.receive() => received.map(LogMessage::Referee),
+        };
+        let message = match received {
+            Ok(message) => message,
+            Err(e) => {
+                warn!(?e, "couldn't receive message to record");
+                continue;
+            }
+        };
+        let is_referee = matches!(message, LogMessage::Referee(_));
+        if let Err(e) = writer
+            .write(timestamp_ns(SystemTime::now()), &message)
+            .and_then(|_| if is_referee { writer.flush() } else { Ok(()) })
+        {
+            return e;
+        }
+    }
+}
@@ -1,241 +1,118 @@
+use clap::Parser;
 use crabe_async::{
-    actions::{backwards_strike, do_square_rrt},
-    controllers::sim_controller::SimRobotController,
+    actions::{backwards_strike, do_square_rrt, place_ball},
+    config::Config,
+    controllers::{real_controller::RealRobotController, sim_controller::SimRobotController},
     game_controller::GameController,
-    launch_control_thread, update_world_with_vision_forever, viewer,
-    world::{TeamColor, World},
+    game_state::{GameState, RunningState, StoppedState},
+    launch_control_thread,
+    log_file::{record_forever, LogWriter},
+    math::Vec2,
+    replay::{check_speed, LogReplay, ReplayMode},
+    testing::simulator::{Simulator, SimulatorServer},
+    tracked_vision::TrackedVision,
+    update_visibility_forever, update_world_with_game_controller_forever,
+    update_world_with_geometry_forever, update_world_with_tracked_vision_forever,
+    update_world_with_vision_forever, viewer,
+    vision::Vision,
+    world::{FieldSide, TeamColor, World},
 };
-use std::{str::FromStr, time::Duration};
-use tokio::{join, select, time::sleep};
-use tracing::info;
+use std::{future::pending, net::Ipv4Addr, path::PathBuf, str::FromStr, time::Duration};
+use tokio::{join, pin, select, time::sleep};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 
-// #[derive(Debug, Clone, Copy)]
-// enum HaltedState {
-//     Halt,
-//     Timeout,
-// }
+/// robots of each team on the field of the built-in simulator
+const LOCAL_SIMULATOR_ROBOTS_PER_TEAM: u32 = 6;
 
-// #[derive(Debug, Clone, Copy)]
-// enum StoppedState {
-//     Stop,
-//     PrepareKickoff,
-//     BallPlacementUs,
-//     BallPlacementThem,
-//     PreparePenalty,
-// }
-
-// #[derive(Debug, Clone, Copy)]
-// enum RunningState {
-//     Kickoff,
-//     FreeKickUs,
-//     FreeKickThem,
-//     Penalty,
-//     Run,
-// }
-
-// #[derive(Debug, Clone, Copy)]
-// enum GameState {
-//     Halted(HaltedState),
-//     Stopped(StoppedState),
-//     Running(RunningState),
-// }
-
-// impl GameState {
-//     pub fn update(self, event: GameEvent, color: TeamColor) -> Self {
-//         match (self, event, color) {
-//             // (from any state) Halt -> Halt
-//             (_, GameEvent::RefereeCommand(Command::Halt), _) => {
-//                 GameState::Halted(HaltedState::Halt)
-//             }
-//             // Halted
-//             (
-//                 GameState::Halted(HaltedState::Halt | HaltedState::Timeout),
-//                 GameEvent::RefereeCommand(Command::Stop),
-//                 _,
-//             ) => GameState::Stopped(StoppedState::Stop),
-//             // (
-//             //     GameState::Stopped(StoppedState::Stop),
-//             //     GameEvent::RefereeCommand(Command::Timeout),
-//             // ) => GameState::Halted(HaltedState::Timeout),
-//             // Stopped
-//             // (
-//             //     GameState::Stopped(StoppedState::Stop),
-//             //     GameEvent::RefereeCommand(Command::PrepareKickoff),
-//             // ) => GameState::Stopped(StoppedState::PrepareKickoff),
-//             (
-//                 GameState::Stopped(StoppedState::Stop),
-//                 GameEvent::RefereeCommand(Command::BallPlacementBlue),
-//                 TeamColor::Blue,
-//             ) => GameState::Stopped(StoppedState::BallPlacementUs),
-//             (
-//                 GameState::Stopped(StoppedState::Stop),
-//                 GameEvent::RefereeCommand(Command::BallPlacementBlue),
-//                 TeamColor::Yellow,
-//             ) => GameState::Stopped(StoppedState::BallPlacementThem),
-//             (
-//                 GameState::Stopped(StoppedState::Stop),
-//                 GameEvent::RefereeCommand(Command::BallPlacementYellow),
-//                 TeamColor::Blue,
-//             ) => GameState::Stopped(StoppedState::BallPlacementThem),
-//             (
-//                 GameState::Stopped(StoppedState::Stop),
-//                 GameEvent::RefereeCommand(Command::BallPlacementYellow),
-//                 TeamColor::Yellow,
-//             ) => GameState::Stopped(StoppedState::BallPlacementUs),
-//             // (
-//             //     GameState::Stopped(StoppedState::Stop),
-//             //     GameEvent::RefereeCommand(Command::PreparePenalty),
-//             // ) => GameState::Stopped(StoppedState::PreparePenalty),
-//             // FREE KICKS
-//             (
-//                 GameState::Stopped(StoppedState::Stop),
-//                 GameEvent::RefereeCommand(Command::DirectFreeBlue),
-//                 TeamColor::Blue,
-//             ) => GameState::Running(RunningState::FreeKickUs),
-//             (
-//                 GameState::Stopped(StoppedState::Stop),
-//                 GameEvent::RefereeCommand(Command::DirectFreeYellow),
-//                 TeamColor::Blue,
-//             ) => GameState::Running(RunningState::FreeKickThem),
-//             (
-//                 GameState::Stopped(StoppedState::Stop),
-//                 GameEvent::RefereeCommand(Command::DirectFreeBlue),
-//                 TeamColor::Yellow,
-//             ) => GameState::Running(RunningState::FreeKickThem),
-//             (
-//                 GameState::Stopped(StoppedState::Stop),
-//                 GameEvent::RefereeCommand(Command::DirectFreeYellow),
-//                 TeamColor::Yellow,
-//             ) => GameState::Running(RunningState::FreeKickUs),
-
-//             (
-//                 GameState::Stopped(StoppedState::Stop),
-//                 GameEvent::RefereeCommand(Command::ForceStart),
-//                 _,
-//             ) => GameState::Running(RunningState::Run),
-//             (
-//                 GameState::Stopped(StoppedState::PrepareKickoff),
-//                 GameEvent::RefereeCommand(Command::NormalStart),
-//                 _,
-//             ) => GameState::Running(RunningState::Kickoff),
-//             (
-//                 GameState::Stopped(StoppedState::BallPlacementUs | StoppedState::BallPlacementThem),
-//                 GameEvent::RefereeCommand(Command::Stop),
-//                 _,
-//             ) => GameState::Stopped(StoppedState::Stop),
-//             // (
-//             //     GameState::Stopped(StoppedState::BallPlacement),
-//             //     GameEvent::RefereeCommand(Command::Continue),
-//             // ) => GameState::Running(RunningState::FreeKick),
-//             (
-//                 GameState::Stopped(StoppedState::PreparePenalty),
-//                 GameEvent::RefereeCommand(Command::NormalStart),
-//                 _,
-//             ) => GameState::Running(RunningState::Penalty),
-//             // Running
-//             // TODO: fix the AfterXSeconds(_)
-//             // (
-//             //     GameState::Running(RunningState::Kickoff),
-//             //     GameEvent::AfterXSeconds(_) | GameEvent::BallMoved,
-//             // ) => GameState::Running(RunningState::Run),
-//             // (
-//             //     GameState::Running(RunningState::FreeKick),
-//             //     GameEvent::AfterXSeconds(_) | GameEvent::BallMoved,
-//             // ) => GameState::Running(RunningState::Run),
-//             // (GameState::Running(RunningState::Run), GameEvent::Stop) => {
-//             //     GameState::Stopped(StoppedState::Stop)
-//             // }
-//             (s, e, _) => {
-//                 println!(
-//                     "[WARNING] unexpected game state and event combination ({:?}, {:?})",
-//                     s, e
-//                 );
-//                 s
-//             }
-//         }
-//     }
-// }
-
-// #[derive(Debug, Clone, Copy)]
-// enum GameEvent {
-//     RefereeCommand(Command),
-// }
-
-async fn play(world: World, _gc: GameController) {
-    // let state = GameState::Halted(HaltedState::Halt);
-
-    // let interval = tokio::time::interval(CONTROL_PERIOD);
-    // let start = Instant::now();
+/// Makes every ally robot stop moving (used when halted or stopped).
+fn stop_all_robots(world: &World) {
+    for robot in world.team.lock().unwrap().values() {
+        robot.set_target_vel(Vec2::zero());
+        robot.set_target_angular_vel(0.);
+        robot.disable_dribbler();
+    }
+}
 
-    // loop {
-    //     let referee_event = gc.receive().await.unwrap();
-    //     state = state.update(
-    //         GameEvent::RefereeCommand(referee_event.command()),
-    //         world.team_color,
-    //     );
-    //     println!(
-    //         "gc: received {:?}, transitionning to {:?}",
-    //         referee_event.command(),
-    //         state
-    //     );
+/// Plays until the game state changes.
+async fn play_state(world: &World, state: GameState) {
+    match state {
+        GameState::Stopped(StoppedState::BallPlacementUs) => {
+            let placer = world.get_closest_ally_to(world.ball.get_pos());
+            if let (Some(placer), Some(target)) = (placer, world.get_designated_position()) {
+                place_ball(world, &placer, &world.ball, &target).await;
+            }
+            stop_all_robots(world);
+        }
+        GameState::Running(RunningState::Run) => run(world).await,
+        GameState::Halted(_) | GameState::Stopped(_) | GameState::Running(_) => {
+            // TODO: write strategies for kickoffs, free kicks and penalties
+            stop_all_robots(world);
+        }
+    }
+    // nothing left to do in this state, wait for the next one
+    pending::<()>().await;
+}
 
-    //     match state {
-    //         GameState::Halted(halted_state) => todo!(),
-    //         GameState::Stopped(stopped_state) => match stopped_state {
-    //             StoppedState::Stop => todo!(),
-    //             StoppedState::PrepareKickoff => todo!(),
-    //             StoppedState::BallPlacementUs => {
-    //                 if let Some(p) = referee_event.designated_position {
-    //                     let p = Point2::new(p.x, p.y);
-    //                     place_ball(&world, &r0, &ball, &p).await;
-    //                 }
-    //             }
-    //             StoppedState::PreparePenalty => todo!(),
-    //             StoppedState::BallPlacementThem => todo!(),
-    //         },
-    //         GameState::Running(running_state) => todo!(),
-    //     }
-    // }
+async fn play(world: World) {
+    let game_state_notifier = world.get_game_state_notifier();
+    loop {
+        // listening before reading the state, so that a change in between isn't missed
+        let state_change = game_state_notifier.notified();
+        pin!(state_change);
+        state_change.as_mut().enable();
+        let state = world.get_game_state();
+        info!(?state, "playing");
+        select! {
+            _ = play_state(&world, state) => {}
+            _ = state_change => {}
+        }
+    }
+}
 
+async fn run(world: &World) {
     loop {
-        let r0 = if let Some(r0) = world.team.lock().unwrap().get(&3).cloned() {
+        let r0 = world.team.lock().unwrap().get(&3).cloned();
+        let r0 = if let Some(r0) = r0 {
             r0
         } else {
+            world.next_update().await;
             continue;
         };
-        let r1 = if let Some(r1) = world.team.lock().unwrap().get(&4).cloned() {
+        let r1 = world.team.lock().unwrap().get(&4).cloned();
+        let r1 = if let Some(r1) = r1 {
             r1
         } else {
+            world.next_update().await;
             continue;
         };
         // let r2 = world.team.lock().unwrap().get(&5).unwrap().clone();
         let ball = world.ball.clone();
 
         // let _ = r0
-        //     .goto(&world, &Point2::zero(), None, AvoidanceMode::None)
+        //     .goto(world, &Point2::zero(), None, AvoidanceMode::None)
         //     .await;
 
-        // keep(&world, &r0, &ball).await;
+        // keep(world, &r0, &ball).await;
 
         let (res1, _res2) = join!(
-            do_square_rrt(&world, &r0),
-            backwards_strike(&world, &r1, &ball)
+            do_square_rrt(world, &r0),
+            backwards_strike(world, &r1, &ball)
         );
         res1.expect("r3 couldn't do the square");
         // res2.expect("r1 couldn't do the square");
 
-        // if let Err(e) = do_square_rrt(&world, &r0).await {
+        // if let Err(e) = do_square_rrt(world, &r0).await {
         //     println!("{:?}", e);
         // }
 
-        // backwards_strike(&world, &r0, &ball).await;
+        // backwards_strike(world, &r0, &ball).await;
 
-        // three_attackers_attack(&world, &r1, &r0, &r2).await;
+        // three_attackers_attack(world, &r1, &r0, &r2).await;
 
         // let _ = r0
         //     .goto(
-        //         &world,
+        //         world,
         //         &|| {
         //             Point2::new(
         //                 start.elapsed().as_secs_f64().cos() * 1.0,
@@ -250,46 +127,274 @@ async fn play(world: World, _gc: GameController) {
     }
 }
 
+/// Coral, a Robocup SSL AI.
+///
+/// Options given on the command line override the ones from the config file.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// path to a TOML config file
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// our team color (blue or yellow)
+    #[arg(long)]
+    color: Option<TeamColor>,
+    /// control real robots
+    #[arg(long, conflicts_with = "sim")]
+    real: bool,
+    /// control simulated robots
+    #[arg(long)]
+    sim: bool,
+    /// half of the field where our goal is (positive or negative), until the game controller tells us
+    #[arg(long)]
+    field_side: Option<FieldSide>,
+    /// use a league tracker instead of our own vision filters
+    #[arg(long)]
+    tracked_vision: bool,
+    /// vision multicast group
+    #[arg(long)]
+    vision_ip: Option<Ipv4Addr>,
+    /// vision port (defaults depend on --real/--sim)
+    #[arg(long)]
+    vision_port: Option<u16>,
+    /// game controller multicast group
+    #[arg(long)]
+    gc_ip: Option<Ipv4Addr>,
+    /// game controller port
+    #[arg(long)]
+    gc_port: Option<u16>,
+    /// run the built-in simulator (implies --sim)
+    #[arg(long, conflicts_with = "real")]
+    local_sim: bool,
+    /// simulator ip
+    #[arg(long)]
+    sim_ip: Option<Ipv4Addr>,
+    /// port of the simulator's robot control for our team
+    #[arg(long)]
+    sim_port: Option<u16>,
+    /// base station ip (real robots)
+    #[arg(long)]
+    base_station_ip: Option<Ipv4Addr>,
+    /// base station port (real robots)
+    #[arg(long)]
+    base_station_port: Option<u16>,
+    /// interface on which the vision & game controller multicast groups are joined
+    #[arg(long)]
+    multicast_interface: Option<Ipv4Addr>,
+    /// port on which the viewer server listens
+    #[arg(long)]
+    viewer_port: Option<u16>,
+    /// record the vision & referee messages to this SSL log file (gzip-compressed if it ends with .gz)
+    #[arg(long)]
+    record: Option<PathBuf>,
+    /// replay this SSL log file instead of listening to the vision & game controller
+    #[arg(long)]
+    replay: Option<PathBuf>,
+    /// replay speed, 1 is real time
+    #[arg(long, value_parser = parse_replay_speed)]
+    replay_speed: Option<f64>,
+    /// replay a vision frame each time Enter is pressed
+    #[arg(long, conflicts_with = "replay_speed")]
+    replay_step: bool,
+    /// log filter, using the `RUST_LOG` syntax
+    #[arg(long)]
+    log_filter: Option<String>,
+}
+
+fn parse_replay_speed(s: &str) -> Result<f64, String> {
+    let speed = s.parse::<f64>().map_err(|e| e.to_string())?;
+    check_speed(speed).map_err(|e| e.to_string())
+}
+
+impl Cli {
+    /// loads the config file (if any) and applies the command line's overrides
+    fn into_config(self) -> Config {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path).expect("couldn't load the config file"),
+            None => Config::default(),
+        };
+        if let Some(color) = self.color {
+            config.color = color;
+        }
+        if self.real || self.sim || self.local_sim {
+            config.real = self.real;
+        }
+        config.simulator.local |= self.local_sim;
+        config.field_side = self.field_side.or(config.field_side);
+        config.tracked_vision.enabled |= self.tracked_vision;
+        config.vision.ip = self.vision_ip.or(config.vision.ip);
+        config.vision.port = self.vision_port.or(config.vision.port);
+        config.game_controller.ip = self.gc_ip.or(config.game_controller.ip);
+        config.game_controller.port = self.gc_port.or(config.game_controller.port);
+        config.simulator.ip = self.sim_ip.or(config.simulator.ip);
+        if let Some(port) = self.sim_port {
+            match config.color {
+                TeamColor::Blue => config.simulator.blue_port = Some(port),
+                TeamColor::Yellow => config.simulator.yellow_port = Some(port),
+            }
+        }
+        config.base_station.ip = self.base_station_ip.or(config.base_station.ip);
+        config.base_station.port = self.base_station_port.or(config.base_station.port);
+        config.network.multicast_interface = self
+            .multicast_interface
+            .or(config.network.multicast_interface);
+        config.viewer.port = self.viewer_port.or(config.viewer.port);
+        config.record = self.record.or(config.record);
+        config.replay.file = self.replay.or(config.replay.file);
+        config.replay.speed = self.replay_speed.or(config.replay.speed);
+        config.replay.step |= self.replay_step;
+        if let Some(log_filter) = self.log_filter {
+            config.log_filter = log_filter;
+        }
+        config
+    }
+}
+
 /// Simulation of a real control loop
 #[tokio::main]
 async fn main() {
+    let config = Cli::parse().into_config();
+
     tracing_subscriber::fmt()
         .with_env_filter(
-            // we only log >=warn OR >=info for viewer OR >=debug for this crate
-            EnvFilter::from_str("warn,crabe_async::viewer=info,crabe_async=debug")
-                .expect("couldn't parse log filter"),
+            EnvFilter::from_str(&config.log_filter).expect("couldn't parse log filter"),
         )
         .init();
 
-    // options which will come from cli
-    let color = TeamColor::Blue;
-    let real = false;
-
+    let color = config.color;
+    let real = config.real;
     info!("Starting up Coral (color: {:?}, real: {})", color, real);
+    debug!(?config, "loaded config");
 
-    let world = World::default_with_team_color(color);
-    let gc = GameController::new(None, None);
-    let controller = if real {
-        unimplemented!("didn't write real robots controller yet");
-    } else {
-        SimRobotController::new(color).await
+    let mut world = World::default_with_team_color(color);
+    world.fleet_specs = config.robots.clone();
+    if let Some(side) = config.field_side {
+        world.field.set_side(color, side);
+    }
+    let interface = config.network.multicast_interface;
+    let mut replay = config.replay.file.as_ref().map(|path| {
+        let mode = match config.replay.speed {
+            _ if config.replay.step => ReplayMode::Step,
+            Some(speed) => ReplayMode::Accelerated(speed),
+            None => ReplayMode::RealTime,
+        };
+        LogReplay::open(path, mode).expect("couldn't open the replayed log file")
+    });
+    if let Some(replay) = replay.as_ref().filter(|_| config.replay.step) {
+        let control = replay.control();
+        info!("replaying step by step, press Enter to replay the next vision frame");
+        // blocking reads, on their own thread
+        std::thread::spawn(move || {
+            for _ in std::io::stdin().lines() {
+                control.step();
+            }
+        });
+    }
+    let gc = match &mut replay {
+        Some(replay) => replay.game_controller(),
+        None => GameController::new(
+            config.game_controller.ip,
+            config.game_controller.port,
+            interface,
+        ),
     };
-    viewer::init().await;
+    viewer::init(config.viewer.port).await;
 
-    tokio::spawn(update_world_with_vision_forever(world.clone(), real));
-    let control_thread_handle = launch_control_thread(world.clone(), controller);
+    if !real && config.simulator.local {
+        let mut simulator = Simulator::new();
+        simulator.place_teams(LOCAL_SIMULATOR_ROBOTS_PER_TEAM);
+        let server = SimulatorServer::new(simulator, &config.simulator, &config.vision).await;
+        tokio::spawn(server.run_forever());
+    }
+
+    if let Some(path) = &config.record {
+        let writer = LogWriter::create(path).expect("couldn't create the log file");
+        // the recorder joins the multicast groups with its own sockets
+        // the vision is recorded with the tracked frames too, for its geometry
+        let vision = Vision::new(config.vision.ip, config.vision.port, interface, real);
+        let tracked_vision = config.tracked_vision.enabled.then(|| {
+            TrackedVision::new(
+                config.tracked_vision.ip,
+                config.tracked_vision.port,
+                interface,
+            )
+        });
+        let gc = GameController::new(
+            config.game_controller.ip,
+            config.game_controller.port,
+            interface,
+        );
+        info!(?path, "recording the match");
+        tokio::spawn(async move {
+            let e = record_forever(writer, Some(vision), tracked_vision, gc).await;
+            warn!(?e, "couldn't write the log file, recording stopped");
+        });
+    }
+
+    let vision = match &mut replay {
+        Some(replay) => replay.vision(),
+        None => Vision::new(config.vision.ip, config.vision.port, interface, real),
+    };
+    if config.tracked_vision.enabled {
+        let tracked_vision = match &mut replay {
+            Some(replay) => replay.tracked_vision(),
+            None => TrackedVision::new(
+                config.tracked_vision.ip,
+                config.tracked_vision.port,
+                interface,
+            ),
+        };
+        tokio::spawn(update_world_with_tracked_vision_forever(
+            world.clone(),
+            tracked_vision,
+            config.tracked_vision.source_name,
+        ));
+        // the trackers don't send the geometry (field size, defense areas..)
+        tokio::spawn(update_world_with_geometry_forever(world.clone(), vision));
+    } else {
+        tokio::spawn(update_world_with_vision_forever(world.clone(), vision));
+    }
+    if let Some(replay) = replay {
+        tokio::spawn(async move {
+            if let Err(e) = replay.run().await {
+                warn!(?e, "couldn't replay the log file");
+            }
+        });
+    }
+    tokio::spawn(update_world_with_game_controller_forever(world.clone(), gc));
+    // a replay's vision time doesn't follow the clock (paused, accelerated..)
+    if config.replay.file.is_none() {
+        tokio::spawn(update_visibility_forever(world.clone()));
+    }
+    let control_thread_handle = if real {
+        let controller =
+            RealRobotController::new(config.base_station.ip, config.base_station.port).await;
+        launch_control_thread(world.clone(), controller)
+    } else {
+        let controller = SimRobotController::new(
+            color,
+            config.simulator.ip,
+            config.simulator.get_robot_control_port(color),
+        )
+        .await;
+        launch_control_thread(world.clone(), controller)
+    };
 
     // await allies detection
     world.allies_detection().await;
 
-    // play until ctrl-c
+    // play until ctrl-c, in its own task so that a panicking strategy still stops the robots
+    let mut strategy = tokio::spawn(play(world));
     select! {
-        _ = play(world, gc) => {}
+        r = &mut strategy => {
+            error!(?r, "the strategy stopped");
+        }
         r = tokio::signal::ctrl_c() => {
             r.expect("failed to listen for event");
             info!("detected ctrl-c, stopping now!");
         }
     }
+    strategy.abort();
 
     control_thread_handle.stop().await;
 }
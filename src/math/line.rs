@@ -14,6 +14,14 @@ impl Line {
         Self { start, end }
     }
 
+    pub fn start(&self) -> Point2 {
+        self.start
+    }
+
+    pub fn end(&self) -> Point2 {
+        self.end
+    }
+
     pub fn closest_point_to(&self, point: Point2) -> Point2 {
         let line_direction = self.end - self.start;
         let point_direction = point - self.start;
@@ -20,3 +20,8 @@ pub fn angle_difference(alpha1: f64, alpha2: f64) -> f64 {
         d => d,
     }
 }
+
+/// wraps an angle in radians to [-PI, PI]
+pub fn wrap_angle(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
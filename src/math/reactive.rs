@@ -54,7 +54,7 @@ pub trait ReactivePoint2Ext: Reactive<Point2> + Sized {
         ReactivePoint::new(self, t)
     }
 
-    fn minus<'t, T: ReactiveVec2Ext>(&self, t: T) -> ReactivePoint<'_, Self, impl Reactive<Vec2>> {
+    fn minus<T: ReactiveVec2Ext>(&self, t: T) -> ReactivePoint<'_, Self, impl Reactive<Vec2>> {
         ReactivePoint::new(self, t.mul(-1.))
     }
 }
@@ -28,6 +28,27 @@ impl Rect {
         )
     }
 
+    pub fn contains(&self, p: Point2) -> bool {
+        (self.top_left.x..=self.bottom_right.x).contains(&p.x)
+            && (self.bottom_right.y..=self.top_left.y).contains(&p.y)
+    }
+
+    /// whether the whole circle of `radius` at `center` is inside
+    pub fn contains_circle(&self, center: Point2, radius: f64) -> bool {
+        self.top_left.x + radius <= center.x
+            && center.x + radius <= self.bottom_right.x
+            && self.bottom_right.y + radius <= center.y
+            && center.y + radius <= self.top_left.y
+    }
+
+    /// the same rect with `margin` added on every side (shrinks it if `margin` is negative)
+    pub fn grow(&self, margin: f64) -> Self {
+        Self::new(
+            Point2::new(self.top_left.x - margin, self.top_left.y + margin),
+            Point2::new(self.bottom_right.x + margin, self.bottom_right.y - margin),
+        )
+    }
+
     pub fn center(&self) -> Point2 {
         Point2::new(
             (self.top_left.x + self.bottom_right.x) / 2.,
@@ -36,6 +36,12 @@ impl Vec2 {
     pub fn dot(&self, rhs: Self) -> f64 {
         self.x * rhs.x + self.y * rhs.y
     }
+
+    /// rotated by `angle` in radians (counterclockwise)
+    pub fn rotated(&self, angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
 }
 
 impl Add for Vec2 {
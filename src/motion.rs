@@ -0,0 +1,565 @@
+//! Closed-loop motion control, between the skills and the `RobotController`.
+//!
+//! The skills give the robots a `MotionTarget` (see `AllyRobot::move_to`), the control loop then
+//! plans a trajectory to it (position & orientation, see `BangBangPose`) and tracks it on every
+//! `CONTROL_PERIOD`: the velocity of the trajectory is sent as feed-forward, corrected by a PI controller
+//! on the position error and a P controller on the orientation error.
+//!
+//! The vision shows the robots as they were a while ago: a command only shows up in the detections
+//! after the command-to-vision latency, which is measured for each robot. Before being compared to
+//! the trajectory, the detected state is predicted forward with the commands sent during that latency.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::{
+    math::{angle_difference, wrap_angle, Point2, Vec2},
+    trajectories::{
+        bangbang_pose::{BangBangPose, Pose, Twist},
+        Trajectory,
+    },
+    world::{AllyRobot, KinematicLimits, RobotId},
+    CONTROL_PERIOD,
+};
+
+/// proportional gain of the position feedback in [1/s]
+const KP: f64 = 3.;
+/// integral gain of the position feedback in [1/s^2]
+const KI: f64 = 1.;
+/// max accumulated position error in [m.s], against integral windup
+const MAX_INTEGRAL: f64 = 0.1;
+/// proportional gain of the orientation feedback in [1/s]
+const KP_ORIENTATION: f64 = 3.;
+/// deviation from the trajectory in [m] after which a new one is planned from the robot's state
+const REPLAN_DISTANCE: f64 = 0.2;
+/// deviation from the trajectory's orientation in [rad] after which a new one is planned
+const REPLAN_ANGLE: f64 = 0.3;
+/// a destination moved more than this in [m] gets a new trajectory
+const DESTINATION_MOVED_DISTANCE: f64 = 0.01;
+/// a target orientation changed more than this in [rad] gets a new trajectory
+const ORIENTATION_CHANGED_ANGLE: f64 = 0.01;
+
+/// latency assumed until it's measured
+pub const DEFAULT_LATENCY: Duration = Duration::from_millis(50);
+const MAX_LATENCY: Duration = Duration::from_millis(250);
+/// the latency is measured on the detections of the last second
+const LATENCY_WINDOW: Duration = Duration::from_secs(1);
+/// detections needed to measure the latency
+const MIN_LATENCY_SAMPLES: usize = 20;
+/// commanded speed variation in [m/s] needed to measure the latency, a still robot tells nothing
+const MIN_COMMAND_VARIATION: f64 = 0.3;
+/// weight of a new measurement in the latency estimate
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// Where the motion control drives a robot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionTarget {
+    pub destination: Point2,
+    /// in [rad], the orientation is free if `None`
+    pub orientation: Option<f64>,
+    /// in [m/s], the robot passes by its destination at this velocity instead of stopping there if it isn't zero
+    pub final_vel: Vec2,
+}
+
+/// A command sent to a robot, in the world's frame.
+#[derive(Debug, Clone, Copy)]
+struct SentCommand {
+    instant: Instant,
+    vel: Twist,
+}
+
+/// Measures the command-to-vision latency of a robot, by finding the delay which best
+/// matches its detected velocities to the velocities it was commanded.
+#[derive(Debug)]
+struct LatencyEstimator {
+    commands: VecDeque<SentCommand>,
+    /// instant at which the detections were received, with the detected velocity
+    detections: VecDeque<(Instant, Vec2)>,
+    latency: Duration,
+}
+
+impl LatencyEstimator {
+    fn new() -> Self {
+        Self {
+            commands: VecDeque::new(),
+            detections: VecDeque::new(),
+            latency: DEFAULT_LATENCY,
+        }
+    }
+
+    /// the last command sent at or before `instant`
+    fn command_at(&self, instant: Instant) -> Option<&SentCommand> {
+        let i = self.commands.partition_point(|c| c.instant <= instant);
+        i.checked_sub(1).map(|i| &self.commands[i])
+    }
+
+    fn add_command(&mut self, command: SentCommand) {
+        self.commands.push_back(command);
+        while self
+            .commands
+            .front()
+            .is_some_and(|c| command.instant - c.instant > MAX_LATENCY + LATENCY_WINDOW)
+        {
+            self.commands.pop_front();
+        }
+    }
+
+    fn add_detection(&mut self, instant: Instant, vel: Vec2) {
+        self.detections.push_back((instant, vel));
+        while self
+            .detections
+            .front()
+            .is_some_and(|(t, _)| instant - *t > LATENCY_WINDOW)
+        {
+            self.detections.pop_front();
+        }
+        if let Some(measured) = self.measure() {
+            let latency = self.latency.as_secs_f64();
+            self.latency = Duration::from_secs_f64(
+                latency + (measured.as_secs_f64() - latency) * LATENCY_SMOOTHING,
+            );
+        }
+    }
+
+    fn measure(&self) -> Option<Duration> {
+        if self.detections.len() < MIN_LATENCY_SAMPLES {
+            return None;
+        }
+        let speeds = self.commands.iter().map(|c| c.vel.vel.norm());
+        let variation = speeds.clone().fold(f64::MIN, f64::max) - speeds.fold(f64::MAX, f64::min);
+        if variation < MIN_COMMAND_VARIATION {
+            return None;
+        }
+
+        let oldest_command = self.commands.front()?.instant;
+        let mut best = None;
+        let mut delay = Duration::ZERO;
+        while delay <= MAX_LATENCY {
+            let errors = self
+                .detections
+                .iter()
+                .filter_map(|(t, vel)| {
+                    let commanded_at = t.checked_sub(delay).filter(|t| *t >= oldest_command)?;
+                    let command = self.command_at(commanded_at)?;
+                    Some((*vel - command.vel.vel).norm().powi(2))
+                })
+                .collect::<Vec<f64>>();
+            if errors.len() >= MIN_LATENCY_SAMPLES {
+                let error = errors.iter().sum::<f64>() / errors.len() as f64;
+                if best.is_none_or(|(_, best_error)| error < best_error) {
+                    best = Some((delay, error));
+                }
+            }
+            delay += CONTROL_PERIOD;
+        }
+        best.map(|(delay, _)| delay)
+    }
+}
+
+/// Predicted state of a robot, when the command sent now takes effect.
+#[derive(Debug, Clone, Copy)]
+struct PredictedState {
+    pose: Pose,
+    vel: Twist,
+}
+
+/// The trajectory a robot is following.
+#[derive(Debug, Clone, Copy)]
+struct Plan {
+    trajectory: BangBangPose,
+    start: Instant,
+    target: MotionTarget,
+    limits: KinematicLimits,
+}
+
+impl Plan {
+    /// whether the plan still leads to `target` from `state`, within `limits`
+    fn is_valid(
+        &self,
+        target: &MotionTarget,
+        limits: &KinematicLimits,
+        state: &PredictedState,
+        now: Instant,
+    ) -> bool {
+        let expected = self
+            .trajectory
+            .get_position((now - self.start).as_secs_f64());
+        let orientation_changed = match (self.target.orientation, target.orientation) {
+            (Some(planned), Some(wanted)) => {
+                angle_difference(planned, wanted).abs() > ORIENTATION_CHANGED_ANGLE
+            }
+            (None, None) => false,
+            _ => true,
+        };
+        self.limits == *limits
+            && (self.target.destination - target.destination).norm() < DESTINATION_MOVED_DISTANCE
+            && self.target.final_vel == target.final_vel
+            && !orientation_changed
+            && (expected.pos - state.pose.pos).norm() < REPLAN_DISTANCE
+            && angle_difference(expected.orientation, state.pose.orientation).abs() < REPLAN_ANGLE
+    }
+}
+
+#[derive(Debug)]
+struct RobotMotion {
+    latency: LatencyEstimator,
+    /// `last_update` of the last detection, with the instant it was received at
+    last_detection: Option<(f64, Instant)>,
+    plan: Option<Plan>,
+    /// accumulated position error in [m.s]
+    integral: Vec2,
+    last_tick: Option<Instant>,
+}
+
+impl RobotMotion {
+    fn new() -> Self {
+        Self {
+            latency: LatencyEstimator::new(),
+            last_detection: None,
+            plan: None,
+            integral: Vec2::zero(),
+            last_tick: None,
+        }
+    }
+
+    /// The detected state, moved by the commands which don't show in the detection yet.
+    fn predict(&self, robot: &AllyRobot, now: Instant) -> PredictedState {
+        let detected = PredictedState {
+            pose: Pose {
+                pos: robot.get_pos(),
+                orientation: robot.get_orientation(),
+            },
+            vel: Twist {
+                vel: robot.get_vel(),
+                angular_vel: robot.get_angular_vel(),
+            },
+        };
+        self.predict_from(detected, now)
+    }
+
+    /// `detected` moved by the commands sent since the last detection was captured, its orientation wrapped to [-PI, PI]
+    fn predict_from(&self, mut state: PredictedState, now: Instant) -> PredictedState {
+        let Some((_, received)) = self.last_detection else {
+            return state;
+        };
+        let Some(from) = received.checked_sub(self.latency.latency) else {
+            return state;
+        };
+        let commands = &self.latency.commands;
+        for (i, command) in commands.iter().enumerate() {
+            let end = commands.get(i + 1).map_or(now, |next| next.instant);
+            let start = command.instant.max(from);
+            if end <= start {
+                continue;
+            }
+            let dt = (end - start).as_secs_f64();
+            state.pose.pos += command.vel.vel * dt;
+            state.pose.orientation += command.vel.angular_vel * dt;
+            state.vel = command.vel;
+        }
+        state.pose.orientation = wrap_angle(state.pose.orientation);
+        state
+    }
+
+    fn plan(
+        &mut self,
+        state: &PredictedState,
+        target: MotionTarget,
+        limits: KinematicLimits,
+        now: Instant,
+    ) -> Plan {
+        self.integral = Vec2::zero();
+        let target_pose = Pose {
+            pos: target.destination,
+            // keeps its orientation if it's free
+            orientation: target.orientation.unwrap_or(state.pose.orientation),
+        };
+        let trajectory = if target.final_vel == Vec2::zero() {
+            BangBangPose::new(
+                state.pose,
+                state.vel,
+                target_pose,
+                limits.max_vel,
+                limits.get_trajectory_acc(),
+                limits.max_angular_vel,
+                limits.max_angular_acc,
+            )
+        } else {
+            BangBangPose::with_final_velocity(
+                state.pose,
+                state.vel,
+                target_pose,
+                target.final_vel,
+                limits.max_vel,
+                limits.get_trajectory_acc(),
+                limits.max_angular_vel,
+                limits.max_angular_acc,
+            )
+        };
+        *self.plan.insert(Plan {
+            trajectory,
+            start: now,
+            target,
+            limits,
+        })
+    }
+
+    /// The command in the world's frame which tracks the plan to `target`.
+    fn track(
+        &mut self,
+        state: &PredictedState,
+        target: MotionTarget,
+        limits: KinematicLimits,
+        now: Instant,
+    ) -> Twist {
+        let dt = self
+            .last_tick
+            .map_or(CONTROL_PERIOD, |t| now - t)
+            .as_secs_f64();
+        let plan = match self.plan {
+            Some(plan) if plan.is_valid(&target, &limits, state, now) => plan,
+            _ => self.plan(state, target, limits, now),
+        };
+
+        let t = (now - plan.start).as_secs_f64();
+        let expected = plan.trajectory.get_position(t);
+        let feed_forward = plan.trajectory.get_velocity(t);
+
+        let error = expected.pos - state.pose.pos;
+        self.integral = self.integral + error * dt;
+        if self.integral.norm() > MAX_INTEGRAL {
+            self.integral = self.integral.normalized() * MAX_INTEGRAL;
+        }
+        let mut vel = feed_forward.vel + error * KP + self.integral * KI;
+        if vel.norm() > limits.max_vel {
+            vel = vel.normalized() * limits.max_vel;
+        }
+
+        let orientation_error = angle_difference(expected.orientation, state.pose.orientation);
+        let angular_vel = (feed_forward.angular_vel + orientation_error * KP_ORIENTATION)
+            .clamp(-limits.max_angular_vel, limits.max_angular_vel);
+        Twist { vel, angular_vel }
+    }
+
+    fn update(&mut self, robot: &AllyRobot, now: Instant) {
+        if let Some(last_update) = robot.get_last_update() {
+            if self.last_detection.is_none_or(|(t, _)| t != last_update) {
+                self.last_detection = Some((last_update, now));
+                self.latency.add_detection(now, robot.get_vel());
+            }
+        }
+
+        let vel = match robot.get_motion_target() {
+            Some(target) => {
+                let state = self.predict(robot, now);
+                let vel = self.track(&state, target, robot.get_limits(), now);
+                robot.set_motion_command(vel.vel.rotated(-state.pose.orientation), vel.angular_vel);
+                vel
+            }
+            None => {
+                self.plan = None;
+                self.integral = Vec2::zero();
+                Twist {
+                    vel: robot.get_target_vel().rotated(robot.get_orientation()),
+                    angular_vel: robot.get_target_angular_vel(),
+                }
+            }
+        };
+        self.latency.add_command(SentCommand { instant: now, vel });
+        self.last_tick = Some(now);
+    }
+}
+
+/// Motion control of the robots of a team, updated before each command sent to the robots.
+#[derive(Debug)]
+pub struct MotionControl {
+    robots: HashMap<RobotId, RobotMotion>,
+}
+
+impl Default for MotionControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MotionControl {
+    pub fn new() -> Self {
+        Self {
+            robots: HashMap::new(),
+        }
+    }
+
+    /// Sets the target velocities of the robots which have a `MotionTarget`.
+    pub fn update(&mut self, robots: &[AllyRobot]) {
+        let now = Instant::now();
+        for robot in robots {
+            let motion = self.robots.entry(robot.get_id()).or_insert_with(|| {
+                debug!(robot_id = robot.get_id(), "motion control of a new robot");
+                RobotMotion::new()
+            });
+            motion.update(robot, now);
+        }
+    }
+
+    /// The measured command-to-vision latency of a robot, `DEFAULT_LATENCY` until it moved enough to be measured.
+    pub fn get_latency(&self, id: RobotId) -> Option<Duration> {
+        self.robots.get(&id).map(|motion| motion.latency.latency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    /// commanded speed in [m/s] at `t` [s]: a square wave, so that the delay shows
+    fn commanded_speed(t: Duration) -> f64 {
+        if (t.as_millis() / 200).is_multiple_of(2) {
+            0.
+        } else {
+            1.
+        }
+    }
+
+    #[test]
+    fn latency_is_recovered_from_delayed_detections() {
+        let latency = Duration::from_millis(120);
+        let start = Instant::now();
+        let mut estimator = LatencyEstimator::new();
+        let mut t = Duration::ZERO;
+        while t < Duration::from_secs(3) {
+            estimator.add_command(SentCommand {
+                instant: start + t,
+                vel: Twist {
+                    vel: Vec2::new(commanded_speed(t), 0.),
+                    angular_vel: 0.,
+                },
+            });
+            // the robot follows the commands perfectly, the vision shows it `latency` late
+            let detected = t.checked_sub(latency).map_or(0., commanded_speed);
+            estimator.add_detection(start + t, Vec2::new(detected, 0.));
+            t += CONTROL_PERIOD;
+        }
+        let error = estimator.latency.as_secs_f64() - latency.as_secs_f64();
+        assert!(
+            error.abs() <= CONTROL_PERIOD.as_secs_f64(),
+            "measured {:?} instead of {latency:?}",
+            estimator.latency
+        );
+    }
+
+    #[test]
+    fn latency_isnt_measured_on_a_still_robot() {
+        let start = Instant::now();
+        let mut estimator = LatencyEstimator::new();
+        for i in 0..100 {
+            let instant = start + CONTROL_PERIOD * i;
+            estimator.add_command(SentCommand {
+                instant,
+                vel: Twist {
+                    vel: Vec2::zero(),
+                    angular_vel: 0.,
+                },
+            });
+            estimator.add_detection(instant, Vec2::zero());
+        }
+        assert_eq!(estimator.latency, DEFAULT_LATENCY);
+    }
+
+    #[test]
+    fn prediction_wraps_the_orientation() {
+        let latency = Duration::from_millis(100);
+        for angular_vel in [4., -4.] {
+            let start = Instant::now();
+            let mut motion = RobotMotion::new();
+            motion.latency.latency = latency;
+            let mut now = start;
+            while now - start < 2 * latency {
+                motion.latency.add_command(SentCommand {
+                    instant: now,
+                    vel: Twist {
+                        vel: Vec2::zero(),
+                        angular_vel,
+                    },
+                });
+                now += CONTROL_PERIOD;
+            }
+            motion.last_detection = Some((0., now));
+            // turning across ±PI during the latency
+            let detected_orientation = 3.0_f64.copysign(angular_vel);
+            let detected = PredictedState {
+                pose: Pose {
+                    pos: Point2::zero(),
+                    orientation: detected_orientation,
+                },
+                vel: Twist {
+                    vel: Vec2::zero(),
+                    angular_vel,
+                },
+            };
+            let predicted = motion.predict_from(detected, now);
+            let expected = detected_orientation + angular_vel * latency.as_secs_f64();
+            assert!(
+                predicted.pose.orientation.abs() <= PI,
+                "{}",
+                predicted.pose.orientation
+            );
+            assert!(angle_difference(predicted.pose.orientation, expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn integral_is_clamped() {
+        let target = MotionTarget {
+            destination: Point2::zero(),
+            orientation: Some(0.),
+            final_vel: Vec2::zero(),
+        };
+        let limits = KinematicLimits::default();
+        // the robot stays stuck next to its destination, without being far enough to replan
+        let state = PredictedState {
+            pose: Pose {
+                pos: Point2::new(0.15, 0.),
+                orientation: 0.,
+            },
+            vel: Twist {
+                vel: Vec2::zero(),
+                angular_vel: 0.,
+            },
+        };
+        let start = Instant::now();
+        let mut motion = RobotMotion::new();
+        motion.plan(
+            &PredictedState {
+                pose: Pose {
+                    pos: Point2::zero(),
+                    ..state.pose
+                },
+                ..state
+            },
+            target,
+            limits,
+            start,
+        );
+        let mut command = None;
+        for i in 0..200 {
+            let now = start + CONTROL_PERIOD * i;
+            command = Some(motion.track(&state, target, limits, now));
+            motion.last_tick = Some(now);
+        }
+        assert!((motion.integral.norm() - MAX_INTEGRAL).abs() < 1e-9);
+        let command = command.expect("tracked at least once");
+        let expected_vel = -0.15 * KP - MAX_INTEGRAL * KI;
+        assert!(
+            (command.vel.x - expected_vel).abs() < 1e-9,
+            "{:?}",
+            command.vel
+        );
+    }
+}
@@ -6,7 +6,8 @@ use std::io;
 pub mod multicast_receiver;
 pub mod udp_transceiver;
 
-const BUFFER_SIZE: usize = 1024;
+/// max UDP payload, geometry & tracked frames don't fit in 1KB
+pub(crate) const BUFFER_SIZE: usize = 65_507;
 
 #[derive(Debug)]
 pub enum ReceiveError {
@@ -19,3 +20,17 @@ pub enum SendError {
     SocketSendError(io::Error),
     EncodeError(EncodeError),
 }
+
+impl ReceiveError {
+    /// Socket errors (e.g. a restarting peer) & garbled datagrams may not happen again.
+    pub fn is_fatal(&self) -> bool {
+        false
+    }
+}
+
+impl SendError {
+    /// Socket errors (e.g. an unreachable peer) may not happen again, a message which can't be encoded will always fail.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, SendError::EncodeError(_))
+    }
+}
@@ -20,7 +20,12 @@ pub enum MulticastUdpReceiverCreationError {
 use MulticastUdpReceiverCreationError::*; // for readability in `MulticastUdpReceiver::new`
 
 impl MulticastUdpReceiver {
-    pub fn new(ip: Ipv4Addr, port: u16) -> Result<Self, MulticastUdpReceiverCreationError> {
+    /// Joins the `ip` multicast group on `interface` (`Ipv4Addr::UNSPECIFIED` lets the OS choose).
+    pub fn new(
+        ip: Ipv4Addr,
+        port: u16,
+        interface: Ipv4Addr,
+    ) -> Result<Self, MulticastUdpReceiverCreationError> {
         let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))
             .map_err(SocketCreationError)?;
         socket
@@ -33,7 +38,7 @@ impl MulticastUdpReceiver {
             .bind(&SocketAddrV4::new(ip, port).into())
             .map_err(SocketBindError)?;
         socket
-            .join_multicast_v4(&ip, &Ipv4Addr::UNSPECIFIED)
+            .join_multicast_v4(&ip, &interface)
             .map_err(SocketJoinMulticastError)?;
         let std_socket: StdUdpSocket = socket.into();
         Ok(Self {
@@ -38,6 +38,15 @@ impl UdpTransceiver {
             .map_err(SendError::SocketSendError)
     }
 
+    /// Sends without waiting, fails if the socket isn't ready (usable outside of a task).
+    pub fn try_send<T: prost::Message + Default>(&self, packet: T) -> Result<usize, SendError> {
+        let mut buf = Vec::with_capacity(packet.encoded_len());
+        packet.encode(&mut buf).map_err(SendError::EncodeError)?;
+        self.socket
+            .try_send(&buf)
+            .map_err(SendError::SocketSendError)
+    }
+
     pub async fn receive<T: prost::Message + Default>(&mut self) -> Result<T, ReceiveError> {
         let received_bytes_count = self
             .socket
@@ -47,4 +56,17 @@ impl UdpTransceiver {
         T::decode(Cursor::new(&self.buffer[0..received_bytes_count]))
             .map_err(ReceiveError::DecodeError)
     }
+
+    /// Receives a packet if one is already queued, without waiting.
+    pub fn try_receive<T: prost::Message + Default>(&mut self) -> Result<Option<T>, ReceiveError> {
+        match self.socket.try_recv(&mut self.buffer) {
+            Ok(received_bytes_count) => {
+                T::decode(Cursor::new(&self.buffer[0..received_bytes_count]))
+                    .map(Some)
+                    .map_err(ReceiveError::DecodeError)
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(ReceiveError::SocketReceiveError(e)),
+        }
+    }
 }
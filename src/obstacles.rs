@@ -0,0 +1,495 @@
+//! Obstacles avoided by the path planning of our robots.
+//!
+//! The obstacles of a robot are assembled from the world by `Obstacles::new`, following its
+//! `AvoidanceRules`: the robots & the ball (which move along their predicted trajectories), the
+//! defense areas, the goals, the outside of the field and the distances to the ball required by the game state.
+//! A point is free for a robot if its whole body is out of every obstacle.
+
+use std::ops::BitOr;
+
+use crate::{
+    game_state::{GameState, RunningState, StoppedState},
+    math::{Point2, Rect, Vec2},
+    trajectories::{ball::BallTrajectory, Trajectory},
+    world::{AllyRobot, AvoidanceMode, RobotId, TeamColor, World},
+    IgnoreMutexErr,
+};
+
+/// distance in [m] kept between the robots when avoiding them
+const ROBOT_AVOIDANCE_MARGIN: f64 = 0.1;
+/// distance in [m] kept from the ball when avoiding it
+const BALL_AVOIDANCE_MARGIN: f64 = 0.1;
+/// distance in [m] to the ball required by the rules during a STOP or the other team's free kicks
+pub const STOP_BALL_DISTANCE: f64 = 0.5;
+/// thickness in [m] of the goals' walls, which aren't in the geometry
+const GOAL_WALL_THICKNESS: f64 = 0.02;
+/// the obstacles moving at a constant velocity are extrapolated for this long at most, in [s]
+const PREDICTION_HORIZON: f64 = 0.5;
+
+/// Which obstacles a robot avoids, combined with `|`.
+///
+/// # Example
+/// ```
+/// use crabe_async::{obstacles::AvoidanceRules, world::AvoidanceMode};
+///
+/// // a robot pushing through the others, but keeping away from the defense areas & the ball
+/// let rules = AvoidanceRules {
+///     ally_defense_area: true,
+///     ennemy_defense_area: true,
+///     ..Default::default()
+/// } | AvoidanceRules {
+///     ball: true,
+///     ..Default::default()
+/// };
+/// assert!(rules.ball && !rules.allies);
+/// assert!(AvoidanceRules::from(AvoidanceMode::None).is_empty());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AvoidanceRules {
+    pub allies: bool,
+    pub ennemies: bool,
+    pub ball: bool,
+    /// always allowed for our keeper (for every robot until the game controller names it)
+    pub ally_defense_area: bool,
+    pub ennemy_defense_area: bool,
+    /// the goals' posts & nets
+    pub goals: bool,
+    /// stay between the field's walls
+    pub out_of_field: bool,
+    /// keep the distances to the ball required by the game state (STOP, the other team's free kicks & ball placements)
+    pub game_state: bool,
+}
+
+impl AvoidanceRules {
+    /// whether nothing is avoided
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl BitOr for AvoidanceRules {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self {
+            allies: self.allies || rhs.allies,
+            ennemies: self.ennemies || rhs.ennemies,
+            ball: self.ball || rhs.ball,
+            ally_defense_area: self.ally_defense_area || rhs.ally_defense_area,
+            ennemy_defense_area: self.ennemy_defense_area || rhs.ennemy_defense_area,
+            goals: self.goals || rhs.goals,
+            out_of_field: self.out_of_field || rhs.out_of_field,
+            game_state: self.game_state || rhs.game_state,
+        }
+    }
+}
+
+impl From<AvoidanceMode> for AvoidanceRules {
+    /// avoiding the robots also avoids the field's obstacles & follows the game state
+    fn from(mode: AvoidanceMode) -> Self {
+        let avoid_robots = Self {
+            allies: true,
+            ennemies: true,
+            ball: false,
+            ally_defense_area: true,
+            ennemy_defense_area: true,
+            goals: true,
+            out_of_field: true,
+            game_state: true,
+        };
+        match mode {
+            AvoidanceMode::None => Self::default(),
+            AvoidanceMode::AvoidRobots => avoid_robots,
+            AvoidanceMode::AvoidRobotsAndBall => Self {
+                ball: true,
+                ..avoid_robots
+            },
+        }
+    }
+}
+
+/// An area, in meters.
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    Circle {
+        center: Point2,
+        radius: f64,
+    },
+    Rect(Rect),
+    /// the points closer than `radius` to the segment
+    Capsule {
+        start: Point2,
+        end: Point2,
+        radius: f64,
+    },
+    /// everything outside of the rect
+    Outside(Rect),
+}
+
+impl Shape {
+    /// whether a circle of `radius` in [m] at `p` overlaps the shape
+    pub fn overlaps(&self, p: Point2, radius: f64) -> bool {
+        match *self {
+            Shape::Circle {
+                center,
+                radius: shape_radius,
+            } => (p - center).norm() < shape_radius + radius,
+            Shape::Rect(rect) => rect.grow(radius).contains(p),
+            Shape::Capsule {
+                start,
+                end,
+                radius: shape_radius,
+            } => (p - closest_point_on_segment(start, end, p)).norm() < shape_radius + radius,
+            // shrinking the rect by the radius would flip it for circles wider than it
+            Shape::Outside(rect) => !rect.contains_circle(p, radius),
+        }
+    }
+}
+
+fn closest_point_on_segment(start: Point2, end: Point2, p: Point2) -> Point2 {
+    let segment = end - start;
+    let length_squared = segment.dot(segment);
+    if length_squared == 0. {
+        return start;
+    }
+    let t = ((p - start).dot(segment) / length_squared).clamp(0., 1.);
+    start + segment * t
+}
+
+/// How an obstacle is expected to move.
+#[derive(Debug, Clone)]
+pub enum ObstacleMotion {
+    Static,
+    /// at a constant velocity in [m/s], for `PREDICTION_HORIZON` at most
+    Linear(Vec2),
+    /// along the ball's predicted trajectory
+    Ball(BallTrajectory),
+}
+
+impl ObstacleMotion {
+    /// how much the obstacle moved in `t` [s]
+    fn get_offset(&self, t: f64) -> Vec2 {
+        match self {
+            ObstacleMotion::Static => Vec2::zero(),
+            ObstacleMotion::Linear(vel) => *vel * t.min(PREDICTION_HORIZON),
+            ObstacleMotion::Ball(trajectory) => {
+                trajectory.get_position(t) - trajectory.get_position(0.)
+            }
+        }
+    }
+}
+
+/// What an obstacle is, to know what a robot collides with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObstacleKind {
+    Robot {
+        color: TeamColor,
+        id: RobotId,
+    },
+    Ball,
+    /// distance to the ball required by the game state
+    BallDistance,
+    /// area around the ball & its placement position, while the other team places it
+    BallPlacement,
+    DefenseArea(TeamColor),
+    Goal(TeamColor),
+    OutOfField,
+}
+
+#[derive(Debug, Clone)]
+pub struct Obstacle {
+    pub kind: ObstacleKind,
+    /// area covered now
+    pub shape: Shape,
+    pub motion: ObstacleMotion,
+}
+
+impl Obstacle {
+    pub fn new_static(kind: ObstacleKind, shape: Shape) -> Self {
+        Self {
+            kind,
+            shape,
+            motion: ObstacleMotion::Static,
+        }
+    }
+
+    /// whether a circle of `radius` in [m] at `p` overlaps the obstacle in `t` [s]
+    pub fn overlaps(&self, p: Point2, radius: f64, t: f64) -> bool {
+        // moving the point backwards is moving the shape forward
+        self.shape.overlaps(p - self.motion.get_offset(t), radius)
+    }
+}
+
+/// The obstacles of one of our robots.
+#[derive(Debug, Clone)]
+pub struct Obstacles {
+    /// radius of the robot in [m]
+    radius: f64,
+    obstacles: Vec<Obstacle>,
+}
+
+impl Obstacles {
+    /// The obstacles `robot` has to avoid according to the `rules` in the current `world`.
+    pub fn new(world: &World, robot: &AllyRobot, rules: AvoidanceRules) -> Self {
+        let mut obstacles = vec![];
+        let field = &world.field;
+        let ally_color = world.team_color;
+        let ennemy_color = ally_color.opposite();
+        let radius = robot.get_specs().radius;
+
+        if rules.allies {
+            obstacles.extend(
+                world
+                    .team
+                    .lock()
+                    .unwrap_ignore_poison()
+                    .values()
+                    .filter(|r| r.get_id() != robot.get_id()) // can't collide with myself
+                    .filter(|r| r.is_active()) // robots which left the field
+                    .map(|r| Obstacle {
+                        kind: ObstacleKind::Robot {
+                            color: ally_color,
+                            id: r.get_id(),
+                        },
+                        shape: Shape::Circle {
+                            center: r.get_pos(),
+                            radius: r.get_specs().radius + ROBOT_AVOIDANCE_MARGIN,
+                        },
+                        motion: ObstacleMotion::Linear(r.get_vel()),
+                    }),
+            );
+        }
+        if rules.ennemies {
+            // we don't know the ennemies' specs, only the biggest robots allowed
+            let ennemy_radius = field.get_max_robot_radius();
+            obstacles.extend(
+                world
+                    .ennemies
+                    .lock()
+                    .unwrap_ignore_poison()
+                    .values()
+                    .filter(|r| r.is_active())
+                    .map(|r| Obstacle {
+                        kind: ObstacleKind::Robot {
+                            color: ennemy_color,
+                            id: r.get_id(),
+                        },
+                        shape: Shape::Circle {
+                            center: r.get_pos(),
+                            radius: ennemy_radius + ROBOT_AVOIDANCE_MARGIN,
+                        },
+                        motion: ObstacleMotion::Linear(r.get_vel()),
+                    }),
+            );
+        }
+        if rules.ball {
+            obstacles.push(Obstacle {
+                kind: ObstacleKind::Ball,
+                shape: Shape::Circle {
+                    center: world.ball.get_pos(),
+                    radius: field.get_ball_radius() + BALL_AVOIDANCE_MARGIN,
+                },
+                motion: ObstacleMotion::Ball(world.ball.get_trajectory()),
+            });
+        }
+        if rules.ally_defense_area && !is_keeper(world, robot.get_id()) {
+            obstacles.push(Obstacle::new_static(
+                ObstacleKind::DefenseArea(ally_color),
+                Shape::Rect(field.get_defense_area(ally_color)),
+            ));
+        }
+        if rules.ennemy_defense_area {
+            obstacles.push(Obstacle::new_static(
+                ObstacleKind::DefenseArea(ennemy_color),
+                Shape::Rect(field.get_defense_area(ennemy_color)),
+            ));
+        }
+        if rules.goals {
+            for color in [ally_color, ennemy_color] {
+                obstacles.push(Obstacle::new_static(
+                    ObstacleKind::Goal(color),
+                    Shape::Rect(field.get_goal_bounding_box(color).grow(GOAL_WALL_THICKNESS)),
+                ));
+            }
+        }
+        if rules.out_of_field {
+            obstacles.push(Obstacle::new_static(
+                ObstacleKind::OutOfField,
+                Shape::Outside(field.get_boundary_bounding_box()),
+            ));
+        }
+        if rules.game_state {
+            obstacles.extend(game_state_obstacles(world));
+        }
+
+        Self { radius, obstacles }
+    }
+
+    /// the obstacles the robot is in at `p`, which it must be able to leave
+    pub fn without_obstacles_at(mut self, p: Point2) -> Self {
+        let radius = self.radius;
+        self.obstacles
+            .retain(|obstacle| !obstacle.overlaps(p, radius, 0.));
+        self
+    }
+
+    /// the first obstacle the robot would collide with at `p` in `t` [s]
+    pub fn get_collision(&self, p: Point2, t: f64) -> Option<&Obstacle> {
+        self.obstacles
+            .iter()
+            .find(|obstacle| obstacle.overlaps(p, self.radius, t))
+    }
+
+    /// whether the robot can be at `p` in `t` [s]
+    pub fn is_free(&self, p: Point2, t: f64) -> bool {
+        self.get_collision(p, t).is_none()
+    }
+
+    pub fn get_obstacles(&self) -> &[Obstacle] {
+        &self.obstacles
+    }
+}
+
+/// whether the robot is our keeper, as told by the game controller (any robot may be without one)
+fn is_keeper(world: &World, id: RobotId) -> bool {
+    world.get_last_referee().is_none_or(|referee| {
+        let team = match world.team_color {
+            TeamColor::Blue => referee.blue,
+            TeamColor::Yellow => referee.yellow,
+        };
+        team.goalkeeper == id as u32
+    })
+}
+
+/// distances to the ball required by the rules
+fn game_state_obstacles(world: &World) -> Vec<Obstacle> {
+    let ball_distance = Obstacle::new_static(
+        ObstacleKind::BallDistance,
+        Shape::Circle {
+            center: world.ball.get_pos(),
+            radius: STOP_BALL_DISTANCE,
+        },
+    );
+    match world.get_game_state() {
+        GameState::Stopped(StoppedState::Stop | StoppedState::PrepareKickoffThem)
+        | GameState::Running(RunningState::FreeKickThem | RunningState::KickoffThem) => {
+            vec![ball_distance]
+        }
+        GameState::Stopped(StoppedState::BallPlacementThem) => {
+            let ball = world.ball.get_pos();
+            vec![Obstacle::new_static(
+                ObstacleKind::BallPlacement,
+                Shape::Capsule {
+                    start: ball,
+                    end: world.get_designated_position().unwrap_or(ball),
+                    radius: STOP_BALL_DISTANCE,
+                },
+            )]
+        }
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game_state::HaltedState,
+        league_protocols::game_controller_packet::{referee, Referee},
+    };
+
+    fn field() -> Rect {
+        Rect::new(Point2::new(-1., -0.5), Point2::new(1., 0.5))
+    }
+
+    #[test]
+    fn outside_overlaps_circles_crossing_the_border() {
+        let outside = Shape::Outside(field());
+        assert!(!outside.overlaps(Point2::new(0., 0.), 0.1));
+        assert!(!outside.overlaps(Point2::new(0.85, 0.), 0.1));
+        assert!(outside.overlaps(Point2::new(0.95, 0.), 0.1));
+        assert!(outside.overlaps(Point2::new(0., 0.45), 0.1));
+        assert!(outside.overlaps(Point2::new(2., 0.), 0.1));
+        // wider than the rect: overlaps the outside wherever it is
+        assert!(outside.overlaps(Point2::new(0., 0.), 0.6));
+    }
+
+    #[test]
+    fn capsule_overlaps_around_its_segment() {
+        let capsule = Shape::Capsule {
+            start: Point2::new(0., 0.),
+            end: Point2::new(1., 0.),
+            radius: 0.5,
+        };
+        assert!(capsule.overlaps(Point2::new(0.5, 0.55), 0.1));
+        assert!(!capsule.overlaps(Point2::new(0.5, 0.65), 0.1));
+        // beyond the ends, the capsule is round
+        assert!(capsule.overlaps(Point2::new(1.5, 0.), 0.1));
+        assert!(!capsule.overlaps(Point2::new(1.45, 0.45), 0.1));
+        assert!(!capsule.overlaps(Point2::new(-0.65, 0.), 0.1));
+
+        let point = Shape::Capsule {
+            start: Point2::new(0., 0.),
+            end: Point2::new(0., 0.),
+            radius: 0.5,
+        };
+        assert!(point.overlaps(Point2::new(0., 0.55), 0.1));
+        assert!(!point.overlaps(Point2::new(0.65, 0.), 0.1));
+    }
+
+    fn world_with_ball_at(ball: Point2, game_state: GameState) -> World {
+        let world = World::default_with_team_color(TeamColor::Blue);
+        world.ball.set_pos(ball);
+        world.set_game_state(game_state);
+        world
+    }
+
+    #[test]
+    fn game_state_obstacles_keep_away_from_the_ball() {
+        let ball = Point2::new(1., 1.);
+        for game_state in [
+            GameState::Stopped(StoppedState::Stop),
+            GameState::Stopped(StoppedState::PrepareKickoffThem),
+            GameState::Running(RunningState::FreeKickThem),
+            GameState::Running(RunningState::KickoffThem),
+        ] {
+            let obstacles = game_state_obstacles(&world_with_ball_at(ball, game_state));
+            assert_eq!(obstacles.len(), 1, "{game_state:?}");
+            assert_eq!(obstacles[0].kind, ObstacleKind::BallDistance);
+            assert!(obstacles[0].overlaps(Point2::new(1.4, 1.), 0.09, 0.));
+            assert!(!obstacles[0].overlaps(Point2::new(1.6, 1.), 0.09, 0.));
+        }
+        for game_state in [
+            GameState::Halted(HaltedState::Halt),
+            GameState::Stopped(StoppedState::PrepareKickoffUs),
+            GameState::Stopped(StoppedState::BallPlacementUs),
+            GameState::Running(RunningState::FreeKickUs),
+            GameState::Running(RunningState::Run),
+        ] {
+            let world = world_with_ball_at(ball, game_state);
+            assert!(game_state_obstacles(&world).is_empty(), "{game_state:?}");
+        }
+    }
+
+    #[test]
+    fn game_state_obstacles_keep_away_from_the_other_team_placement() {
+        let world = world_with_ball_at(
+            Point2::new(1., 1.),
+            GameState::Stopped(StoppedState::BallPlacementThem),
+        );
+        world.set_last_referee(Referee {
+            designated_position: Some(referee::Point {
+                x: -1000.,
+                y: 1000.,
+            }),
+            ..Default::default()
+        });
+        let obstacles = game_state_obstacles(&world);
+        assert_eq!(obstacles.len(), 1);
+        assert_eq!(obstacles[0].kind, ObstacleKind::BallPlacement);
+        // anywhere between the ball & the placement position
+        assert!(obstacles[0].overlaps(Point2::new(0., 1.4), 0.09, 0.));
+        assert!(!obstacles[0].overlaps(Point2::new(0., 1.6), 0.09, 0.));
+        assert!(obstacles[0].overlaps(Point2::new(-1.4, 1.), 0.09, 0.));
+    }
+}
@@ -0,0 +1,352 @@
+//! Replay of SSL log files, feeding their vision, tracker & referee messages to a `Vision`, a `TrackedVision`
+//! & a `GameController` as if they were received from the network.
+//!
+//! The replay runs in real time, accelerated or step by step (one vision frame at a time),
+//! and can seek to a timestamp of the log, see `LogReplayControl`.
+//!
+//! # Examples
+//!
+//! ```
+//! use crabe_async::{
+//!     league_protocols::vision_packet::SslWrapperPacket,
+//!     log_file::{LogMessage, LogWriter},
+//!     replay::{LogReplay, ReplayMode},
+//! };
+//!
+//! #[tokio::main(flavor = "current_thread", start_paused = true)]
+//! async fn main() {
+//!     let path = std::env::temp_dir().join("coral_replay_example.log");
+//!     let mut writer = LogWriter::create(&path).unwrap();
+//!     for i in 0..3 {
+//!         // a vision frame every 16ms
+//!         writer.write(i * 16_000_000, &LogMessage::Vision(SslWrapperPacket::default())).unwrap();
+//!     }
+//!     drop(writer);
+//!
+//!     let mut replay = LogReplay::open(&path, ReplayMode::Step).unwrap();
+//!     let mut vision = replay.vision();
+//!     let control = replay.control();
+//!     tokio::spawn(replay.run());
+//!
+//!     control.seek(16_000_000); // skips the first frame
+//!     control.step();
+//!     vision.receive().await.unwrap();
+//!     control.set_mode(ReplayMode::Accelerated(10.));
+//!     vision.receive().await.unwrap();
+//! }
+//! ```
+
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    future::pending,
+    io::Read,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tokio::{
+    select,
+    sync::mpsc,
+    time::{sleep_until, Instant},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    game_controller::GameController,
+    league_protocols::{
+        game_controller_packet::Referee, tracked_vision_packet::TrackerWrapperPacket,
+        vision_packet::SslWrapperPacket,
+    },
+    log_file::{LogFileError, LogMessage, LogReader, LogRecord},
+    net::{multicast_receiver::MulticastUdpReceiver, ReceiveError},
+    tracked_vision::TrackedVision,
+    vision::Vision,
+};
+
+/// messages replayed ahead of their consumer
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Pace of a replay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayMode {
+    /// at the pace of the recording
+    RealTime,
+    /// this many times faster than the recording
+    Accelerated(f64),
+    /// paused, replays up to the next vision frame at each `LogReplayControl::step`
+    Step,
+}
+
+impl ReplayMode {
+    /// `None` if the replay isn't timed, an invalid speed pauses the replay like `Step`
+    fn get_speed(&self) -> Option<f64> {
+        match self {
+            ReplayMode::RealTime => Some(1.),
+            ReplayMode::Accelerated(speed) => check_speed(*speed).ok(),
+            ReplayMode::Step => None,
+        }
+    }
+}
+
+/// A replay speed which isn't a positive finite number.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidSpeedError(pub f64);
+
+impl Display for InvalidSpeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid replay speed {}, expected a positive number",
+            self.0
+        )
+    }
+}
+
+impl Error for InvalidSpeedError {}
+
+/// `speed` if it's a valid replay speed (positive & finite)
+pub fn check_speed(speed: f64) -> Result<f64, InvalidSpeedError> {
+    if speed.is_finite() && speed > 0. {
+        Ok(speed)
+    } else {
+        Err(InvalidSpeedError(speed))
+    }
+}
+
+#[derive(Debug)]
+enum ReplayCommand {
+    SetMode(ReplayMode),
+    Step,
+    Seek(i64),
+}
+
+/// Controls a running `LogReplay`, the commands are ignored once the replay is over.
+#[derive(Clone)]
+pub struct LogReplayControl {
+    commands: mpsc::UnboundedSender<ReplayCommand>,
+}
+
+impl LogReplayControl {
+    /// Modes with an invalid speed are ignored.
+    pub fn set_mode(&self, mode: ReplayMode) {
+        if let ReplayMode::Accelerated(speed) = mode {
+            if let Err(e) = check_speed(speed) {
+                warn!("{e}");
+                return;
+            }
+        }
+        let _ = self.commands.send(ReplayCommand::SetMode(mode));
+    }
+
+    /// In step mode, replays the messages up to the next vision frame.
+    pub fn step(&self) {
+        let _ = self.commands.send(ReplayCommand::Step);
+    }
+
+    /// Continues the replay from the first message received at or after `timestamp` in [ns] since the UNIX epoch.
+    pub fn seek(&self, timestamp: i64) {
+        let _ = self.commands.send(ReplayCommand::Seek(timestamp));
+    }
+}
+
+/// Messages of a replay, waits forever once the replay is over (like a silent network).
+pub(crate) struct ReplayReceiver<T>(mpsc::Receiver<T>);
+
+impl<T> ReplayReceiver<T> {
+    async fn receive(&mut self) -> T {
+        match self.0.recv().await {
+            Some(message) => message,
+            None => pending().await,
+        }
+    }
+}
+
+/// Where the `Vision`, `TrackedVision` & `GameController` get their messages from.
+pub(crate) enum PacketSource<T> {
+    /// boxed, the receiver holds its buffer
+    Network(Box<MulticastUdpReceiver>),
+    Replay(ReplayReceiver<T>),
+}
+
+impl<T: prost::Message + Default> PacketSource<T> {
+    pub(crate) async fn receive(&mut self) -> Result<T, ReceiveError> {
+        match self {
+            PacketSource::Network(socket) => socket.receive::<T>().await,
+            PacketSource::Replay(replay) => Ok(replay.receive().await),
+        }
+    }
+}
+
+/// Replays a log file, see the module's documentation.
+pub struct LogReplay {
+    path: PathBuf,
+    reader: LogReader<Box<dyn Read + Send>>,
+    /// read but not replayed yet
+    next: Option<LogRecord>,
+    vision: Option<mpsc::Sender<SslWrapperPacket>>,
+    tracker: Option<mpsc::Sender<TrackerWrapperPacket>>,
+    referee: Option<mpsc::Sender<Referee>>,
+    control: LogReplayControl,
+    commands: mpsc::UnboundedReceiver<ReplayCommand>,
+    mode: ReplayMode,
+    /// vision frames left to replay in step mode
+    pending_steps: usize,
+    /// instant at which the message of the timestamp is replayed, the timing of the others follows
+    anchor: Option<(Instant, i64)>,
+}
+
+impl LogReplay {
+    pub fn open(path: impl AsRef<Path>, mode: ReplayMode) -> Result<Self, LogFileError> {
+        let path = path.as_ref().to_path_buf();
+        let (commands_sender, commands) = mpsc::unbounded_channel();
+        Ok(Self {
+            reader: LogReader::open(&path)?,
+            path,
+            next: None,
+            vision: None,
+            tracker: None,
+            referee: None,
+            control: LogReplayControl {
+                commands: commands_sender,
+            },
+            commands,
+            mode,
+            pending_steps: 0,
+            anchor: None,
+        })
+    }
+
+    /// A `Vision` receiving the replayed vision messages.
+    /// The vision messages aren't replayed (and don't slow the replay down) if there isn't one.
+    pub fn vision(&mut self) -> Vision {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        self.vision = Some(sender);
+        Vision::from_source(PacketSource::Replay(ReplayReceiver(receiver)))
+    }
+
+    /// A `TrackedVision` receiving the replayed tracker messages.
+    /// The tracker messages aren't replayed if there isn't one.
+    pub fn tracked_vision(&mut self) -> TrackedVision {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        self.tracker = Some(sender);
+        TrackedVision::from_source(PacketSource::Replay(ReplayReceiver(receiver)))
+    }
+
+    /// A `GameController` receiving the replayed referee messages.
+    /// The referee messages aren't replayed if there isn't one.
+    pub fn game_controller(&mut self) -> GameController {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        self.referee = Some(sender);
+        GameController::from_source(PacketSource::Replay(ReplayReceiver(receiver)))
+    }
+
+    pub fn control(&self) -> LogReplayControl {
+        self.control.clone()
+    }
+
+    /// Timestamp in [ns] since the UNIX epoch of the next replayed message, `None` at the end of the log.
+    pub fn get_next_timestamp(&mut self) -> Result<Option<i64>, LogFileError> {
+        Ok(self.peek()?.map(|record| record.timestamp))
+    }
+
+    fn peek(&mut self) -> Result<Option<&LogRecord>, LogFileError> {
+        if self.next.is_none() {
+            self.next = self.reader.read_record()?;
+        }
+        Ok(self.next.as_ref())
+    }
+
+    fn seek(&mut self, timestamp: i64) -> Result<(), LogFileError> {
+        if self.get_next_timestamp()?.is_none_or(|t| t > timestamp) {
+            // the log files can only be read forward
+            self.reader = LogReader::open(&self.path)?;
+            self.next = None;
+        }
+        while self.get_next_timestamp()?.is_some_and(|t| t < timestamp) {
+            self.next = None;
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, command: ReplayCommand) -> Result<(), LogFileError> {
+        debug!(?command, "replay command");
+        match command {
+            ReplayCommand::SetMode(mode) => {
+                self.mode = mode;
+                self.pending_steps = 0;
+            }
+            ReplayCommand::Step => self.pending_steps += 1,
+            ReplayCommand::Seek(timestamp) => self.seek(timestamp)?,
+        }
+        self.anchor = None;
+        Ok(())
+    }
+
+    async fn replay(&mut self, record: LogRecord) -> Result<(), LogFileError> {
+        // a consumer which was dropped doesn't stop the replay
+        match record.decode().map_err(LogFileError::DecodeError)? {
+            Some(LogMessage::Vision(packet)) => {
+                self.pending_steps = self.pending_steps.saturating_sub(1);
+                if let Some(vision) = &self.vision {
+                    let _ = vision.send(packet).await;
+                }
+            }
+            Some(LogMessage::Referee(referee)) => {
+                if let Some(gc) = &self.referee {
+                    let _ = gc.send(referee).await;
+                }
+            }
+            Some(LogMessage::Tracker(packet)) => {
+                if let Some(tracked_vision) = &self.tracker {
+                    let _ = tracked_vision.send(packet).await;
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Replays the log until its end.
+    pub async fn run(mut self) -> Result<(), LogFileError> {
+        info!(path = ?self.path, mode = ?self.mode, "replaying");
+        loop {
+            while let Ok(command) = self.commands.try_recv() {
+                self.apply(command)?;
+            }
+            let timestamp = match self.get_next_timestamp()? {
+                Some(timestamp) => timestamp,
+                None => {
+                    info!("end of the replay");
+                    return Ok(());
+                }
+            };
+
+            match self.mode.get_speed() {
+                Some(speed) => {
+                    let (anchor_instant, anchor_timestamp) =
+                        *self.anchor.get_or_insert((Instant::now(), timestamp));
+                    let delay = (timestamp - anchor_timestamp).max(0) as f64 / 1e9 / speed;
+                    select! {
+                        _ = sleep_until(anchor_instant + Duration::from_secs_f64(delay)) => {}
+                        Some(command) = self.commands.recv() => {
+                            self.apply(command)?;
+                            continue;
+                        }
+                    }
+                }
+                None if self.pending_steps == 0 => {
+                    if let Some(command) = self.commands.recv().await {
+                        self.apply(command)?;
+                    }
+                    continue;
+                }
+                None => {}
+            }
+
+            if let Some(record) = self.next.take() {
+                self.replay(record).await?;
+            }
+        }
+    }
+}
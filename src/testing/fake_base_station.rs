@@ -0,0 +1,109 @@
+//! A fake base station, to test `RealRobotController` without robots.
+//!
+//! # Examples
+//!
+//! ```
+//! use crabe_async::{
+//!     controllers::{real_controller::RealRobotController, RobotController},
+//!     league_protocols::robot_packet::{BaseFeedback, BaseToPc},
+//!     math::Vec2,
+//!     testing::fake_base_station::FakeBaseStation,
+//!     world::{AllyRobot, TeamColor},
+//! };
+//! use std::net::Ipv4Addr;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut base_station = FakeBaseStation::new().await;
+//!     let mut controller =
+//!         RealRobotController::new(Some(Ipv4Addr::LOCALHOST), Some(base_station.get_port())).await;
+//!
+//!     let robot = AllyRobot::default_with_id(3, TeamColor::Blue);
+//!     robot.set_target_vel(Vec2::new(1., 0.));
+//!     let (_, commands) = tokio::join!(
+//!         controller.send_proper_command_for(vec![robot].into_iter()),
+//!         base_station.receive_commands(),
+//!     );
+//!     let commands = commands.unwrap();
+//!     assert_eq!(commands.commands[0].robot_id, 3);
+//!     assert_eq!(commands.commands[0].normal_velocity, 1.);
+//!
+//!     // the controller reads the feedback queued since its last commands when it sends the next ones
+//!     base_station
+//!         .send_feedback(BaseToPc {
+//!             feedbacks: vec![BaseFeedback { robot_id: 3, ir: true, ..Default::default() }],
+//!         })
+//!         .await
+//!         .unwrap();
+//!     let feedback = controller
+//!         .send_proper_command_for(std::iter::empty())
+//!         .await
+//!         .unwrap();
+//!     assert!(feedback[&3].has_ball);
+//! }
+//! ```
+
+use std::{
+    io::{self, Cursor},
+    net::{Ipv4Addr, SocketAddr},
+};
+
+use tokio::net::UdpSocket;
+
+use crate::{
+    league_protocols::robot_packet::{BaseToPc, PcToBase},
+    net::{ReceiveError, SendError, BUFFER_SIZE},
+};
+
+/// Listens on localhost for the commands of a `RealRobotController`, and answers with arbitrary feedback.
+pub struct FakeBaseStation {
+    socket: UdpSocket,
+    /// address of the last controller which sent commands
+    controller_addr: Option<SocketAddr>,
+    buffer: Vec<u8>,
+}
+
+impl FakeBaseStation {
+    /// binds on a free port of localhost, see `get_port`
+    pub async fn new() -> Self {
+        Self {
+            socket: UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+                .await
+                .expect("Failed to bind fake base station"),
+            controller_addr: None,
+            buffer: vec![0; BUFFER_SIZE],
+        }
+    }
+
+    pub fn get_port(&self) -> u16 {
+        self.socket
+            .local_addr()
+            .expect("bound sockets should have a local address")
+            .port()
+    }
+
+    pub async fn receive_commands(&mut self) -> Result<PcToBase, ReceiveError> {
+        let (received_bytes_count, addr) = self
+            .socket
+            .recv_from(&mut self.buffer)
+            .await
+            .map_err(ReceiveError::SocketReceiveError)?;
+        self.controller_addr = Some(addr);
+        prost::Message::decode(Cursor::new(&self.buffer[0..received_bytes_count]))
+            .map_err(ReceiveError::DecodeError)
+    }
+
+    /// Sends feedback to the controller which sent the last commands.
+    pub async fn send_feedback(&self, feedback: BaseToPc) -> Result<usize, SendError> {
+        let addr = self
+            .controller_addr
+            .ok_or(SendError::SocketSendError(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "no controller sent commands yet",
+            )))?;
+        self.socket
+            .send_to(&prost::Message::encode_to_vec(&feedback), addr)
+            .await
+            .map_err(SendError::SocketSendError)
+    }
+}
@@ -1 +1,5 @@
+pub mod fake_base_station;
+pub mod scenario;
 pub mod simulation_control;
+pub mod simulator;
+pub mod synchronous_simulation;
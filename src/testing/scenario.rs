@@ -0,0 +1,504 @@
+//! Scenario tests of the skills: a declared initial world, a skill ran on a synchronous simulation
+//! and assertions on what happened.
+//!
+//! The initial world is set up with the simulation protocol's teleportations, so a scenario runs on
+//! any `SynchronousSimulator`: the built-in `Simulator` in CI, ER-Force's simulator locally.
+//! The robots of the simulator which aren't declared by the scenario are removed.
+//! When an assertion fails, the trace of the scenario (poses of the robots & ball at each step)
+//! is saved as a CSV file whose path is in the panic message.
+//!
+//! # Examples
+//!
+//! ```
+//! use crabe_async::{
+//!     math::Point2,
+//!     testing::{
+//!         scenario::{Scenario, ScenarioRobot},
+//!         simulator::Simulator,
+//!     },
+//!     world::AvoidanceMode,
+//! };
+//!
+//! #[tokio::main(flavor = "current_thread", start_paused = true)]
+//! async fn main() {
+//!     let scenario = Scenario {
+//!         name: "goto_forward".to_string(),
+//!         allies: vec![ScenarioRobot::new(0, Point2::new(-1., 0.), 0.)],
+//!         ..Default::default()
+//!     };
+//!     let target = Point2::new(0., 0.5);
+//!     let outcome = scenario
+//!         .run(Simulator::new(), |world| async move {
+//!             let robot = world.team.lock().unwrap()[&0].clone();
+//!             robot.goto(&world, &target, Some(0.), AvoidanceMode::None).await
+//!         })
+//!         .await
+//!         .unwrap();
+//!     outcome.assert_finished();
+//!     outcome.assert_robot_reached(0, target, 0.05);
+//!     outcome.assert_no_collision();
+//! }
+//! ```
+
+use std::{collections::HashSet, fmt::Write as _, future::Future, path::PathBuf, time::Duration};
+
+use tracing::warn;
+
+use crate::{
+    league_protocols::simulation_packet::{
+        RobotId as SimRobotId, SimulatorCommand, SimulatorControl, Team, TeleportBall,
+        TeleportRobot,
+    },
+    math::{Point2, ReactivePoint2Ext, Rect, Vec2},
+    world::{FieldSide, RobotId, TeamColor, World},
+    IgnoreMutexErr,
+};
+
+use super::synchronous_simulation::{SynchronousSimulation, SynchronousSimulator};
+
+/// time given to the vision to detect the robots of the initial world
+const DETECTION_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// robots closer than the sum of their radii plus this margin in [m] (for the vision's noise) touch
+const COLLISION_MARGIN: f64 = 0.002;
+
+/// A robot of the initial world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScenarioRobot {
+    pub id: RobotId,
+    /// in [m]
+    pub pos: Point2,
+    /// in [rad]
+    pub orientation: f64,
+}
+
+impl ScenarioRobot {
+    pub fn new(id: RobotId, pos: Point2, orientation: f64) -> Self {
+        Self {
+            id,
+            pos,
+            orientation,
+        }
+    }
+}
+
+/// The initial world of a scenario & how long it runs.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    /// names the trace file
+    pub name: String,
+    /// the controlled team, the simulator must apply the synchronous commands to this team
+    pub team_color: TeamColor,
+    pub ally_side: FieldSide,
+    pub allies: Vec<ScenarioRobot>,
+    pub ennemies: Vec<ScenarioRobot>,
+    /// in [m]
+    pub ball_pos: Point2,
+    /// in [m/s]
+    pub ball_vel: Vec2,
+    /// simulated time after which the skill is cancelled
+    pub timeout: Duration,
+    /// simulated time kept running once the skill is done, e.g. for a kicked ball to reach the goal
+    pub after_skill: Duration,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            name: "scenario".to_string(),
+            team_color: TeamColor::Blue,
+            ally_side: FieldSide::Negative,
+            allies: vec![],
+            ennemies: vec![],
+            ball_pos: Point2::zero(),
+            ball_vel: Vec2::zero(),
+            timeout: Duration::from_secs(10),
+            after_skill: Duration::from_secs(1),
+        }
+    }
+}
+
+/// The poses at a step of the scenario.
+#[derive(Clone, Debug)]
+pub struct TraceFrame {
+    /// simulated time in [s] since the start of the skill
+    pub time: f64,
+    pub ball_pos: Point2,
+    /// (color, id, position, orientation) of the visible robots
+    pub robots: Vec<(TeamColor, RobotId, Point2, f64)>,
+}
+
+/// Two robots which touched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Collision {
+    /// simulated time in [s] since the start of the skill
+    pub time: f64,
+    pub robots: [(TeamColor, RobotId); 2],
+}
+
+/// What happened during a scenario.
+pub struct ScenarioOutcome<T> {
+    name: String,
+    world: World,
+    /// `None` if the skill timed out
+    result: Option<T>,
+    ennemy_goal: Rect,
+    trace: Vec<TraceFrame>,
+    collisions: Vec<Collision>,
+    /// declared robots which weren't detected within `DETECTION_TIMEOUT`, the skill didn't run then
+    undetected: Vec<(TeamColor, RobotId)>,
+}
+
+fn sim_robot_id(color: TeamColor, id: RobotId) -> SimRobotId {
+    SimRobotId {
+        id: Some(id as u32),
+        team: Some(match color {
+            TeamColor::Blue => Team::Blue,
+            TeamColor::Yellow => Team::Yellow,
+        } as i32),
+    }
+}
+
+fn teleport_robot(color: TeamColor, robot: &ScenarioRobot) -> TeleportRobot {
+    TeleportRobot {
+        id: sim_robot_id(color, robot.id),
+        x: Some(robot.pos.x as f32),
+        y: Some(robot.pos.y as f32),
+        orientation: Some(robot.orientation as f32),
+        v_x: Some(0.),
+        v_y: Some(0.),
+        v_angular: Some(0.),
+        present: Some(true),
+    }
+}
+
+fn remove_robot(color: TeamColor, id: RobotId) -> TeleportRobot {
+    TeleportRobot {
+        id: sim_robot_id(color, id),
+        present: Some(false),
+        ..Default::default()
+    }
+}
+
+fn teleport_command(
+    teleport_ball: Option<TeleportBall>,
+    teleport_robot: Vec<TeleportRobot>,
+) -> SimulatorCommand {
+    SimulatorCommand {
+        control: Some(SimulatorControl {
+            teleport_ball,
+            teleport_robot,
+            simulation_speed: None,
+        }),
+        config: None,
+    }
+}
+
+/// radius in [m] of our robots from their specs, the biggest allowed for the ennemies
+fn robot_radius(world: &World, color: TeamColor, id: RobotId) -> f64 {
+    let ally_radius = if color == world.team_color {
+        world
+            .team
+            .lock()
+            .unwrap_ignore_poison()
+            .get(&id)
+            .map(|r| r.get_specs().radius)
+    } else {
+        None
+    };
+    ally_radius.unwrap_or_else(|| world.field.get_max_robot_radius())
+}
+
+/// The active robots of the world.
+fn visible_robots(world: &World) -> Vec<(TeamColor, RobotId, Point2, f64)> {
+    let mut robots = world
+        .team
+        .lock()
+        .unwrap_ignore_poison()
+        .values()
+        .filter(|r| r.is_active())
+        .map(|r| (r.get_color(), r.get_id(), r.get_pos(), r.get_orientation()))
+        .collect::<Vec<_>>();
+    robots.extend(
+        world
+            .ennemies
+            .lock()
+            .unwrap_ignore_poison()
+            .values()
+            .filter(|r| r.is_active())
+            .map(|r| (r.get_color(), r.get_id(), r.get_pos(), r.get_orientation())),
+    );
+    robots.sort_by_key(|&(color, id, _, _)| (color == TeamColor::Yellow, id));
+    robots
+}
+
+impl Scenario {
+    fn colors(&self) -> [(TeamColor, &Vec<ScenarioRobot>); 2] {
+        [
+            (self.team_color, &self.allies),
+            (self.team_color.opposite(), &self.ennemies),
+        ]
+    }
+
+    fn setup_command(&self) -> SimulatorCommand {
+        let teleport_ball = TeleportBall {
+            x: Some(self.ball_pos.x as f32),
+            y: Some(self.ball_pos.y as f32),
+            z: Some(0.),
+            vx: Some(self.ball_vel.x as f32),
+            vy: Some(self.ball_vel.y as f32),
+            vz: Some(0.),
+            teleport_safely: Some(false),
+            roll: Some(true),
+        };
+        let teleport_robots = self
+            .colors()
+            .into_iter()
+            .flat_map(|(color, robots)| robots.iter().map(move |r| teleport_robot(color, r)))
+            .collect();
+        teleport_command(Some(teleport_ball), teleport_robots)
+    }
+
+    fn is_declared(&self, color: TeamColor, id: RobotId) -> bool {
+        self.colors()
+            .into_iter()
+            .any(|(c, robots)| c == color && robots.iter().any(|r| r.id == id))
+    }
+
+    /// Sets the initial world up in the simulator, then runs `skill` until it's done (and `after_skill`)
+    /// or until `timeout`.
+    /// The skill is given the world of the controlled team once every declared robot has been detected.
+    pub async fn run<S, E, T, F, Fut>(
+        &self,
+        simulator: S,
+        skill: F,
+    ) -> Result<ScenarioOutcome<T>, E>
+    where
+        S: SynchronousSimulator<E>,
+        F: FnOnce(World) -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut world = World::default_with_team_color(self.team_color);
+        if let Some(specs) = simulator.get_fleet_specs(self.team_color) {
+            world.fleet_specs = specs;
+        }
+        world.field.set_side(self.team_color, self.ally_side);
+        let mut simulation = SynchronousSimulation::new(world.clone(), simulator);
+
+        simulation.send_simulator_command(self.setup_command());
+        simulation.step().await?;
+        let undeclared = visible_robots(&world)
+            .into_iter()
+            .filter(|&(color, id, _, _)| !self.is_declared(color, id))
+            .map(|(color, id, _, _)| remove_robot(color, id))
+            .collect::<Vec<_>>();
+        if !undeclared.is_empty() {
+            simulation.send_simulator_command(teleport_command(None, undeclared));
+            simulation.step().await?;
+        }
+        // the detections may lag behind the simulation (e.g. `DelayedDetections`)
+        let undetected = |world: &World| {
+            let robots = visible_robots(world);
+            self.colors()
+                .into_iter()
+                .flat_map(|(color, declared)| declared.iter().map(move |r| (color, r.id)))
+                .filter(|robot| !robots.iter().any(|&(c, id, _, _)| (c, id) == *robot))
+                .collect::<Vec<_>>()
+        };
+        let all_detected = simulation
+            .run_until(|world| undetected(world).is_empty(), DETECTION_TIMEOUT)
+            .await?;
+        if !all_detected {
+            let undetected = undetected(&world);
+            warn!(
+                ?undetected,
+                "robots not detected within {DETECTION_TIMEOUT:?}, not running the skill"
+            );
+            return Ok(ScenarioOutcome {
+                name: self.name.clone(),
+                ennemy_goal: world.get_ennemy_goal_bounding_box(),
+                world,
+                result: None,
+                trace: vec![],
+                collisions: vec![],
+                undetected,
+            });
+        }
+        // forget the removed robots, the world only holds the scenario's ones
+        world
+            .team
+            .lock()
+            .unwrap_ignore_poison()
+            .retain(|&id, _| self.is_declared(self.team_color, id));
+        world
+            .ennemies
+            .lock()
+            .unwrap_ignore_poison()
+            .retain(|&id, _| self.is_declared(self.team_color.opposite(), id));
+
+        let start = simulation.get_time();
+        let mut skill = tokio::spawn(skill(world.clone()));
+        let mut result = None;
+        let mut trace = vec![];
+        let mut collisions = vec![];
+        let mut touching = HashSet::new();
+        let mut end = start + self.timeout.as_secs_f64();
+        while simulation.get_time() < end {
+            simulation.step().await?;
+            let time = simulation.get_time() - start;
+            let robots = visible_robots(&world);
+            for (i, &(color_a, id_a, pos_a, _)) in robots.iter().enumerate() {
+                for &(color_b, id_b, pos_b, _) in &robots[i + 1..] {
+                    let pair = [(color_a, id_a), (color_b, id_b)];
+                    let collision_distance = robot_radius(&world, color_a, id_a)
+                        + robot_radius(&world, color_b, id_b)
+                        + COLLISION_MARGIN;
+                    if pos_a.distance_to(&pos_b) <= collision_distance {
+                        // a long contact is a single collision
+                        if touching.insert(pair) {
+                            collisions.push(Collision { time, robots: pair });
+                        }
+                    } else {
+                        touching.remove(&pair);
+                    }
+                }
+            }
+            trace.push(TraceFrame {
+                time,
+                ball_pos: world.ball.get_pos(),
+                robots,
+            });
+
+            if result.is_none() && skill.is_finished() {
+                result = Some((&mut skill).await.expect("the skill panicked"));
+                end = simulation.get_time() + self.after_skill.as_secs_f64();
+            }
+        }
+        skill.abort();
+
+        Ok(ScenarioOutcome {
+            name: self.name.clone(),
+            ennemy_goal: world.get_ennemy_goal_bounding_box(),
+            world,
+            result,
+            trace,
+            collisions,
+            undetected: vec![],
+        })
+    }
+}
+
+impl<T> ScenarioOutcome<T> {
+    /// The world at the end of the scenario.
+    pub fn get_world(&self) -> &World {
+        &self.world
+    }
+
+    /// What the skill returned, `None` if it timed out (or didn't run, see `get_undetected_robots`).
+    pub fn get_result(&self) -> Option<&T> {
+        self.result.as_ref()
+    }
+
+    pub fn get_trace(&self) -> &[TraceFrame] {
+        &self.trace
+    }
+
+    pub fn get_collisions(&self) -> &[Collision] {
+        &self.collisions
+    }
+
+    /// The declared robots which weren't detected within `DETECTION_TIMEOUT`, the skill didn't run if any.
+    pub fn get_undetected_robots(&self) -> &[(TeamColor, RobotId)] {
+        &self.undetected
+    }
+
+    /// Whether the ball was inside the ennemy goal at some step.
+    pub fn ball_entered_ennemy_goal(&self) -> bool {
+        self.trace
+            .iter()
+            .any(|frame| self.ennemy_goal.contains(frame.ball_pos))
+    }
+
+    /// Final distance in [m] of the ally to `target`, `None` if it isn't visible.
+    pub fn robot_distance_to(&self, id: RobotId, target: Point2) -> Option<f64> {
+        let color = self.world.team_color;
+        self.trace
+            .last()?
+            .robots
+            .iter()
+            .find_map(|&(c, i, pos, _)| (c == color && i == id).then(|| pos.distance_to(&target)))
+    }
+
+    /// Writes the trace as CSV (`time,object,x,y,orientation`) in the temporary directory.
+    pub fn save_trace(&self) -> std::io::Result<PathBuf> {
+        let mut csv = "time,object,x,y,orientation\n".to_string();
+        for frame in &self.trace {
+            let _ = writeln!(
+                csv,
+                "{:.3},ball,{:.4},{:.4},",
+                frame.time, frame.ball_pos.x, frame.ball_pos.y
+            );
+            for (color, id, pos, orientation) in &frame.robots {
+                let _ = writeln!(
+                    csv,
+                    "{:.3},{:?} {},{:.4},{:.4},{:.4}",
+                    frame.time, color, id, pos.x, pos.y, orientation
+                );
+            }
+        }
+        let path = std::env::temp_dir().join(format!("coral_scenario_{}.csv", self.name));
+        std::fs::write(&path, csv)?;
+        Ok(path)
+    }
+
+    /// Panics with `message` & the path of the saved trace if `condition` doesn't hold.
+    #[track_caller]
+    pub fn check(&self, condition: bool, message: &str) {
+        if !condition {
+            let trace = match self.save_trace() {
+                Ok(path) => format!("trace saved to {}", path.display()),
+                Err(e) => format!("couldn't save the trace: {e}"),
+            };
+            panic!("scenario {}: {message} ({trace})", self.name);
+        }
+    }
+
+    #[track_caller]
+    pub fn assert_finished(&self) {
+        self.check(
+            self.undetected.is_empty(),
+            &format!(
+                "robots {:?} weren't detected within {DETECTION_TIMEOUT:?}, the skill didn't run",
+                self.undetected
+            ),
+        );
+        self.check(self.result.is_some(), "the skill timed out");
+    }
+
+    #[track_caller]
+    pub fn assert_ball_entered_ennemy_goal(&self) {
+        self.check(
+            self.ball_entered_ennemy_goal(),
+            "the ball didn't enter the ennemy goal",
+        );
+    }
+
+    /// `tolerance` in [m]
+    #[track_caller]
+    pub fn assert_robot_reached(&self, id: RobotId, target: Point2, tolerance: f64) {
+        let distance = self.robot_distance_to(id, target);
+        self.check(
+            distance.is_some_and(|d| d <= tolerance),
+            &format!("robot {id} ended {distance:?}m away from {target:?}, more than {tolerance}m"),
+        );
+    }
+
+    #[track_caller]
+    pub fn assert_no_collision(&self) {
+        self.check(
+            self.collisions.is_empty(),
+            &format!("collisions happened: {:?}", self.collisions),
+        );
+    }
+}
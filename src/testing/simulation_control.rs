@@ -1,6 +1,11 @@
 use crate::{
-    league_protocols::simulation_packet::{
-        RobotId, SimulatorCommand, SimulatorControl, SimulatorResponse, TeleportRobot,
+    league_protocols::{
+        convert,
+        simulation_packet::{
+            self, Division, RealismConfig, RobotId, RobotLimits, RobotSpecs, SimulatorCommand,
+            SimulatorConfig, SimulatorControl, SimulatorResponse, SslGeometryData, Team,
+            TeleportBall, TeleportRobot,
+        },
     },
     math::{Point2, Vec2},
     net::{udp_transceiver::UdpTransceiver, ReceiveError, SendError},
@@ -8,28 +13,139 @@ use crate::{
 };
 use std::net::Ipv4Addr;
 
+use super::simulator::SimField;
+
 const DEFAULT_IP: Ipv4Addr = Ipv4Addr::LOCALHOST;
 const DEFAULT_PORT: u16 = 10300;
 
+/// error code of a command about a robot which isn't on the field
+pub const UNKNOWN_ROBOT: &str = "UNKNOWN_ROBOT";
+/// error code of a command about a robot without a (valid) team
+pub const UNKNOWN_TEAM: &str = "UNKNOWN_TEAM";
+/// prefix of the error codes of the features a simulator doesn't support
+pub const UNSUPPORTED: &str = "UNSUPPORTED";
+
 pub struct SimulationController {
     socket: UdpTransceiver,
 }
 
+/// An error reported by the simulator, the codes are simulator specific.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulatorError {
+    UnknownRobot(Option<String>),
+    UnknownTeam(Option<String>),
+    Unsupported {
+        code: String,
+        message: Option<String>,
+    },
+    Other {
+        code: Option<String>,
+        message: Option<String>,
+    },
+}
+
+impl From<simulation_packet::SimulatorError> for SimulatorError {
+    fn from(error: simulation_packet::SimulatorError) -> Self {
+        match error.code {
+            Some(code) if code == UNKNOWN_ROBOT => Self::UnknownRobot(error.message),
+            Some(code) if code == UNKNOWN_TEAM => Self::UnknownTeam(error.message),
+            Some(code) if code.starts_with(UNSUPPORTED) => Self::Unsupported {
+                code,
+                message: error.message,
+            },
+            code => Self::Other {
+                code,
+                message: error.message,
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum SimulatorControlError {
     SimulatorControlRequestError(SendError),
     SimulatorControlResponseError(ReceiveError),
+    /// the simulator received the command but reported errors
+    SimulatorErrors(Vec<SimulatorError>),
+}
+
+fn robot_id(id: u8, team: TeamColor) -> RobotId {
+    RobotId {
+        id: Some(id as u32),
+        team: Some(match team {
+            TeamColor::Blue => Team::Blue,
+            TeamColor::Yellow => Team::Yellow,
+        } as i32),
+    }
 }
 
 impl SimulationController {
-    pub async fn new() -> Self {
+    pub async fn new(custom_ip: Option<Ipv4Addr>, custom_port: Option<u16>) -> Self {
         Self {
-            socket: UdpTransceiver::new(DEFAULT_IP, DEFAULT_PORT)
-                .await
-                .expect("Failed to setup simulator controller."),
+            socket: UdpTransceiver::new(
+                custom_ip.unwrap_or(DEFAULT_IP),
+                custom_port.unwrap_or(DEFAULT_PORT),
+            )
+            .await
+            .expect("Failed to setup simulator controller."),
         }
     }
 
+    /// Sends a command and waits for the simulator's response.
+    pub async fn send_command(
+        &mut self,
+        command: SimulatorCommand,
+    ) -> Result<(), SimulatorControlError> {
+        self.socket
+            .send(command)
+            .await
+            .map_err(SimulatorControlError::SimulatorControlRequestError)?;
+        let response = self
+            .socket
+            .receive::<SimulatorResponse>()
+            .await
+            .map_err(SimulatorControlError::SimulatorControlResponseError)?;
+        if response.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(SimulatorControlError::SimulatorErrors(
+                response.errors.into_iter().map(Into::into).collect(),
+            ))
+        }
+    }
+
+    async fn send_control(
+        &mut self,
+        control: SimulatorControl,
+    ) -> Result<(), SimulatorControlError> {
+        self.send_command(SimulatorCommand {
+            control: Some(control),
+            config: None,
+        })
+        .await
+    }
+
+    async fn send_config(&mut self, config: SimulatorConfig) -> Result<(), SimulatorControlError> {
+        self.send_command(SimulatorCommand {
+            control: None,
+            config: Some(config),
+        })
+        .await
+    }
+
+    async fn teleport_robots(
+        &mut self,
+        teleport_robot: Vec<TeleportRobot>,
+    ) -> Result<(), SimulatorControlError> {
+        self.send_control(SimulatorControl {
+            teleport_ball: None,
+            teleport_robot,
+            simulation_speed: None,
+        })
+        .await
+    }
+
+    /// Teleports a robot, it's added to the field if it wasn't there.
     pub async fn tp_robot(
         &mut self,
         id: u8,
@@ -38,37 +154,170 @@ impl SimulationController {
         orientation: Option<f64>,
         vel: Option<Vec2>,
         angular_vel: Option<f64>,
-    ) -> Result<SimulatorResponse, SimulatorControlError> {
-        let rid = RobotId {
-            id: Some(id as u32),
-            team: Some(match team {
-                TeamColor::Blue => 0, // TODO: check if this is right
-                TeamColor::Yellow => 1,
-            }),
-        };
-        self.socket
-            .send(SimulatorCommand {
-                control: Some(SimulatorControl {
-                    teleport_ball: None,
-                    teleport_robot: vec![TeleportRobot {
-                        id: rid,
-                        x: pos.map(|p| p.x as f32),
-                        y: pos.map(|p| p.y as f32),
-                        orientation: orientation.map(|o| o as f32),
-                        v_x: vel.map(|v| v.x as f32),
-                        v_y: vel.map(|v| v.y as f32),
-                        v_angular: angular_vel.map(|s| s as f32),
-                        present: Some(true),
-                    }],
-                    simulation_speed: None,
-                }),
-                config: None,
-            })
-            .await
-            .map_err(SimulatorControlError::SimulatorControlRequestError)?;
-        self.socket
-            .receive::<SimulatorResponse>()
+    ) -> Result<(), SimulatorControlError> {
+        self.teleport_robots(vec![TeleportRobot {
+            id: robot_id(id, team),
+            x: pos.map(|p| p.x as f32),
+            y: pos.map(|p| p.y as f32),
+            orientation: orientation.map(|o| o as f32),
+            v_x: vel.map(|v| v.x as f32),
+            v_y: vel.map(|v| v.y as f32),
+            v_angular: angular_vel.map(|s| s as f32),
+            present: Some(true),
+        }])
+        .await
+    }
+
+    /// Adds a still robot to the field.
+    pub async fn add_robot(
+        &mut self,
+        id: u8,
+        team: TeamColor,
+        pos: Point2,
+        orientation: f64,
+    ) -> Result<(), SimulatorControlError> {
+        self.tp_robot(id, team, Some(pos), Some(orientation), None, None)
             .await
-            .map_err(SimulatorControlError::SimulatorControlResponseError)
+    }
+
+    /// Removes a robot from the field.
+    pub async fn remove_robot(
+        &mut self,
+        id: u8,
+        team: TeamColor,
+    ) -> Result<(), SimulatorControlError> {
+        self.teleport_robots(vec![TeleportRobot {
+            id: robot_id(id, team),
+            present: Some(false),
+            ..Default::default()
+        }])
+        .await
+    }
+
+    async fn send_teleport_ball(
+        &mut self,
+        teleport_ball: TeleportBall,
+    ) -> Result<(), SimulatorControlError> {
+        self.send_control(SimulatorControl {
+            teleport_ball: Some(teleport_ball),
+            teleport_robot: vec![],
+            simulation_speed: None,
+        })
+        .await
+    }
+
+    /// Puts the ball on the ground at `pos`, rolling at `vel` (or still).
+    /// The robots in the way are moved.
+    pub async fn teleport_ball(
+        &mut self,
+        pos: Point2,
+        vel: Option<Vec2>,
+    ) -> Result<(), SimulatorControlError> {
+        self.send_teleport_ball(TeleportBall {
+            x: Some(pos.x as f32),
+            y: Some(pos.y as f32),
+            z: Some(0.),
+            vx: vel.map(|v| v.x as f32),
+            vy: vel.map(|v| v.y as f32),
+            vz: Some(0.),
+            teleport_safely: Some(true),
+            roll: Some(true),
+        })
+        .await
+    }
+
+    /// Kicks the ball from `pos`, as if a robot kicked it: it slides before rolling.
+    /// It's chipped if `vel_z` in [m/s] is positive.
+    pub async fn kick_ball(
+        &mut self,
+        pos: Point2,
+        vel: Vec2,
+        vel_z: f64,
+    ) -> Result<(), SimulatorControlError> {
+        self.send_teleport_ball(TeleportBall {
+            x: Some(pos.x as f32),
+            y: Some(pos.y as f32),
+            z: Some(0.),
+            vx: Some(vel.x as f32),
+            vy: Some(vel.y as f32),
+            vz: Some(vel_z as f32),
+            teleport_safely: Some(false),
+            roll: Some(false),
+        })
+        .await
+    }
+
+    /// Simulated time over real time, 1 is real time.
+    pub async fn set_simulation_speed(&mut self, speed: f64) -> Result<(), SimulatorControlError> {
+        self.send_control(SimulatorControl {
+            teleport_ball: None,
+            teleport_robot: vec![],
+            simulation_speed: Some(speed as f32),
+        })
+        .await
+    }
+
+    pub async fn set_robot_specs(
+        &mut self,
+        specs: impl IntoIterator<Item = RobotSpecs>,
+    ) -> Result<(), SimulatorControlError> {
+        self.send_config(SimulatorConfig {
+            robot_specs: specs.into_iter().collect(),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Only changes the movement limits of the robot, its other specs are kept.
+    pub async fn set_robot_limits(
+        &mut self,
+        id: u8,
+        team: TeamColor,
+        limits: RobotLimits,
+    ) -> Result<(), SimulatorControlError> {
+        self.set_robot_specs([RobotSpecs {
+            id: robot_id(id, team),
+            limits: Some(limits),
+            ..Default::default()
+        }])
+        .await
+    }
+
+    pub async fn set_realism_config(
+        &mut self,
+        realism_config: RealismConfig,
+    ) -> Result<(), SimulatorControlError> {
+        self.send_config(SimulatorConfig {
+            realism_config: Some(realism_config),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Changes the field to the division's one, `DivUnknown` keeps the current field.
+    pub async fn set_division(&mut self, division: Division) -> Result<(), SimulatorControlError> {
+        let field = match division {
+            Division::DivA => SimField::division_a(),
+            Division::DivB => SimField::division_b(),
+            Division::DivUnknown => return Ok(()),
+        };
+        self.send_config(SimulatorConfig {
+            geometry: Some(SslGeometryData {
+                field: convert(&field.to_packet()),
+                calib: vec![],
+                models: None,
+            }),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Port on which the simulator sends the vision packets.
+    pub async fn set_vision_port(&mut self, port: u16) -> Result<(), SimulatorControlError> {
+        self.send_config(SimulatorConfig {
+            vision_port: Some(port as u32),
+            ..Default::default()
+        })
+        .await
     }
 }
@@ -0,0 +1,791 @@
+//! A lightweight 2D simulator running inside Coral, to run the strategies without grSim or ER-Force's simulator.
+//!
+//! It speaks the league's simulation protocol (`RobotControl`, `SimulatorCommand`) and
+//! outputs the detection of a single camera seeing the whole field.
+//! The robots are omnidirectional with acceleration limits, the ball follows the league's ball models
+//! (see `StraightTwoPhaseModel` & `ChipFixedLossModel`), robots can dribble, kick & chip
+//! and collide with each other and with the ball.
+//!
+//! It can be used:
+//! - synchronously, as a `SynchronousSimulator`, for deterministic tests
+//! - over the network like the other simulators, see `SimulatorServer`
+//!
+//! # Examples
+//!
+//! ```
+//! use crabe_async::{
+//!     math::Point2,
+//!     testing::{simulator::Simulator, synchronous_simulation::SynchronousSimulation},
+//!     world::{TeamColor, World},
+//! };
+//!
+//! #[tokio::main(flavor = "current_thread", start_paused = true)]
+//! async fn main() {
+//!     let mut simulator = Simulator::new();
+//!     simulator.add_robot(TeamColor::Blue, 0, Point2::new(-1., 0.), 0.);
+//!     simulator.set_ball(Point2::new(1., 0.), None);
+//!
+//!     let world = World::default_with_team_color(TeamColor::Blue);
+//!     let mut simulation = SynchronousSimulation::new(world.clone(), simulator);
+//!     simulation.step().await.unwrap();
+//!
+//!     let robot = world.team.lock().unwrap()[&0].clone();
+//!     assert!((robot.get_pos().x - -1.).abs() < 1e-3);
+//!     assert!((world.ball.get_pos().x - 1.).abs() < 1e-3);
+//! }
+//! ```
+
+mod physics;
+mod server;
+
+pub use physics::{SimRobotLimits, SimRobotSpecs};
+pub use server::*;
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    future::Future,
+};
+
+use tracing::warn;
+
+use crate::{
+    league_protocols::{
+        convert,
+        simulation_packet::{
+            self, robot_move_command, RobotCommand, RobotControl, RobotControlResponse,
+            SimulationSyncRequest, SimulationSyncResponse, SimulatorCommand, SimulatorError,
+            SimulatorResponse, Team, TeleportBall, TeleportRobot,
+        },
+        vision_packet::{
+            SslBallModelChipFixedLoss, SslBallModelStraightTwoPhase, SslDetectionBall,
+            SslDetectionFrame, SslDetectionRobot, SslGeometryData, SslGeometryFieldSize,
+            SslGeometryModels, SslWrapperPacket,
+        },
+    },
+    math::{Point2, Vec2},
+    world::{
+        ChipFixedLossModel, FleetSpecs, RobotId, SpecsProfile, StraightTwoPhaseModel, TeamColor,
+    },
+    DETECTION_SCALING_FACTOR,
+};
+
+use physics::{ball_robot_interaction, robot_robot_collision, SimBall, SimRobot, Walls};
+
+use super::{
+    simulation_control::{UNKNOWN_ROBOT, UNKNOWN_TEAM, UNSUPPORTED},
+    synchronous_simulation::SynchronousSimulator,
+};
+
+/// max duration in [s] of a physics step, longer simulation steps are split
+const MAX_SUB_STEP: f64 = 0.001;
+
+/// the geometry is sent with one detection frame out of this many
+const GEOMETRY_PERIOD: u32 = 60;
+
+/// duration in [s] of a synchronous step if the request doesn't specify it
+const DEFAULT_SYNC_STEP: f64 = 0.01;
+
+const ROBOTS_SPACING: f64 = 0.3;
+
+/// Dimensions of the simulated field, in [m].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimField {
+    pub field_length: f64,
+    pub field_width: f64,
+    pub goal_width: f64,
+    pub goal_depth: f64,
+    pub goal_height: f64,
+    pub boundary_width: f64,
+    pub defense_area_depth: f64,
+    pub defense_area_width: f64,
+    pub center_circle_radius: f64,
+    pub line_thickness: f64,
+    pub goal_center_to_penalty_mark: f64,
+    pub ball_radius: f64,
+    pub max_robot_radius: f64,
+}
+
+impl Default for SimField {
+    fn default() -> Self {
+        Self::division_b()
+    }
+}
+
+impl SimField {
+    pub fn division_a() -> Self {
+        Self {
+            field_length: 12.,
+            field_width: 9.,
+            goal_width: 1.8,
+            defense_area_depth: 1.8,
+            defense_area_width: 3.6,
+            goal_center_to_penalty_mark: 8.,
+            ..Self::division_b()
+        }
+    }
+
+    pub fn division_b() -> Self {
+        Self {
+            field_length: 9.,
+            field_width: 6.,
+            goal_width: 1.,
+            goal_depth: 0.18,
+            goal_height: 0.155,
+            boundary_width: 0.3,
+            defense_area_depth: 1.,
+            defense_area_width: 2.,
+            center_circle_radius: 0.5,
+            line_thickness: 0.01,
+            goal_center_to_penalty_mark: 6.,
+            ball_radius: 0.0215,
+            max_robot_radius: 0.09,
+        }
+    }
+
+    /// values missing from the packet keep their previous value
+    pub fn update_from_packet(&mut self, packet: &SslGeometryFieldSize) {
+        let mm = |v: i32| v as f64 / DETECTION_SCALING_FACTOR;
+        self.field_length = mm(packet.field_length);
+        self.field_width = mm(packet.field_width);
+        self.goal_width = mm(packet.goal_width);
+        self.goal_depth = mm(packet.goal_depth);
+        self.boundary_width = mm(packet.boundary_width);
+        let update = |value: &mut f64, new: Option<i32>| {
+            if let Some(new) = new {
+                *value = mm(new);
+            }
+        };
+        update(&mut self.goal_height, packet.goal_height);
+        update(&mut self.defense_area_depth, packet.penalty_area_depth);
+        update(&mut self.defense_area_width, packet.penalty_area_width);
+        update(&mut self.center_circle_radius, packet.center_circle_radius);
+        update(&mut self.line_thickness, packet.line_thickness);
+        update(
+            &mut self.goal_center_to_penalty_mark,
+            packet.goal_center_to_penalty_mark,
+        );
+        if let Some(ball_radius) = packet.ball_radius {
+            self.ball_radius = ball_radius as f64 / DETECTION_SCALING_FACTOR;
+        }
+        if let Some(max_robot_radius) = packet.max_robot_radius {
+            self.max_robot_radius = max_robot_radius as f64 / DETECTION_SCALING_FACTOR;
+        }
+    }
+
+    pub fn to_packet(&self) -> SslGeometryFieldSize {
+        let mm = |v: f64| (v * DETECTION_SCALING_FACTOR).round() as i32;
+        SslGeometryFieldSize {
+            field_length: mm(self.field_length),
+            field_width: mm(self.field_width),
+            goal_width: mm(self.goal_width),
+            goal_depth: mm(self.goal_depth),
+            boundary_width: mm(self.boundary_width),
+            field_lines: vec![],
+            field_arcs: vec![],
+            penalty_area_depth: Some(mm(self.defense_area_depth)),
+            penalty_area_width: Some(mm(self.defense_area_width)),
+            center_circle_radius: Some(mm(self.center_circle_radius)),
+            line_thickness: Some(mm(self.line_thickness)),
+            goal_center_to_penalty_mark: Some(mm(self.goal_center_to_penalty_mark)),
+            goal_height: Some(mm(self.goal_height)),
+            ball_radius: Some((self.ball_radius * DETECTION_SCALING_FACTOR) as f32),
+            max_robot_radius: Some((self.max_robot_radius * DETECTION_SCALING_FACTOR) as f32),
+        }
+    }
+
+    fn walls(&self) -> Walls {
+        Walls {
+            half_length: self.field_length / 2.,
+            half_width: self.field_width / 2.,
+            boundary_width: self.boundary_width,
+            half_goal_width: self.goal_width / 2.,
+            goal_depth: self.goal_depth,
+            goal_height: self.goal_height,
+        }
+    }
+}
+
+/// Two robots which started touching each other.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RobotCollision {
+    /// simulated time in [s]
+    pub time: f64,
+    pub robots: [(TeamColor, u32); 2],
+}
+
+fn simulator_error(code: &str, message: String) -> SimulatorError {
+    SimulatorError {
+        code: Some(code.to_string()),
+        message: Some(message),
+    }
+}
+
+fn team_color(team: Option<i32>) -> Option<TeamColor> {
+    match team.and_then(|team| Team::try_from(team).ok()) {
+        Some(Team::Blue) => Some(TeamColor::Blue),
+        Some(Team::Yellow) => Some(TeamColor::Yellow),
+        _ => None,
+    }
+}
+
+/// See the module's documentation.
+pub struct Simulator {
+    field: SimField,
+    robots: Vec<SimRobot>,
+    ball: SimBall,
+    straight_model: StraightTwoPhaseModel,
+    chip_model: ChipFixedLossModel,
+    /// specs of the robots which aren't the default ones, kept for robots which are added later
+    robot_specs: HashMap<(TeamColor, u32), SimRobotSpecs>,
+    /// robots which touched the ball with their dribbler during the last simulation step
+    dribbler_contacts: HashSet<(TeamColor, u32)>,
+    /// pairs of robots currently touching each other
+    touching_robots: HashSet<[(TeamColor, u32); 2]>,
+    collisions: Vec<RobotCollision>,
+    /// simulated time in [s]
+    time: f64,
+    frame_number: u32,
+    /// simulated time over real time, for `SimulatorServer`
+    simulation_speed: f64,
+    /// team controlled through `SynchronousSimulator::step`
+    synchronous_team: TeamColor,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulator {
+    /// An empty division B field with the ball at its center.
+    pub fn new() -> Self {
+        Self {
+            field: SimField::default(),
+            robots: vec![],
+            ball: SimBall::default(),
+            straight_model: StraightTwoPhaseModel::default(),
+            chip_model: ChipFixedLossModel::default(),
+            robot_specs: HashMap::new(),
+            dribbler_contacts: HashSet::new(),
+            touching_robots: HashSet::new(),
+            collisions: vec![],
+            time: 0.,
+            frame_number: 0,
+            simulation_speed: 1.,
+            synchronous_team: TeamColor::Blue,
+        }
+    }
+
+    /// Places `robots_per_team` robots of each team on a line in their half, blue on the negative half.
+    pub fn place_teams(&mut self, robots_per_team: u32) {
+        for (color, side) in [(TeamColor::Blue, -1.), (TeamColor::Yellow, 1.)] {
+            for id in 0..robots_per_team {
+                let y = (id as f64 - (robots_per_team - 1) as f64 / 2.) * ROBOTS_SPACING;
+                let orientation = if side < 0. { 0. } else { std::f64::consts::PI };
+                self.add_robot(color, id, Point2::new(side * 1., y), orientation);
+            }
+        }
+    }
+
+    /// Adds a robot, or teleports it if it's already on the field.
+    pub fn add_robot(&mut self, color: TeamColor, id: u32, pos: Point2, orientation: f64) {
+        match self.get_robot_mut(color, id) {
+            Some(robot) => {
+                robot.pos = pos;
+                robot.orientation = orientation;
+                robot.vel = Vec2::zero();
+                robot.angular_vel = 0.;
+            }
+            None => {
+                let specs = self.get_robot_specs(color, id);
+                self.robots
+                    .push(SimRobot::new(id, color, pos, orientation, specs));
+            }
+        }
+    }
+
+    pub fn remove_robot(&mut self, color: TeamColor, id: u32) {
+        self.robots
+            .retain(|robot| (robot.color, robot.id) != (color, id));
+        if self.ball.dribbled_by == Some((color, id)) {
+            self.ball.dribbled_by = None;
+        }
+    }
+
+    pub fn has_robot(&self, color: TeamColor, id: u32) -> bool {
+        self.get_robot(color, id).is_some()
+    }
+
+    fn get_robot(&self, color: TeamColor, id: u32) -> Option<&SimRobot> {
+        self.robots
+            .iter()
+            .find(|robot| (robot.color, robot.id) == (color, id))
+    }
+
+    fn get_robot_mut(&mut self, color: TeamColor, id: u32) -> Option<&mut SimRobot> {
+        self.robots
+            .iter_mut()
+            .find(|robot| (robot.color, robot.id) == (color, id))
+    }
+
+    pub fn get_robot_pos(&self, color: TeamColor, id: u32) -> Option<Point2> {
+        self.get_robot(color, id).map(|robot| robot.pos)
+    }
+
+    pub fn get_robot_orientation(&self, color: TeamColor, id: u32) -> Option<f64> {
+        self.get_robot(color, id).map(|robot| robot.orientation)
+    }
+
+    pub fn get_robot_vel(&self, color: TeamColor, id: u32) -> Option<Vec2> {
+        self.get_robot(color, id).map(|robot| robot.vel)
+    }
+
+    pub fn get_robot_specs(&self, color: TeamColor, id: u32) -> SimRobotSpecs {
+        self.robot_specs
+            .get(&(color, id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// specs the robots of this team should be driven with
+    pub fn get_fleet_specs(&self, color: TeamColor) -> FleetSpecs {
+        FleetSpecs {
+            default: SimRobotSpecs::default().into(),
+            profiles: self
+                .robot_specs
+                .iter()
+                .filter(|((c, _), _)| *c == color)
+                .map(|(&(_, id), &specs)| SpecsProfile {
+                    ids: vec![id as RobotId],
+                    specs: specs.into(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn set_robot_specs(&mut self, color: TeamColor, id: u32, specs: SimRobotSpecs) {
+        self.robot_specs.insert((color, id), specs);
+        if let Some(robot) = self.get_robot_mut(color, id) {
+            robot.specs = specs;
+        }
+    }
+
+    /// Puts the ball on the ground at `pos`, rolling at `vel` (or still).
+    pub fn set_ball(&mut self, pos: Point2, vel: Option<Vec2>) {
+        self.ball = SimBall {
+            pos,
+            vel: vel.unwrap_or_default(),
+            switch_speed: vel.map_or(0., |v| v.norm()),
+            ..Default::default()
+        };
+    }
+
+    pub fn get_ball_pos(&self) -> Point2 {
+        self.ball.pos
+    }
+
+    pub fn get_ball_vel(&self) -> Vec2 {
+        self.ball.vel
+    }
+
+    /// in [m]
+    pub fn get_ball_height(&self) -> f64 {
+        self.ball.z
+    }
+
+    /// robot holding the ball with its dribbler
+    pub fn get_ball_holder(&self) -> Option<(TeamColor, u32)> {
+        self.ball.dribbled_by
+    }
+
+    pub fn get_field(&self) -> SimField {
+        self.field
+    }
+
+    pub fn set_field(&mut self, field: SimField) {
+        self.field = field;
+    }
+
+    /// simulated time in [s]
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn get_simulation_speed(&self) -> f64 {
+        self.simulation_speed
+    }
+
+    /// collisions between robots since the creation of the simulator
+    pub fn get_collisions(&self) -> &[RobotCollision] {
+        &self.collisions
+    }
+
+    /// the robot control of the synchronous requests is applied to this team (blue by default)
+    pub fn set_synchronous_team(&mut self, color: TeamColor) {
+        self.synchronous_team = color;
+    }
+
+    /// Advances the simulation of `duration` in [s].
+    pub fn simulate(&mut self, duration: f64) {
+        self.dribbler_contacts.clear();
+        if duration <= 0. {
+            return;
+        }
+        let sub_steps = (duration / MAX_SUB_STEP).ceil();
+        let dt = duration / sub_steps;
+        for _ in 0..sub_steps as usize {
+            self.sub_step(dt);
+        }
+    }
+
+    fn sub_step(&mut self, dt: f64) {
+        let walls = self.field.walls();
+        for robot in self.robots.iter_mut() {
+            robot.update(dt);
+            walls.keep_robot_inside(robot);
+        }
+
+        let mut touching_robots = HashSet::new();
+        for i in 0..self.robots.len() {
+            let (left, right) = self.robots.split_at_mut(i + 1);
+            let r1 = &mut left[i];
+            for r2 in right.iter_mut() {
+                if robot_robot_collision(r1, r2) {
+                    let pair = [(r1.color, r1.id), (r2.color, r2.id)];
+                    if !self.touching_robots.contains(&pair) {
+                        self.collisions.push(RobotCollision {
+                            time: self.time,
+                            robots: pair,
+                        });
+                    }
+                    touching_robots.insert(pair);
+                }
+            }
+        }
+        self.touching_robots = touching_robots;
+
+        let previous_ball_pos = self.ball.pos;
+        if self.ball.dribbled_by.is_none() {
+            self.ball.update(dt, &self.straight_model, &self.chip_model);
+        }
+        for robot in self.robots.iter_mut() {
+            let contact = ball_robot_interaction(
+                &mut self.ball,
+                self.field.ball_radius,
+                robot,
+                &self.straight_model,
+            );
+            if contact.dribbler {
+                self.dribbler_contacts.insert((robot.color, robot.id));
+            }
+        }
+        walls.bounce_ball(&mut self.ball, self.field.ball_radius, previous_ball_pos);
+        self.time += dt;
+    }
+
+    /// Applies the commands of a team's robots, returns their feedback.
+    pub fn handle_robot_control(
+        &mut self,
+        color: TeamColor,
+        control: RobotControl,
+    ) -> RobotControlResponse {
+        let mut response = RobotControlResponse::default();
+        for command in control.robot_commands {
+            match self.get_robot_mut(color, command.id) {
+                Some(robot) => apply_robot_command(robot, command),
+                None => response.errors.push(simulator_error(
+                    UNKNOWN_ROBOT,
+                    format!("there is no {:?} robot {} on the field", color, command.id),
+                )),
+            }
+        }
+        response.feedback = self
+            .robots
+            .iter()
+            .filter(|robot| robot.color == color)
+            .map(|robot| simulation_packet::RobotFeedback {
+                id: robot.id,
+                dribbler_ball_contact: Some(self.dribbler_contacts.contains(&(color, robot.id))),
+                custom: None,
+            })
+            .collect();
+        response
+    }
+
+    /// Applies a simulator command (teleportations, configuration..).
+    pub fn handle_simulator_command(&mut self, command: SimulatorCommand) -> SimulatorResponse {
+        let mut response = SimulatorResponse::default();
+        if let Some(control) = command.control {
+            if let Some(teleport_ball) = control.teleport_ball {
+                self.teleport_ball(teleport_ball);
+            }
+            for teleport_robot in control.teleport_robot {
+                if let Err(error) = self.teleport_robot(teleport_robot) {
+                    response.errors.push(error);
+                }
+            }
+            if let Some(speed) = control.simulation_speed {
+                self.simulation_speed = speed as f64;
+            }
+        }
+        if let Some(config) = command.config {
+            if let Some(geometry) = config.geometry {
+                self.field.update_from_packet(&convert(&geometry.field));
+                if let Some(models) = geometry.models {
+                    if let Some(straight) = models.straight_two_phase {
+                        self.straight_model =
+                            convert::<_, SslBallModelStraightTwoPhase>(&straight).into();
+                    }
+                    if let Some(chip) = models.chip_fixed_loss {
+                        self.chip_model = convert::<_, SslBallModelChipFixedLoss>(&chip).into();
+                    }
+                }
+            }
+            for specs in config.robot_specs {
+                let Some(color) = team_color(specs.id.team) else {
+                    response.errors.push(simulator_error(
+                        UNKNOWN_TEAM,
+                        "robot specs without team".to_string(),
+                    ));
+                    continue;
+                };
+                let id = specs.id.id.unwrap_or_default();
+                let mut robot_specs = self.get_robot_specs(color, id);
+                robot_specs.update_from_packet(specs);
+                self.set_robot_specs(color, id, robot_specs);
+            }
+            if config.vision_port.is_some() {
+                response.errors.push(simulator_error(
+                    &format!("{UNSUPPORTED}_VISION_PORT"),
+                    "the vision port can't be changed".to_string(),
+                ));
+            }
+        }
+        response
+    }
+
+    fn teleport_ball(&mut self, teleport: TeleportBall) {
+        let ball = &mut self.ball;
+        ball.dribbled_by = None;
+        ball.pos = Point2::new(
+            teleport.x.map_or(ball.pos.x, f64::from),
+            teleport.y.map_or(ball.pos.y, f64::from),
+        );
+        ball.z = teleport.z.map_or(0., f64::from);
+        ball.vel = Vec2::new(
+            teleport.vx.map_or(0., f64::from),
+            teleport.vy.map_or(0., f64::from),
+        );
+        ball.vel_z = teleport.vz.map_or(0., f64::from);
+        ball.hops = 0;
+        ball.switch_speed = if teleport.roll() {
+            ball.vel.norm()
+        } else {
+            ball.vel.norm() * self.straight_model.k_switch
+        };
+
+        if teleport.teleport_safely() {
+            let ball_pos = ball.pos;
+            let clearance = self.field.ball_radius;
+            for robot in self.robots.iter_mut() {
+                let delta = robot.pos - ball_pos;
+                let min_distance = robot.specs.radius + clearance;
+                if delta.norm() < min_distance {
+                    let direction = if delta.norm() > 0. {
+                        delta.normalized()
+                    } else {
+                        Vec2::new(1., 0.)
+                    };
+                    robot.pos = ball_pos + direction * min_distance;
+                    robot.vel = Vec2::zero();
+                    robot.angular_vel = 0.;
+                }
+            }
+        }
+    }
+
+    fn teleport_robot(&mut self, teleport: TeleportRobot) -> Result<(), SimulatorError> {
+        let color = team_color(teleport.id.team).ok_or(simulator_error(
+            UNKNOWN_TEAM,
+            "teleported robot without team".to_string(),
+        ))?;
+        let id = teleport.id.id.unwrap_or_default();
+        match teleport.present {
+            Some(false) => {
+                self.remove_robot(color, id);
+                return Ok(());
+            }
+            Some(true) if !self.has_robot(color, id) => {
+                self.add_robot(color, id, Point2::zero(), 0.);
+            }
+            _ => {}
+        }
+        let ball_holder = self.ball.dribbled_by;
+        let robot = self.get_robot_mut(color, id).ok_or(simulator_error(
+            UNKNOWN_ROBOT,
+            format!("there is no {:?} robot {} on the field", color, id),
+        ))?;
+        robot.pos = Point2::new(
+            teleport.x.map_or(robot.pos.x, f64::from),
+            teleport.y.map_or(robot.pos.y, f64::from),
+        );
+        robot.orientation = teleport.orientation.map_or(robot.orientation, f64::from);
+        robot.vel = Vec2::new(teleport.v_x() as f64, teleport.v_y() as f64);
+        robot.angular_vel = teleport.v_angular() as f64;
+        if ball_holder == Some((color, id)) {
+            self.ball.dribbled_by = None;
+        }
+        Ok(())
+    }
+
+    /// What the (single) camera sees, in the vision's units.
+    pub fn get_detection(&self) -> SslDetectionFrame {
+        let detect_robot = |robot: &SimRobot| SslDetectionRobot {
+            confidence: 1.,
+            robot_id: Some(robot.id),
+            x: (robot.pos.x * DETECTION_SCALING_FACTOR) as f32,
+            y: (robot.pos.y * DETECTION_SCALING_FACTOR) as f32,
+            orientation: Some(robot.orientation as f32),
+            pixel_x: 0.,
+            pixel_y: 0.,
+            height: Some((robot.specs.height * DETECTION_SCALING_FACTOR) as f32),
+        };
+        let robots_of = |color: TeamColor| {
+            self.robots
+                .iter()
+                .filter(|robot| robot.color == color)
+                .map(detect_robot)
+                .collect()
+        };
+        SslDetectionFrame {
+            frame_number: self.frame_number,
+            t_capture: self.time,
+            t_sent: self.time,
+            camera_id: 0,
+            balls: vec![SslDetectionBall {
+                confidence: 1.,
+                area: None,
+                x: (self.ball.pos.x * DETECTION_SCALING_FACTOR) as f32,
+                y: (self.ball.pos.y * DETECTION_SCALING_FACTOR) as f32,
+                z: Some((self.ball.z * DETECTION_SCALING_FACTOR) as f32),
+                pixel_x: 0.,
+                pixel_y: 0.,
+            }],
+            robots_yellow: robots_of(TeamColor::Yellow),
+            robots_blue: robots_of(TeamColor::Blue),
+        }
+    }
+
+    pub fn get_geometry(&self) -> SslGeometryData {
+        SslGeometryData {
+            field: self.field.to_packet(),
+            calib: vec![],
+            models: Some(SslGeometryModels {
+                straight_two_phase: Some(SslBallModelStraightTwoPhase {
+                    acc_slide: self.straight_model.acc_slide,
+                    acc_roll: self.straight_model.acc_roll,
+                    k_switch: self.straight_model.k_switch,
+                }),
+                chip_fixed_loss: Some(SslBallModelChipFixedLoss {
+                    damping_xy_first_hop: self.chip_model.damping_xy_first_hop,
+                    damping_xy_other_hops: self.chip_model.damping_xy_other_hops,
+                    damping_z: self.chip_model.damping_z,
+                }),
+            }),
+        }
+    }
+
+    /// The next vision packet, the geometry is sent every `GEOMETRY_PERIOD` frames.
+    pub fn next_vision_packet(&mut self) -> SslWrapperPacket {
+        let packet = SslWrapperPacket {
+            detection: Some(self.get_detection()),
+            geometry: self
+                .frame_number
+                .is_multiple_of(GEOMETRY_PERIOD)
+                .then(|| self.get_geometry()),
+        };
+        self.frame_number += 1;
+        packet
+    }
+
+    /// Applies the request's commands, simulates its step and returns the detection at the end of it.
+    pub fn step_synchronously(&mut self, request: SimulationSyncRequest) -> SimulationSyncResponse {
+        if let Some(command) = request.simulator_command {
+            for error in self.handle_simulator_command(command).errors {
+                warn!(?error, "simulator command failed");
+            }
+        }
+        // the feedback must be the one after the step, the commands are applied before
+        if let Some(control) = request.robot_control {
+            let errors = self
+                .handle_robot_control(self.synchronous_team, control)
+                .errors;
+            for error in errors {
+                warn!(?error, "robot command failed");
+            }
+        }
+        self.simulate(request.sim_step.map_or(DEFAULT_SYNC_STEP, f64::from));
+        let robot_control_response =
+            self.handle_robot_control(self.synchronous_team, RobotControl::default());
+        let detection = self.get_detection();
+        self.frame_number += 1;
+        SimulationSyncResponse {
+            detection: vec![convert(&detection)],
+            robot_control_response: Some(robot_control_response),
+        }
+    }
+}
+
+fn apply_robot_command(robot: &mut SimRobot, command: RobotCommand) {
+    match command
+        .move_command
+        .and_then(|move_command| move_command.command)
+    {
+        Some(robot_move_command::Command::LocalVelocity(vel)) => {
+            robot.target_vel = Vec2::new(vel.forward as f64, vel.left as f64);
+            robot.target_angular_vel = vel.angular as f64;
+        }
+        Some(robot_move_command::Command::GlobalVelocity(vel)) => {
+            robot.target_vel =
+                physics::rotate(Vec2::new(vel.x as f64, vel.y as f64), -robot.orientation);
+            robot.target_angular_vel = vel.angular as f64;
+        }
+        Some(robot_move_command::Command::WheelVelocity(vel)) => {
+            let (target_vel, target_angular_vel) = robot.specs.local_vel_from_wheels(
+                [
+                    vel.front_right,
+                    vel.back_right,
+                    vel.back_left,
+                    vel.front_left,
+                ]
+                .map(f64::from),
+            );
+            robot.target_vel = target_vel;
+            robot.target_angular_vel = target_angular_vel;
+        }
+        None => {}
+    }
+    robot.kick = command
+        .kick_speed
+        .filter(|speed| *speed > 0.)
+        .map(|speed| (speed as f64, command.kick_angle() as f64));
+    if let Some(dribbler_speed) = command.dribbler_speed {
+        robot.dribbler_speed = dribbler_speed as f64;
+    }
+}
+
+impl SynchronousSimulator<Infallible> for Simulator {
+    fn step(
+        &mut self,
+        request: SimulationSyncRequest,
+    ) -> impl Future<Output = Result<SimulationSyncResponse, Infallible>> + Send {
+        let response = self.step_synchronously(request);
+        async { Ok(response) }
+    }
+
+    fn get_fleet_specs(&self, color: TeamColor) -> Option<FleetSpecs> {
+        Some(Simulator::get_fleet_specs(self, color))
+    }
+}
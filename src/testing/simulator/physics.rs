@@ -0,0 +1,621 @@
+use std::f64::consts::PI;
+
+use crate::{
+    league_protocols::simulation_packet::{RobotLimits, RobotSpecs},
+    math::{wrap_angle, Point2, Vec2},
+    trajectories::ball::{CHIP_MIN_VEL_Z, GRAVITY},
+    world::{
+        ChipFixedLossModel, KinematicLimits, RobotSpecs as Specs, StraightTwoPhaseModel, TeamColor,
+    },
+};
+
+/// part of the normal velocity kept by the ball when it bounces on a robot
+const BALL_ROBOT_RESTITUTION: f64 = 0.5;
+
+/// part of the normal velocity kept by the ball when it bounces on a wall
+const BALL_WALL_RESTITUTION: f64 = 0.6;
+
+/// the goals' nets absorb most of the ball's energy
+const BALL_GOAL_RESTITUTION: f64 = 0.1;
+
+/// the ball touches the dribbler when it's closer than this to it, in [m]
+const DRIBBLER_CONTACT_MARGIN: f64 = 0.005;
+
+/// the dribbler can't capture a ball going faster than this relative to the robot, in [m/s]
+const DRIBBLER_MAX_CAPTURE_SPEED: f64 = 1.5;
+
+/// Movement limits of a simulated robot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimRobotLimits {
+    /// in [m/s^2]
+    pub acc_speedup_absolute_max: f64,
+    /// in [rad/s^2]
+    pub acc_speedup_angular_max: f64,
+    /// in [m/s^2]
+    pub acc_brake_absolute_max: f64,
+    /// in [rad/s^2]
+    pub acc_brake_angular_max: f64,
+    /// in [m/s]
+    pub vel_absolute_max: f64,
+    /// in [rad/s]
+    pub vel_angular_max: f64,
+}
+
+impl Default for SimRobotLimits {
+    fn default() -> Self {
+        Self {
+            acc_speedup_absolute_max: 4.,
+            acc_speedup_angular_max: 50.,
+            acc_brake_absolute_max: 6.,
+            acc_brake_angular_max: 50.,
+            vel_absolute_max: 4.,
+            vel_angular_max: 20.,
+        }
+    }
+}
+
+impl SimRobotLimits {
+    /// limits missing from the packet keep their previous value
+    pub fn update_from_packet(&mut self, limits: RobotLimits) {
+        let update = |value: &mut f64, new: Option<f32>| {
+            if let Some(new) = new {
+                *value = new as f64;
+            }
+        };
+        update(
+            &mut self.acc_speedup_absolute_max,
+            limits.acc_speedup_absolute_max,
+        );
+        update(
+            &mut self.acc_speedup_angular_max,
+            limits.acc_speedup_angular_max,
+        );
+        update(
+            &mut self.acc_brake_absolute_max,
+            limits.acc_brake_absolute_max,
+        );
+        update(
+            &mut self.acc_brake_angular_max,
+            limits.acc_brake_angular_max,
+        );
+        update(&mut self.vel_absolute_max, limits.vel_absolute_max);
+        update(&mut self.vel_angular_max, limits.vel_angular_max);
+    }
+}
+
+impl From<SimRobotLimits> for KinematicLimits {
+    fn from(limits: SimRobotLimits) -> Self {
+        Self {
+            max_vel: limits.vel_absolute_max,
+            max_acc: limits.acc_speedup_absolute_max,
+            max_dec: limits.acc_brake_absolute_max,
+            max_angular_vel: limits.vel_angular_max,
+            max_angular_acc: limits.acc_speedup_angular_max,
+        }
+    }
+}
+
+/// Physical characteristics of a simulated robot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimRobotSpecs {
+    /// in [m]
+    pub radius: f64,
+    /// in [m]
+    pub height: f64,
+    /// distance from the robot's center to its (flat) dribbler in [m]
+    pub center_to_dribbler: f64,
+    /// in [m/s], unlimited if `None`
+    pub max_linear_kick_speed: Option<f64>,
+    /// in [m/s], unlimited if `None`
+    pub max_chip_kick_speed: Option<f64>,
+    pub limits: SimRobotLimits,
+    /// angles of the wheels in [rad], clockwise from the front: front right, back right, back left, front left
+    pub wheel_angles: [f64; 4],
+}
+
+impl Default for SimRobotSpecs {
+    fn default() -> Self {
+        Self {
+            radius: 0.09,
+            height: 0.15,
+            center_to_dribbler: 0.075,
+            max_linear_kick_speed: Some(6.5),
+            max_chip_kick_speed: Some(5.),
+            limits: SimRobotLimits::default(),
+            wheel_angles: [PI / 3., 3. * PI / 4., 5. * PI / 4., 5. * PI / 3.],
+        }
+    }
+}
+
+/// the specs our robots should be driven with in this simulator
+impl From<SimRobotSpecs> for Specs {
+    fn from(specs: SimRobotSpecs) -> Self {
+        let center_to_dribbler = specs.center_to_dribbler.min(specs.radius);
+        Self {
+            radius: specs.radius,
+            height: specs.height,
+            dribbler_width: 2. * (specs.radius.powi(2) - center_to_dribbler.powi(2)).sqrt(),
+            max_kick_speed: specs
+                .max_linear_kick_speed
+                .unwrap_or(Specs::default().max_kick_speed),
+            limits: specs.limits.into(),
+        }
+    }
+}
+
+impl SimRobotSpecs {
+    /// specs missing from the packet keep their previous value
+    pub fn update_from_packet(&mut self, specs: RobotSpecs) {
+        if let Some(radius) = specs.radius {
+            self.radius = radius as f64;
+        }
+        if let Some(height) = specs.height {
+            self.height = height as f64;
+        }
+        if let Some(center_to_dribbler) = specs.center_to_dribbler {
+            self.center_to_dribbler = center_to_dribbler as f64;
+        }
+        if let Some(speed) = specs.max_linear_kick_speed {
+            self.max_linear_kick_speed = Some(speed as f64);
+        }
+        if let Some(speed) = specs.max_chip_kick_speed {
+            self.max_chip_kick_speed = Some(speed as f64);
+        }
+        if let Some(limits) = specs.limits {
+            self.limits.update_from_packet(limits);
+        }
+        if let Some(angles) = specs.wheel_angles {
+            self.wheel_angles = [
+                angles.front_right,
+                angles.back_right,
+                angles.back_left,
+                angles.front_left,
+            ]
+            .map(f64::from);
+        }
+    }
+
+    /// half the width of the flat front of the robot, in [m]
+    fn dribbler_half_width(&self) -> f64 {
+        (self.radius.powi(2) - self.center_to_dribbler.powi(2))
+            .max(0.)
+            .sqrt()
+    }
+
+    /// Local velocity (forward, left, angular) from the speeds in [m/s] of the wheels, in the order of `wheel_angles`.
+    /// The wheels are redundant: the least squares solution is used.
+    pub(super) fn local_vel_from_wheels(&self, wheel_speeds: [f64; 4]) -> (Vec2, f64) {
+        // each wheel's speed is sin(a) * forward + cos(a) * left + radius * angular
+        let rows = self.wheel_angles.map(|a| [a.sin(), a.cos(), self.radius]);
+        // normal equations (AᵀA) x = Aᵀv, solved with Cramer's rule
+        let mut ata = [[0.; 3]; 3];
+        let mut atv = [0.; 3];
+        for (row, speed) in rows.iter().zip(wheel_speeds) {
+            for i in 0..3 {
+                atv[i] += row[i] * speed;
+                for j in 0..3 {
+                    ata[i][j] += row[i] * row[j];
+                }
+            }
+        }
+        let det = |m: [[f64; 3]; 3]| {
+            m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+                - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+                + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+        };
+        let d = det(ata);
+        if d.abs() < f64::EPSILON {
+            return (Vec2::zero(), 0.);
+        }
+        let solve = |column: usize| {
+            let mut m = ata;
+            for (i, row) in m.iter_mut().enumerate() {
+                row[column] = atv[i];
+            }
+            det(m) / d
+        };
+        (Vec2::new(solve(0), solve(1)), solve(2))
+    }
+}
+
+pub(super) fn rotate(v: Vec2, angle: f64) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+/// `v` with a norm of at most `max`
+fn clamp_norm(v: Vec2, max: f64) -> Vec2 {
+    let norm = v.norm();
+    if norm > max {
+        v * (max / norm)
+    } else {
+        v
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct SimRobot {
+    pub id: u32,
+    pub color: TeamColor,
+    pub pos: Point2,
+    pub orientation: f64,
+    /// in the field's frame
+    pub vel: Vec2,
+    pub angular_vel: f64,
+    /// commanded velocity, in the robot's frame (forward, left)
+    pub target_vel: Vec2,
+    pub target_angular_vel: f64,
+    /// 0 when the dribbler is off
+    pub dribbler_speed: f64,
+    /// (speed [m/s], angle [deg]) of the kick to trigger when the ball touches the dribbler
+    pub kick: Option<(f64, f64)>,
+    pub specs: SimRobotSpecs,
+}
+
+impl SimRobot {
+    pub fn new(
+        id: u32,
+        color: TeamColor,
+        pos: Point2,
+        orientation: f64,
+        specs: SimRobotSpecs,
+    ) -> Self {
+        Self {
+            id,
+            color,
+            pos,
+            orientation,
+            vel: Vec2::zero(),
+            angular_vel: 0.,
+            target_vel: Vec2::zero(),
+            target_angular_vel: 0.,
+            dribbler_speed: 0.,
+            kick: None,
+            specs,
+        }
+    }
+
+    fn forward(&self) -> Vec2 {
+        rotate(Vec2::new(1., 0.), self.orientation)
+    }
+
+    /// velocity of a point of the robot, rotation included
+    fn point_vel(&self, p: Point2) -> Vec2 {
+        let r = p - self.pos;
+        self.vel + Vec2::new(-r.y, r.x) * self.angular_vel
+    }
+
+    /// where a ball held by the dribbler is
+    fn dribbler_ball_pos(&self, ball_radius: f64) -> Point2 {
+        self.pos + self.forward() * (self.specs.center_to_dribbler + ball_radius)
+    }
+
+    /// accelerates towards the commanded velocity within the robot's limits, then moves
+    pub fn update(&mut self, dt: f64) {
+        let limits = self.specs.limits;
+
+        let target_vel = clamp_norm(
+            rotate(self.target_vel, self.orientation),
+            limits.vel_absolute_max,
+        );
+        let acc = if target_vel.norm() > self.vel.norm() {
+            limits.acc_speedup_absolute_max
+        } else {
+            limits.acc_brake_absolute_max
+        };
+        self.vel = self.vel + clamp_norm(target_vel - self.vel, acc * dt);
+
+        let target_angular_vel = self
+            .target_angular_vel
+            .clamp(-limits.vel_angular_max, limits.vel_angular_max);
+        let angular_acc = if target_angular_vel.abs() > self.angular_vel.abs() {
+            limits.acc_speedup_angular_max
+        } else {
+            limits.acc_brake_angular_max
+        };
+        self.angular_vel +=
+            (target_angular_vel - self.angular_vel).clamp(-angular_acc * dt, angular_acc * dt);
+
+        self.pos += self.vel * dt;
+        self.orientation = wrap_angle(self.orientation + self.angular_vel * dt);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(super) struct SimBall {
+    pub pos: Point2,
+    /// height in [m]
+    pub z: f64,
+    pub vel: Vec2,
+    pub vel_z: f64,
+    /// the ball slides faster than this in [m/s], and rolls under it
+    pub switch_speed: f64,
+    /// hops since the last chip kick
+    pub hops: u32,
+    /// robot holding the ball with its dribbler
+    pub dribbled_by: Option<(TeamColor, u32)>,
+}
+
+impl Default for SimBall {
+    fn default() -> Self {
+        Self {
+            pos: Point2::zero(),
+            z: 0.,
+            vel: Vec2::zero(),
+            vel_z: 0.,
+            switch_speed: 0.,
+            hops: 0,
+            dribbled_by: None,
+        }
+    }
+}
+
+impl SimBall {
+    pub fn is_in_the_air(&self) -> bool {
+        self.z > 0. || self.vel_z > 0.
+    }
+
+    /// the ball will slide until it slows down to `k_switch` times its current speed
+    fn start_sliding(&mut self, model: &StraightTwoPhaseModel) {
+        self.switch_speed = self.vel.norm() * model.k_switch;
+    }
+
+    /// moves a free ball along the league's ball models
+    pub fn update(&mut self, dt: f64, straight: &StraightTwoPhaseModel, chip: &ChipFixedLossModel) {
+        if self.is_in_the_air() {
+            self.pos += self.vel * dt;
+            self.z += self.vel_z * dt - GRAVITY * dt.powi(2) / 2.;
+            self.vel_z -= GRAVITY * dt;
+            if self.z <= 0. {
+                self.z = 0.;
+                self.vel_z = -self.vel_z * chip.damping_z;
+                let damping_xy = if self.hops == 0 {
+                    chip.damping_xy_first_hop
+                } else {
+                    chip.damping_xy_other_hops
+                };
+                self.vel = self.vel * damping_xy;
+                self.hops += 1;
+                if self.vel_z < CHIP_MIN_VEL_Z {
+                    self.vel_z = 0.;
+                    // the ball rolls after its last hop
+                    self.switch_speed = self.vel.norm();
+                }
+            }
+            return;
+        }
+
+        let speed = self.vel.norm();
+        if speed == 0. {
+            return;
+        }
+        let acc = if speed > self.switch_speed {
+            straight.acc_slide
+        } else {
+            straight.acc_roll
+        };
+        let new_speed = (speed + acc * dt).max(0.);
+        self.pos += self.vel * ((speed + new_speed) / 2. / speed * dt);
+        self.vel = self.vel * (new_speed / speed);
+    }
+}
+
+/// Result of the interaction between the ball and a robot.
+pub(super) struct BallContact {
+    /// the ball touches the dribbler
+    pub dribbler: bool,
+}
+
+/// Bounces the ball on a robot, captures it with the dribbler & kicks it.
+pub(super) fn ball_robot_interaction(
+    ball: &mut SimBall,
+    ball_radius: f64,
+    robot: &mut SimRobot,
+    straight: &StraightTwoPhaseModel,
+) -> BallContact {
+    let specs = robot.specs;
+    let robot_id = (robot.color, robot.id);
+
+    if ball.dribbled_by == Some(robot_id) {
+        if robot.dribbler_speed <= 0. {
+            ball.dribbled_by = None;
+        } else {
+            ball.pos = robot.dribbler_ball_pos(ball_radius);
+            ball.vel = robot.point_vel(ball.pos);
+            ball.switch_speed = ball.vel.norm();
+        }
+    }
+
+    if ball.z > specs.height {
+        return BallContact { dribbler: false };
+    }
+
+    let local = rotate(ball.pos - robot.pos, -robot.orientation);
+    let half_width = specs.dribbler_half_width();
+    let in_front = local.x > 0. && local.y.abs() <= half_width;
+    let (normal, distance) = if in_front {
+        (Vec2::new(1., 0.), local.x - specs.center_to_dribbler)
+    } else {
+        (local.normalized(), local.norm() - specs.radius)
+    };
+    let touches_dribbler = in_front && distance < ball_radius + DRIBBLER_CONTACT_MARGIN;
+
+    if distance < ball_radius && ball.dribbled_by != Some(robot_id) {
+        let normal = rotate(normal, robot.orientation);
+        ball.pos += normal * (ball_radius - distance);
+        let relative_vel = ball.vel - robot.point_vel(ball.pos);
+        let normal_vel = relative_vel.dot(normal);
+        if normal_vel < 0. {
+            ball.vel = ball.vel - normal * (normal_vel * (1. + BALL_ROBOT_RESTITUTION));
+            ball.start_sliding(straight);
+        }
+        ball.dribbled_by = ball.dribbled_by.filter(|holder| *holder != robot_id);
+    }
+
+    let held = ball.dribbled_by == Some(robot_id);
+    if touches_dribbler
+        && !held
+        && ball.dribbled_by.is_none()
+        && robot.dribbler_speed > 0.
+        && (ball.vel - robot.point_vel(ball.pos)).norm() < DRIBBLER_MAX_CAPTURE_SPEED
+    {
+        ball.dribbled_by = Some(robot_id);
+    }
+
+    if let Some((speed, angle)) = robot.kick {
+        if touches_dribbler || held {
+            let angle = angle.to_radians();
+            let max_speed = if angle > 0. {
+                specs.max_chip_kick_speed
+            } else {
+                specs.max_linear_kick_speed
+            };
+            let speed = max_speed.map_or(speed, |max| speed.min(max));
+            ball.vel = robot.vel + robot.forward() * (speed * angle.cos());
+            ball.vel_z = speed * angle.sin();
+            ball.hops = 0;
+            ball.dribbled_by = None;
+            ball.start_sliding(straight);
+            robot.kick = None;
+        }
+    }
+
+    BallContact {
+        dribbler: touches_dribbler || ball.dribbled_by == Some(robot_id),
+    }
+}
+
+/// Pushes overlapping robots apart, they lose the velocity that made them collide.
+/// Returns whether they collided.
+///
+/// Only the normal velocities are exchanged: there's no friction between the robots,
+/// so they slide along each other without spinning.
+pub(super) fn robot_robot_collision(r1: &mut SimRobot, r2: &mut SimRobot) -> bool {
+    let delta = r2.pos - r1.pos;
+    let distance = delta.norm();
+    let overlap = r1.specs.radius + r2.specs.radius - distance;
+    if overlap <= 0. || distance == 0. {
+        return false;
+    }
+    let normal = delta / distance;
+    r1.pos -= normal * (overlap / 2.);
+    r2.pos += normal * (overlap / 2.);
+    let normal_vel = (r2.vel - r1.vel).dot(normal);
+    if normal_vel < 0. {
+        r1.vel = r1.vel + normal * (normal_vel / 2.);
+        r2.vel = r2.vel - normal * (normal_vel / 2.);
+    }
+    true
+}
+
+/// Moves a robot overlapping the wall from `start` to `end` out of it, it loses the velocity going into the wall.
+fn push_robot_out_of_wall(robot: &mut SimRobot, start: Point2, end: Point2) {
+    let wall = end - start;
+    let t = ((robot.pos - start).dot(wall) / wall.dot(wall)).clamp(0., 1.);
+    let delta = robot.pos - (start + wall * t);
+    let distance = delta.norm();
+    if distance >= robot.specs.radius || distance == 0. {
+        return;
+    }
+    let normal = delta / distance;
+    robot.pos += normal * (robot.specs.radius - distance);
+    let normal_vel = robot.vel.dot(normal);
+    if normal_vel < 0. {
+        robot.vel = robot.vel - normal * normal_vel;
+    }
+}
+
+/// Walls around the field & of the goals.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Walls {
+    /// half length of the field, lines included, in [m]
+    pub half_length: f64,
+    pub half_width: f64,
+    pub boundary_width: f64,
+    pub half_goal_width: f64,
+    pub goal_depth: f64,
+    /// balls flying higher than this in [m] go over the goals
+    pub goal_height: f64,
+}
+
+impl Walls {
+    /// keeps the robot between the field's walls & out of the goals' walls
+    pub fn keep_robot_inside(&self, robot: &mut SimRobot) {
+        let max_x = self.half_length + self.boundary_width - robot.specs.radius;
+        let max_y = self.half_width + self.boundary_width - robot.specs.radius;
+        if robot.pos.x.abs() > max_x {
+            robot.pos.x = robot.pos.x.clamp(-max_x, max_x);
+            robot.vel.x = 0.;
+        }
+        if robot.pos.y.abs() > max_y {
+            robot.pos.y = robot.pos.y.clamp(-max_y, max_y);
+            robot.vel.y = 0.;
+        }
+        for (start, end) in self.goal_walls() {
+            push_robot_out_of_wall(robot, start, end);
+        }
+    }
+
+    /// side & back walls of both goals, as segments
+    fn goal_walls(&self) -> Vec<(Point2, Point2)> {
+        let mut walls = vec![];
+        for side in [-1., 1.] {
+            let goal_line_x = side * self.half_length;
+            let back_x = side * (self.half_length + self.goal_depth);
+            for y in [-self.half_goal_width, self.half_goal_width] {
+                walls.push((Point2::new(goal_line_x, y), Point2::new(back_x, y)));
+            }
+            walls.push((
+                Point2::new(back_x, -self.half_goal_width),
+                Point2::new(back_x, self.half_goal_width),
+            ));
+        }
+        walls
+    }
+
+    /// bounces the ball on the walls, `previous_pos` is used to know if the ball came in a goal
+    pub fn bounce_ball(&self, ball: &mut SimBall, ball_radius: f64, previous_pos: Point2) {
+        let bounce = |pos: &mut f64, vel: &mut f64, max: f64, restitution: f64| {
+            if pos.abs() > max {
+                *pos = pos.clamp(-max, max);
+                *vel = -*vel * restitution;
+            }
+        };
+
+        let was_in_goal =
+            previous_pos.x.abs() > self.half_length && previous_pos.y.abs() < self.half_goal_width;
+        let enters_goal = ball.pos.x.abs() > self.half_length
+            && ball.pos.y.abs() < self.half_goal_width
+            && ball.z < self.goal_height;
+        if was_in_goal {
+            // goal's side walls
+            bounce(
+                &mut ball.pos.y,
+                &mut ball.vel.y,
+                self.half_goal_width - ball_radius,
+                BALL_GOAL_RESTITUTION,
+            );
+        }
+        if was_in_goal || enters_goal {
+            // goal's back wall
+            bounce(
+                &mut ball.pos.x,
+                &mut ball.vel.x,
+                self.half_length + self.goal_depth - ball_radius,
+                BALL_GOAL_RESTITUTION,
+            );
+        }
+        bounce(
+            &mut ball.pos.x,
+            &mut ball.vel.x,
+            self.half_length + self.boundary_width - ball_radius,
+            BALL_WALL_RESTITUTION,
+        );
+        bounce(
+            &mut ball.pos.y,
+            &mut ball.vel.y,
+            self.half_width + self.boundary_width - ball_radius,
+            BALL_WALL_RESTITUTION,
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    io::Cursor,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    time::Duration,
+};
+
+use tokio::{net::UdpSocket, select};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{AddressConfig, SimulatorConfig},
+    league_protocols::simulation_packet::{RobotControl, SimulatorCommand},
+    net::{ReceiveError, SendError, BUFFER_SIZE},
+    world::TeamColor,
+};
+
+use super::Simulator;
+
+const DEFAULT_IP: Ipv4Addr = Ipv4Addr::LOCALHOST;
+const DEFAULT_CONTROL_PORT: u16 = 10300;
+const DEFAULT_BLUE_PORT: u16 = 10301;
+const DEFAULT_YELLOW_PORT: u16 = 10302;
+const DEFAULT_VISION_IP: Ipv4Addr = Ipv4Addr::new(224, 5, 23, 2);
+const DEFAULT_VISION_PORT: u16 = 10020;
+
+/// period of the vision packets, like a 60Hz camera
+const VISION_PERIOD: Duration = Duration::from_micros(16_667);
+
+/// Serves a `Simulator` on the league's simulator ports, like grSim or ER-Force's simulator do.
+/// The simulation runs in real time (times its simulation speed) and the detection is sent to the vision address.
+pub struct SimulatorServer {
+    simulator: Simulator,
+    control_socket: UdpSocket,
+    blue_socket: UdpSocket,
+    yellow_socket: UdpSocket,
+    vision_socket: UdpSocket,
+    vision_addr: SocketAddrV4,
+}
+
+async fn bind(ip: Ipv4Addr, port: u16) -> UdpSocket {
+    UdpSocket::bind((ip, port))
+        .await
+        .expect("Failed to bind simulator port")
+}
+
+async fn receive<T: prost::Message + Default>(
+    socket: &UdpSocket,
+    buffer: &mut [u8],
+) -> Result<(T, SocketAddr), ReceiveError> {
+    let (received_bytes_count, addr) = socket
+        .recv_from(buffer)
+        .await
+        .map_err(ReceiveError::SocketReceiveError)?;
+    let packet = T::decode(Cursor::new(&buffer[0..received_bytes_count]))
+        .map_err(ReceiveError::DecodeError)?;
+    Ok((packet, addr))
+}
+
+async fn send<T: prost::Message>(
+    socket: &UdpSocket,
+    packet: T,
+    addr: SocketAddr,
+) -> Result<usize, SendError> {
+    socket
+        .send_to(&packet.encode_to_vec(), addr)
+        .await
+        .map_err(SendError::SocketSendError)
+}
+
+impl SimulatorServer {
+    /// Listens on the simulator's ip & ports of `config` and sends the detection to `vision`.
+    /// Missing values are the league's defaults.
+    pub async fn new(
+        simulator: Simulator,
+        config: &SimulatorConfig,
+        vision: &AddressConfig,
+    ) -> Self {
+        let ip = config.ip.unwrap_or(DEFAULT_IP);
+        Self {
+            simulator,
+            control_socket: bind(ip, config.control_port.unwrap_or(DEFAULT_CONTROL_PORT)).await,
+            blue_socket: bind(ip, config.blue_port.unwrap_or(DEFAULT_BLUE_PORT)).await,
+            yellow_socket: bind(ip, config.yellow_port.unwrap_or(DEFAULT_YELLOW_PORT)).await,
+            vision_socket: bind(Ipv4Addr::UNSPECIFIED, 0).await,
+            vision_addr: SocketAddrV4::new(
+                vision.ip.unwrap_or(DEFAULT_VISION_IP),
+                vision.port.unwrap_or(DEFAULT_VISION_PORT),
+            ),
+        }
+    }
+
+    pub async fn run_forever(mut self) {
+        info!(vision_addr = %self.vision_addr, "simulator started");
+        let mut control_buffer = vec![0; BUFFER_SIZE];
+        let mut blue_buffer = vec![0; BUFFER_SIZE];
+        let mut yellow_buffer = vec![0; BUFFER_SIZE];
+        let mut interval = tokio::time::interval(VISION_PERIOD);
+        loop {
+            select! {
+                _ = interval.tick() => {
+                    self.simulator.simulate(VISION_PERIOD.as_secs_f64() * self.simulator.get_simulation_speed());
+                    let packet = self.simulator.next_vision_packet();
+                    if let Err(e) = send(&self.vision_socket, packet, self.vision_addr.into()).await {
+                        debug!(?e, "couldn't send detection");
+                    }
+                }
+                received = receive::<SimulatorCommand>(&self.control_socket, &mut control_buffer) => {
+                    match received {
+                        Ok((command, addr)) => {
+                            let response = self.simulator.handle_simulator_command(command);
+                            if let Err(e) = send(&self.control_socket, response, addr).await {
+                                warn!(?e, "couldn't answer simulator command");
+                            }
+                        }
+                        Err(e) => warn!(?e, "couldn't receive simulator command"),
+                    }
+                }
+                received = receive::<RobotControl>(&self.blue_socket, &mut blue_buffer) => {
+                    self.handle_robot_control(TeamColor::Blue, received).await;
+                }
+                received = receive::<RobotControl>(&self.yellow_socket, &mut yellow_buffer) => {
+                    self.handle_robot_control(TeamColor::Yellow, received).await;
+                }
+            }
+        }
+    }
+
+    async fn handle_robot_control(
+        &mut self,
+        color: TeamColor,
+        received: Result<(RobotControl, SocketAddr), ReceiveError>,
+    ) {
+        let socket = match color {
+            TeamColor::Blue => &self.blue_socket,
+            TeamColor::Yellow => &self.yellow_socket,
+        };
+        match received {
+            Ok((control, addr)) => {
+                let response = self.simulator.handle_robot_control(color, control);
+                if let Err(e) = send(socket, response, addr).await {
+                    warn!(?e, ?color, "couldn't answer robot control");
+                }
+            }
+            Err(e) => warn!(?e, ?color, "couldn't receive robot control"),
+        }
+    }
+}
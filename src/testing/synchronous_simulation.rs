@@ -0,0 +1,288 @@
+//! Synchronous simulation, Coral advances the simulator step by step instead of running on the wall clock.
+//!
+//! Each step sends the robots' commands with a `SimulationSyncRequest`, the simulator performs a
+//! `CONTROL_PERIOD` long step and answers with the detection of the cameras, which updates the world.
+//! The tokio clock is then advanced by `CONTROL_PERIOD`: when the runtime's clock is paused
+//! (e.g. `#[tokio::test(start_paused = true)]`), strategies run on the simulated time and
+//! the tests are reproducible & fast-forwarded.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use crabe_async::{
+//!     math::{Point2, ReactivePoint2Ext},
+//!     testing::synchronous_simulation::{SynchronousSimulation, UdpSynchronousSimulator},
+//!     world::{AvoidanceMode, TeamColor, World},
+//! };
+//! use std::time::Duration;
+//!
+//! #[tokio::main(flavor = "current_thread", start_paused = true)]
+//! async fn main() {
+//!     let world = World::default_with_team_color(TeamColor::Blue);
+//!     let simulator = UdpSynchronousSimulator::new(None, None).await;
+//!     let mut simulation = SynchronousSimulation::new(world.clone(), simulator);
+//!     simulation.step().await.unwrap(); // first detection of the robots
+//!
+//!     let robot = world.team.lock().unwrap()[&0].clone();
+//!     let w = world.clone();
+//!     tokio::spawn(async move {
+//!         let _ = robot
+//!             .goto(&w, &Point2::new(1., 0.), None, AvoidanceMode::None)
+//!             .await;
+//!     });
+//!     let arrived = simulation
+//!         .run_until(
+//!             |world| world.team.lock().unwrap()[&0].get_pos().distance_to(&Point2::new(1., 0.)) < 0.05,
+//!             Duration::from_secs(5),
+//!         )
+//!         .await
+//!         .unwrap();
+//!     assert!(arrived);
+//! }
+//! ```
+
+use std::{collections::VecDeque, future::Future, net::Ipv4Addr, time::Duration};
+
+use tracing::{trace, warn};
+
+use crate::{
+    apply_command_watchdog,
+    controllers::sim_controller::{feedback_per_robot, make_robot_control},
+    league_protocols::{
+        convert,
+        simulation_packet::{
+            SimulationSyncRequest, SimulationSyncResponse, SimulatorCommand, SslDetectionFrame,
+        },
+    },
+    motion::MotionControl,
+    net::{udp_transceiver::UdpTransceiver, ReceiveError, SendError},
+    world::{AllyRobot, FleetSpecs, TeamColor, World},
+    DetectionPipeline, IgnoreMutexErr, CONTROL_PERIOD,
+};
+
+const DEFAULT_IP: Ipv4Addr = Ipv4Addr::LOCALHOST;
+/// synchronous simulators are controlled on the simulator control port
+const DEFAULT_PORT: u16 = 10300;
+
+/// A simulator which only advances when asked to.
+pub trait SynchronousSimulator<E> {
+    /// Performs the simulation step of the request, returns the state after it.
+    fn step(
+        &mut self,
+        request: SimulationSyncRequest,
+    ) -> impl Future<Output = Result<SimulationSyncResponse, E>> + Send;
+
+    /// specs of the simulated robots of this team, `None` if the simulator doesn't tell
+    fn get_fleet_specs(&self, _color: TeamColor) -> Option<FleetSpecs> {
+        None
+    }
+}
+
+/// A synchronous simulator reached over UDP.
+pub struct UdpSynchronousSimulator {
+    socket: UdpTransceiver,
+}
+
+#[derive(Debug)]
+pub enum UdpSynchronousSimulatorError {
+    SendRequestError(SendError),
+    ReceiveResponseError(ReceiveError),
+}
+
+impl UdpSynchronousSimulator {
+    pub async fn new(custom_ip: Option<Ipv4Addr>, custom_port: Option<u16>) -> Self {
+        Self {
+            socket: UdpTransceiver::new(
+                custom_ip.unwrap_or(DEFAULT_IP),
+                custom_port.unwrap_or(DEFAULT_PORT),
+            )
+            .await
+            .expect("Failed to setup synchronous simulator."),
+        }
+    }
+}
+
+impl SynchronousSimulator<UdpSynchronousSimulatorError> for UdpSynchronousSimulator {
+    async fn step(
+        &mut self,
+        request: SimulationSyncRequest,
+    ) -> Result<SimulationSyncResponse, UdpSynchronousSimulatorError> {
+        self.socket
+            .send(request)
+            .await
+            .map_err(UdpSynchronousSimulatorError::SendRequestError)?;
+        self.socket
+            .receive::<SimulationSyncResponse>()
+            .await
+            .map_err(UdpSynchronousSimulatorError::ReceiveResponseError)
+    }
+}
+
+/// Delivers the detections of a simulator `delay` steps late, like a vision with a latency
+/// of `delay` times `CONTROL_PERIOD`.
+pub struct DelayedDetections<S> {
+    simulator: S,
+    delay: usize,
+    detections: VecDeque<Vec<SslDetectionFrame>>,
+}
+
+impl<S> DelayedDetections<S> {
+    pub fn new(simulator: S, delay: usize) -> Self {
+        Self {
+            simulator,
+            delay,
+            detections: VecDeque::new(),
+        }
+    }
+}
+
+impl<E, S: SynchronousSimulator<E> + Send> SynchronousSimulator<E> for DelayedDetections<S> {
+    async fn step(&mut self, request: SimulationSyncRequest) -> Result<SimulationSyncResponse, E> {
+        let mut response = self.simulator.step(request).await?;
+        self.detections
+            .push_back(std::mem::take(&mut response.detection));
+        // nothing was seen during the first steps
+        if self.detections.len() > self.delay {
+            response.detection = self.detections.pop_front().unwrap_or_default();
+        }
+        Ok(response)
+    }
+
+    fn get_fleet_specs(&self, color: TeamColor) -> Option<FleetSpecs> {
+        self.simulator.get_fleet_specs(color)
+    }
+}
+
+/// Drives the world & the control of the robots with a `SynchronousSimulator`, see the module's documentation.
+pub struct SynchronousSimulation<S> {
+    world: World,
+    simulator: S,
+    detection_pipeline: DetectionPipeline,
+    /// simulated time in [s]
+    time: f64,
+    /// sent with the next step
+    pending_simulator_command: Option<SimulatorCommand>,
+    motion_control: MotionControl,
+}
+
+impl<S> SynchronousSimulation<S> {
+    pub fn new(world: World, simulator: S) -> Self {
+        Self {
+            detection_pipeline: DetectionPipeline::new(&world),
+            world,
+            simulator,
+            time: 0.,
+            pending_simulator_command: None,
+            motion_control: MotionControl::new(),
+        }
+    }
+
+    /// simulated time in [s] since the creation of the simulation
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    /// The command (teleportations, ball placement..) is applied at the beginning of the next step.
+    pub fn send_simulator_command(&mut self, command: SimulatorCommand) {
+        self.pending_simulator_command = Some(command);
+    }
+
+    pub fn get_simulator(&mut self) -> &mut S {
+        &mut self.simulator
+    }
+
+    /// Advances the simulation of one `CONTROL_PERIOD`:
+    /// sends the robots' commands, updates the world with the result and lets the strategies react.
+    pub async fn step<E>(&mut self) -> Result<(), E>
+    where
+        S: SynchronousSimulator<E>,
+    {
+        let robots = self
+            .world
+            .team
+            .lock()
+            .unwrap_ignore_poison()
+            .values()
+            .filter(|r| r.is_active()) // like the control loop, robots which left the field aren't controlled
+            .cloned()
+            .collect::<Vec<AllyRobot>>();
+        apply_command_watchdog(&robots);
+        self.motion_control.update(&robots);
+        let request = SimulationSyncRequest {
+            sim_step: Some(CONTROL_PERIOD.as_secs_f32()),
+            simulator_command: self.pending_simulator_command.take(),
+            robot_control: Some(make_robot_control(robots.into_iter())),
+        };
+        trace!(?request, "stepping simulation");
+        let response = self.simulator.step(request).await?;
+        self.time += CONTROL_PERIOD.as_secs_f64();
+
+        if let Some(robot_control_response) = response.robot_control_response {
+            for error in &robot_control_response.errors {
+                warn!(?error, "simulator rejected the robot commands");
+            }
+            let mut feedback_per_robot = feedback_per_robot(robot_control_response);
+            for robot in self.world.team.lock().unwrap_ignore_poison().values_mut() {
+                if let Some(feedback) = feedback_per_robot.remove(&robot.get_id()) {
+                    robot.set_feedback(feedback);
+                }
+            }
+        }
+
+        // all the frames of a response are captured at the same instant, no need to wait for other cameras
+        for frame in response.detection {
+            // the simulation package has its own copy of the vision's detection messages
+            self.detection_pipeline
+                .add_frame(&self.world, convert(&frame));
+        }
+        self.detection_pipeline.flush(&self.world);
+
+        // strategies react to the new world and the clock reaches the next step
+        tokio::time::sleep(CONTROL_PERIOD).await;
+        Ok(())
+    }
+
+    /// Steps the simulation for `duration` of simulated time.
+    pub async fn run_for<E>(&mut self, duration: Duration) -> Result<(), E>
+    where
+        S: SynchronousSimulator<E>,
+    {
+        let end = self.time + duration.as_secs_f64();
+        while self.time < end {
+            self.step().await?;
+        }
+        Ok(())
+    }
+
+    /// Steps the simulation until `condition` holds, for at most `timeout` of simulated time.
+    /// Returns whether the condition was met.
+    pub async fn run_until<E>(
+        &mut self,
+        condition: impl Fn(&World) -> bool,
+        timeout: Duration,
+    ) -> Result<bool, E>
+    where
+        S: SynchronousSimulator<E>,
+    {
+        let end = self.time + timeout.as_secs_f64();
+        while !condition(&self.world) {
+            if self.time >= end {
+                return Ok(false);
+            }
+            self.step().await?;
+        }
+        Ok(true)
+    }
+
+    /// Steps the simulation until the simulator fails.
+    pub async fn run_forever<E>(mut self) -> E
+    where
+        S: SynchronousSimulator<E>,
+    {
+        loop {
+            if let Err(e) = self.step().await {
+                return e;
+            }
+        }
+    }
+}
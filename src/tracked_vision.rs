@@ -0,0 +1,150 @@
+use crate::league_protocols::tracked_vision_packet::TrackerWrapperPacket;
+use crate::net::multicast_receiver::MulticastUdpReceiver;
+use crate::replay::PacketSource;
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::info;
+
+const DEFAULT_TRACKED_VISION_IP: Ipv4Addr = Ipv4Addr::new(224, 5, 23, 2);
+const DEFAULT_TRACKED_VISION_PORT: u16 = 10010;
+
+/// after this long without a frame from the tracker in use, another one is selected
+const TRACKER_SILENCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Receiver for the frames of a league tracker (e.g. the autorefs' trackers).
+/// Unlike `Vision`, the frames are already filtered & merged across cameras.
+pub struct TrackedVision {
+    source: PacketSource<TrackerWrapperPacket>,
+}
+
+impl TrackedVision {
+    pub fn new(
+        custom_ip: Option<Ipv4Addr>,
+        custom_port: Option<u16>,
+        custom_interface: Option<Ipv4Addr>,
+    ) -> Self {
+        let ip = match custom_ip {
+            Some(custom_ip) => custom_ip,
+            None => DEFAULT_TRACKED_VISION_IP,
+        };
+        let port = match custom_port {
+            Some(custom_port) => custom_port,
+            None => DEFAULT_TRACKED_VISION_PORT,
+        };
+
+        Self::from_source(PacketSource::Network(Box::new(
+            MulticastUdpReceiver::new(ip, port, custom_interface.unwrap_or(Ipv4Addr::UNSPECIFIED))
+                .expect("Failed to create tracked vision receiver"),
+        )))
+    }
+
+    pub(crate) fn from_source(source: PacketSource<TrackerWrapperPacket>) -> Self {
+        Self { source }
+    }
+
+    pub async fn receive(&mut self) -> Result<TrackerWrapperPacket, crate::net::ReceiveError> {
+        self.source.receive().await
+    }
+}
+
+/// Selects the tracker whose frames are used when multiple trackers are sending on the network:
+/// the first one named `source_name` (or the first one to send a frame if `None`).
+/// Another tracker is selected when the one in use stays silent for `TRACKER_SILENCE_TIMEOUT`,
+/// or when a tracker named `source_name` restarts (with a uuid never seen before).
+#[derive(Debug, Clone, Default)]
+pub struct TrackerSelection {
+    source_name: Option<String>,
+    /// uuid of the tracker in use & when its last frame was received
+    selected: Option<(String, Instant)>,
+    /// uuids of the trackers named `source_name` (if any) which sent frames
+    seen: HashSet<String>,
+}
+
+impl TrackerSelection {
+    pub fn new(source_name: Option<String>) -> Self {
+        Self {
+            source_name,
+            selected: None,
+            seen: HashSet::new(),
+        }
+    }
+
+    /// whether the frames of `packet`, received at `now`, should be used
+    pub fn accepts(&mut self, packet: &TrackerWrapperPacket, now: Instant) -> bool {
+        if let Some((uuid, last_frame)) = &mut self.selected {
+            if *uuid == packet.uuid {
+                *last_frame = now;
+                return true;
+            }
+        }
+        if self.source_name.is_some() && packet.source_name != self.source_name {
+            return false;
+        }
+        let is_new = self.seen.insert(packet.uuid.clone());
+        let can_switch = match &self.selected {
+            None => true,
+            Some((_, last_frame)) => {
+                (self.source_name.is_some() && is_new)
+                    || now - *last_frame >= TRACKER_SILENCE_TIMEOUT
+            }
+        };
+        if !can_switch {
+            return false;
+        }
+        info!(uuid = packet.uuid, source_name = ?packet.source_name, "using tracker");
+        self.selected = Some((packet.uuid.clone(), now));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(uuid: &str, source_name: &str) -> TrackerWrapperPacket {
+        TrackerWrapperPacket {
+            uuid: uuid.to_string(),
+            source_name: Some(source_name.to_string()),
+            tracked_frame: None,
+        }
+    }
+
+    #[test]
+    fn keeps_the_first_tracker() {
+        let start = Instant::now();
+        let mut selection = TrackerSelection::new(None);
+        assert!(selection.accepts(&packet("a", "TIGERs"), start));
+        assert!(!selection.accepts(&packet("b", "ER-Force"), start));
+        assert!(selection.accepts(&packet("a", "TIGERs"), start + TRACKER_SILENCE_TIMEOUT / 2));
+        assert!(!selection.accepts(&packet("b", "ER-Force"), start + TRACKER_SILENCE_TIMEOUT));
+    }
+
+    #[test]
+    fn switches_when_the_tracker_is_silent() {
+        let start = Instant::now();
+        let mut selection = TrackerSelection::new(None);
+        assert!(selection.accepts(&packet("a", "TIGERs"), start));
+        assert!(selection.accepts(&packet("b", "ER-Force"), start + TRACKER_SILENCE_TIMEOUT));
+        assert!(!selection.accepts(&packet("a", "TIGERs"), start + TRACKER_SILENCE_TIMEOUT));
+    }
+
+    #[test]
+    fn follows_a_restarted_named_tracker() {
+        let start = Instant::now();
+        let mut selection = TrackerSelection::new(Some("TIGERs".to_string()));
+        assert!(!selection.accepts(&packet("a", "ER-Force"), start));
+        assert!(selection.accepts(&packet("b", "TIGERs"), start));
+        // restarted with a new uuid
+        assert!(selection.accepts(&packet("c", "TIGERs"), start));
+        // no going back & forth if both keep sending
+        assert!(!selection.accepts(&packet("b", "TIGERs"), start));
+        assert!(selection.accepts(&packet("c", "TIGERs"), start));
+        // other trackers are never used
+        assert!(!selection.accepts(
+            &packet("a", "ER-Force"),
+            start + TRACKER_SILENCE_TIMEOUT * 2
+        ));
+    }
+}
@@ -0,0 +1,228 @@
+use tracing::{debug, trace};
+
+use crate::{
+    league_protocols::vision_packet::SslDetectionBall,
+    math::{Point2, Vec2},
+    DETECTION_SCALING_FACTOR,
+};
+
+use super::Kalman1d;
+
+/// standard deviation of the ball's acceleration in [m/s^2], high because of kicks & bounces
+const BALL_PROCESS_NOISE: f64 = 10.;
+
+/// standard deviation of the vision's ball position in [m]
+const BALL_MEASUREMENT_NOISE: f64 = 0.005;
+
+/// detections under this confidence are ignored
+const BALL_MIN_CONFIDENCE: f32 = 0.1;
+
+/// max ball speed allowed by the rules in [m/s]
+const BALL_MAX_SPEED: f64 = 6.5;
+
+/// detections further than this from the prediction (+ what the ball could travel at max speed) are outliers, in [m]
+const BALL_GATE_MARGIN: f64 = 0.2;
+
+/// after this much time in [s] without an accepted detection, we re-acquire the ball from scratch
+const BALL_REACQUIRE_TIMEOUT: f64 = 0.5;
+
+/// Tracks the ball from the vision detections with a constant velocity Kalman filter.
+///
+/// When multiple balls are detected, the one closest to the prediction is used.
+/// Detections too far from the prediction are rejected as outliers, unless the ball
+/// hasn't been seen for `BALL_REACQUIRE_TIMEOUT`, in which case the most confident detection is picked.
+#[derive(Debug, Clone, Default)]
+pub struct BallTracker {
+    filter: Option<(Kalman1d, Kalman1d)>,
+    /// `t_capture` of the last prediction
+    last_prediction_t: f64,
+    /// `t_capture` of the last accepted detection
+    last_accepted_t: f64,
+}
+
+fn detection_pos(detection: &SslDetectionBall) -> Point2 {
+    Point2::new(
+        detection.x as f64 / DETECTION_SCALING_FACTOR,
+        detection.y as f64 / DETECTION_SCALING_FACTOR,
+    )
+}
+
+impl BallTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_tracking(&self) -> bool {
+        self.filter.is_some()
+    }
+
+    /// `t_capture` of the last detection that was used to update the filter
+    pub fn get_last_accepted_t(&self) -> Option<f64> {
+        self.filter.map(|_| self.last_accepted_t)
+    }
+
+    pub fn get_pos(&self) -> Option<Point2> {
+        self.filter
+            .map(|(x, y)| Point2::new(x.get_pos(), y.get_pos()))
+    }
+
+    pub fn get_vel(&self) -> Option<Vec2> {
+        self.filter
+            .map(|(x, y)| Vec2::new(x.get_vel(), y.get_vel()))
+    }
+
+    /// Updates the tracker with the balls of a detection frame captured at `t_capture`.
+    /// Returns the filtered (position, velocity) if the ball is being tracked.
+    pub fn update(
+        &mut self,
+        detections: &[SslDetectionBall],
+        t_capture: f64,
+    ) -> Option<(Point2, Vec2)> {
+        let candidates = detections
+            .iter()
+            .filter(|d| d.confidence >= BALL_MIN_CONFIDENCE);
+
+        match &mut self.filter {
+            Some((x, y)) if t_capture - self.last_accepted_t < BALL_REACQUIRE_TIMEOUT => {
+                let dt = t_capture - self.last_prediction_t;
+                if dt < 0. {
+                    trace!(dt, "ignoring out of order ball detection");
+                    return self.get_pos().zip(self.get_vel());
+                }
+                x.predict(dt);
+                y.predict(dt);
+                self.last_prediction_t = t_capture;
+
+                let predicted_pos = Point2::new(x.get_pos(), y.get_pos());
+                let gate = BALL_GATE_MARGIN + BALL_MAX_SPEED * (t_capture - self.last_accepted_t);
+                let closest = candidates
+                    .map(detection_pos)
+                    .map(|p| (p, (p - predicted_pos).norm()))
+                    .filter(|(_, d)| *d < gate)
+                    .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2));
+
+                if let Some((p, _)) = closest {
+                    x.update(p.x);
+                    y.update(p.y);
+                    self.last_accepted_t = t_capture;
+                } else {
+                    trace!(?predicted_pos, "no ball detection close to the prediction");
+                }
+            }
+            _ => {
+                // (re)acquire the ball using the most confident detection
+                let best = candidates.max_by(|d1, d2| d1.confidence.total_cmp(&d2.confidence))?;
+                let p = detection_pos(best);
+                debug!(pos = ?p, "acquired ball");
+                self.filter = Some((
+                    Kalman1d::new(p.x, BALL_PROCESS_NOISE, BALL_MEASUREMENT_NOISE),
+                    Kalman1d::new(p.y, BALL_PROCESS_NOISE, BALL_MEASUREMENT_NOISE),
+                ));
+                self.last_prediction_t = t_capture;
+                self.last_accepted_t = t_capture;
+            }
+        }
+
+        self.get_pos().zip(self.get_vel())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_PERIOD: f64 = 1. / 60.;
+
+    /// a detection at `pos` in [m]
+    fn ball_at(pos: Point2, confidence: f32) -> SslDetectionBall {
+        SslDetectionBall {
+            confidence,
+            area: None,
+            x: (pos.x * DETECTION_SCALING_FACTOR) as f32,
+            y: (pos.y * DETECTION_SCALING_FACTOR) as f32,
+            z: None,
+            pixel_x: 0.,
+            pixel_y: 0.,
+        }
+    }
+
+    /// tracks a ball rolling from `start` at `vel` for `n_frames`, returns the next frame's time
+    fn track_rolling_ball(
+        tracker: &mut BallTracker,
+        start: Point2,
+        vel: Vec2,
+        n_frames: usize,
+    ) -> f64 {
+        for i in 0..n_frames {
+            let t = i as f64 * FRAME_PERIOD;
+            tracker.update(&[ball_at(start + vel * t, 0.9)], t);
+        }
+        n_frames as f64 * FRAME_PERIOD
+    }
+
+    #[test]
+    fn converges_to_a_rolling_ball() {
+        let mut tracker = BallTracker::new();
+        let (start, vel) = (Point2::new(-1., 0.5), Vec2::new(2., -1.));
+        let t = track_rolling_ball(&mut tracker, start, vel, 120);
+        let last_t = t - FRAME_PERIOD;
+        let (pos, filtered_vel) = tracker.get_pos().zip(tracker.get_vel()).expect("tracked");
+        assert!((pos - (start + vel * last_t)).norm() < 0.01, "{pos:?}");
+        assert!((filtered_vel - vel).norm() < 0.1, "{filtered_vel:?}");
+    }
+
+    #[test]
+    fn rejects_a_single_outlier() {
+        let mut tracker = BallTracker::new();
+        let (start, vel) = (Point2::zero(), Vec2::new(1., 0.));
+        let t = track_rolling_ball(&mut tracker, start, vel, 60);
+        let before = tracker.get_pos().expect("tracked");
+        tracker.update(&[ball_at(Point2::new(-2., 2.), 0.9)], t);
+        let after = tracker.get_pos().expect("still tracked");
+        // only moved by the prediction
+        assert!((after - before).norm() < 0.05, "{after:?}");
+        assert_eq!(tracker.get_last_accepted_t(), Some(t - FRAME_PERIOD));
+
+        // the next detections are accepted again
+        let next_t = t + FRAME_PERIOD;
+        tracker.update(&[ball_at(start + vel * next_t, 0.9)], next_t);
+        assert_eq!(tracker.get_last_accepted_t(), Some(next_t));
+    }
+
+    #[test]
+    fn picks_the_detection_closest_to_the_prediction() {
+        let mut tracker = BallTracker::new();
+        let (start, vel) = (Point2::zero(), Vec2::new(1., 0.));
+        let t = track_rolling_ball(&mut tracker, start, vel, 60);
+        let expected = start + vel * t;
+        // a more confident ball further away, & a ball under the min confidence right on the prediction
+        tracker.update(
+            &[
+                ball_at(expected + Vec2::new(0., 0.1), 0.9),
+                ball_at(expected + Vec2::new(0., 0.02), 0.3),
+                ball_at(expected, 0.05),
+            ],
+            t,
+        );
+        let pos = tracker.get_pos().expect("tracked");
+        assert!(pos.y > 0. && pos.y < 0.03, "{pos:?}");
+    }
+
+    #[test]
+    fn reacquires_the_ball_after_losing_it() {
+        let mut tracker = BallTracker::new();
+        assert!(tracker.update(&[], 0.).is_none());
+        let t = track_rolling_ball(&mut tracker, Point2::zero(), Vec2::zero(), 30);
+
+        // moved while hidden: too far to be accepted before the timeout
+        let elsewhere = Point2::new(3., -2.);
+        tracker.update(&[ball_at(elsewhere, 0.9)], t);
+        assert!((tracker.get_pos().expect("tracked") - Point2::zero()).norm() < 0.01);
+
+        let t = t + BALL_REACQUIRE_TIMEOUT;
+        tracker.update(&[ball_at(elsewhere, 0.9)], t);
+        assert_eq!(tracker.get_pos(), Some(elsewhere));
+        assert_eq!(tracker.get_vel(), Some(Vec2::zero()));
+        assert_eq!(tracker.get_last_accepted_t(), Some(t));
+    }
+}
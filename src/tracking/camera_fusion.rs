@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use tracing::trace;
+
+use crate::{
+    league_protocols::vision_packet::{SslDetectionBall, SslDetectionFrame, SslDetectionRobot},
+    math::Vec2,
+    world::TeamColor,
+    DETECTION_SCALING_FACTOR,
+};
+
+/// frames captured less than this apart in [s] are considered to be of the same instant (half a 60Hz period)
+const FUSION_WINDOW: f64 = 0.008;
+
+/// a camera that didn't send a frame for this long in [s] isn't waited for anymore
+const CAMERA_TIMEOUT: f64 = 1.;
+
+/// ball detections closer than this in [m] are considered to be the same ball seen by multiple cameras
+const BALL_MERGE_DISTANCE: f64 = 0.1;
+
+/// The detections of all the cameras at a given instant, without duplicates.
+#[derive(Debug, Clone, Default)]
+pub struct FusedFrame {
+    /// capture time of the fused frames, weighted by their number of detections
+    pub t_capture: f64,
+    pub camera_ids: Vec<u32>,
+    pub balls: Vec<SslDetectionBall>,
+    pub robots_yellow: Vec<SslDetectionRobot>,
+    pub robots_blue: Vec<SslDetectionRobot>,
+}
+
+/// Merges the detection frames sent by each camera into `FusedFrame`s.
+///
+/// Frames are buffered until every active camera sent a frame, a camera sends a second frame,
+/// or the frames are too far apart in time. The buffered frames are then fused:
+/// - robots seen by multiple cameras are moved to the fused capture time (see `set_robot_vel`),
+///   then averaged, weighted by the detections' confidence
+/// - balls closer than `BALL_MERGE_DISTANCE` are merged the same way
+/// - frames captured before the last fused frame are dropped
+#[derive(Debug, Clone, Default)]
+pub struct CameraFusion {
+    pending: Vec<SslDetectionFrame>,
+    /// t_capture of the last frame sent by each camera
+    last_seen: HashMap<u32, f64>,
+    last_fused_t: Option<f64>,
+    /// tracked velocities of the robots in [m/s]
+    robot_vels: HashMap<(TeamColor, u32), Vec2>,
+}
+
+/// weight of a detection in the averages, no detection is ignored even with a 0 confidence
+fn weight(confidence: f32) -> f64 {
+    (confidence as f64).max(f64::EPSILON)
+}
+
+fn merge_robots(detections: Vec<SslDetectionRobot>) -> Vec<SslDetectionRobot> {
+    let mut per_id: HashMap<u32, Vec<SslDetectionRobot>> = HashMap::new();
+    for detection in detections {
+        if let Some(id) = detection.robot_id {
+            per_id.entry(id).or_default().push(detection);
+        }
+    }
+
+    per_id
+        .into_values()
+        .map(|detections| {
+            let total_weight: f64 = detections.iter().map(|d| weight(d.confidence)).sum();
+            let mean = |f: &dyn Fn(&SslDetectionRobot) -> f64| {
+                detections
+                    .iter()
+                    .map(|d| weight(d.confidence) * f(d))
+                    .sum::<f64>()
+                    / total_weight
+            };
+            // circular mean, so that -PI and PI average to PI and not 0
+            let orientation = mean(&|d| (d.orientation() as f64).sin())
+                .atan2(mean(&|d| (d.orientation() as f64).cos()));
+            let most_confident = detections
+                .iter()
+                .copied()
+                .max_by(|d1, d2| d1.confidence.total_cmp(&d2.confidence))
+                .unwrap_or_default();
+            SslDetectionRobot {
+                x: mean(&|d| d.x as f64) as f32,
+                y: mean(&|d| d.y as f64) as f32,
+                orientation: Some(orientation as f32),
+                ..most_confident
+            }
+        })
+        .collect()
+}
+
+fn merge_balls(mut detections: Vec<SslDetectionBall>) -> Vec<SslDetectionBall> {
+    let merge_distance = BALL_MERGE_DISTANCE * DETECTION_SCALING_FACTOR;
+    // most confident detections first, they'll be the center of the clusters
+    detections.sort_by(|d1, d2| d2.confidence.total_cmp(&d1.confidence));
+
+    let mut clusters: Vec<Vec<SslDetectionBall>> = Vec::new();
+    for detection in detections {
+        let cluster = clusters.iter_mut().find(|c| {
+            let center = c[0];
+            ((center.x - detection.x) as f64).hypot((center.y - detection.y) as f64)
+                < merge_distance
+        });
+        match cluster {
+            Some(cluster) => cluster.push(detection),
+            None => clusters.push(vec![detection]),
+        }
+    }
+
+    clusters
+        .into_iter()
+        .map(|cluster| {
+            let total_weight: f64 = cluster.iter().map(|d| weight(d.confidence)).sum();
+            let mean = |f: &dyn Fn(&SslDetectionBall) -> f64| {
+                cluster
+                    .iter()
+                    .map(|d| weight(d.confidence) * f(d))
+                    .sum::<f64>()
+                    / total_weight
+            };
+            SslDetectionBall {
+                x: mean(&|d| d.x as f64) as f32,
+                y: mean(&|d| d.y as f64) as f32,
+                ..cluster[0]
+            }
+        })
+        .collect()
+}
+
+impl CameraFusion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the detections where the robots are expected to be `dt` [s] later
+    fn moved_by(
+        &self,
+        color: TeamColor,
+        mut detections: Vec<SslDetectionRobot>,
+        dt: f64,
+    ) -> Vec<SslDetectionRobot> {
+        for detection in &mut detections {
+            let vel = detection
+                .robot_id
+                .and_then(|id| self.robot_vels.get(&(color, id)))
+                .copied()
+                .unwrap_or_default();
+            detection.x += (vel.x * dt * DETECTION_SCALING_FACTOR) as f32;
+            detection.y += (vel.y * dt * DETECTION_SCALING_FACTOR) as f32;
+        }
+        detections
+    }
+
+    /// Sets the tracked velocity of a robot, used to move its detections to a common capture time.
+    pub fn set_robot_vel(&mut self, color: TeamColor, id: u32, vel: Vec2) {
+        self.robot_vels.insert((color, id), vel);
+    }
+
+    /// ids of the cameras that sent a frame recently
+    pub fn get_active_cameras(&self) -> impl Iterator<Item = u32> + '_ {
+        self.last_seen.keys().copied()
+    }
+
+    /// Adds the frame of a camera, returns the frames which are ready to be used (in chronological order).
+    pub fn add_frame(&mut self, frame: SslDetectionFrame) -> Vec<FusedFrame> {
+        if self.last_fused_t.is_some_and(|t| frame.t_capture <= t) {
+            trace!(
+                camera_id = frame.camera_id,
+                "dropping frame older than the last fused frame"
+            );
+            return vec![];
+        }
+
+        self.last_seen.insert(frame.camera_id, frame.t_capture);
+        self.last_seen
+            .retain(|_, last_t| frame.t_capture - *last_t < CAMERA_TIMEOUT);
+
+        let mut fused_frames = Vec::new();
+        let same_camera_twice = self.pending.iter().any(|f| f.camera_id == frame.camera_id);
+        let too_far_apart = self
+            .pending
+            .iter()
+            .any(|f| (frame.t_capture - f.t_capture).abs() > FUSION_WINDOW);
+        if same_camera_twice || too_far_apart {
+            fused_frames.extend(self.flush());
+        }
+
+        self.pending.push(frame);
+        let all_cameras_received = self
+            .last_seen
+            .keys()
+            .all(|id| self.pending.iter().any(|f| f.camera_id == *id));
+        if all_cameras_received {
+            fused_frames.extend(self.flush());
+        }
+        fused_frames
+    }
+
+    /// Fuses the buffered frames, if any.
+    pub fn flush(&mut self) -> Option<FusedFrame> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let frames = std::mem::take(&mut self.pending);
+
+        // frames without detections still tell us when they were captured
+        let detections_count = |f: &SslDetectionFrame| {
+            (f.balls.len() + f.robots_yellow.len() + f.robots_blue.len()).max(1) as f64
+        };
+        let total_weight: f64 = frames.iter().map(detections_count).sum();
+        let t_capture = frames
+            .iter()
+            .map(|f| detections_count(f) * f.t_capture)
+            .sum::<f64>()
+            / total_weight;
+        self.last_fused_t = Some(t_capture);
+
+        let mut fused_frame = FusedFrame {
+            t_capture,
+            ..Default::default()
+        };
+        let (mut balls, mut robots_yellow, mut robots_blue) = (Vec::new(), Vec::new(), Vec::new());
+        for frame in frames {
+            fused_frame.camera_ids.push(frame.camera_id);
+            let dt = t_capture - frame.t_capture;
+            balls.extend(frame.balls);
+            robots_yellow.extend(self.moved_by(TeamColor::Yellow, frame.robots_yellow, dt));
+            robots_blue.extend(self.moved_by(TeamColor::Blue, frame.robots_blue, dt));
+        }
+        fused_frame.balls = merge_balls(balls);
+        fused_frame.robots_yellow = merge_robots(robots_yellow);
+        fused_frame.robots_blue = merge_robots(robots_blue);
+        Some(fused_frame)
+    }
+}
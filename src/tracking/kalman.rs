@@ -0,0 +1,153 @@
+/// Constant velocity Kalman filter on a single axis.
+///
+/// The state is `[position, velocity]`, only the position is measured.
+/// The process noise models the unknown accelerations as white noise of standard deviation `process_noise`.
+#[derive(Debug, Clone, Copy)]
+pub struct Kalman1d {
+    pos: f64,
+    vel: f64,
+    covariance: [[f64; 2]; 2],
+    /// standard deviation of the acceleration in [unit/s^2]
+    process_noise: f64,
+    /// standard deviation of the position measurements in [unit]
+    measurement_noise: f64,
+}
+
+impl Kalman1d {
+    pub fn new(initial_pos: f64, process_noise: f64, measurement_noise: f64) -> Self {
+        let mut kalman = Self {
+            pos: initial_pos,
+            vel: 0.,
+            covariance: Default::default(),
+            process_noise,
+            measurement_noise,
+        };
+        kalman.reset(initial_pos);
+        kalman
+    }
+
+    /// forgets everything and restarts from `pos` with an unknown velocity
+    pub fn reset(&mut self, pos: f64) {
+        self.pos = pos;
+        self.vel = 0.;
+        self.covariance = [
+            [self.measurement_noise.powi(2), 0.],
+            [0., (self.process_noise * 10.).powi(2)], // we know nothing about the velocity yet
+        ];
+    }
+
+    /// advances the state by `dt` seconds
+    pub fn predict(&mut self, dt: f64) {
+        if dt <= 0. {
+            return;
+        }
+        self.pos += self.vel * dt;
+
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        let q = self.process_noise.powi(2);
+        // P = F * P * F^T + Q, with F = [[1, dt], [0, 1]]
+        self.covariance = [
+            [
+                p00 + dt * (p01 + p10) + dt * dt * p11 + q * dt.powi(4) / 4.,
+                p01 + dt * p11 + q * dt.powi(3) / 2.,
+            ],
+            [p10 + dt * p11 + q * dt.powi(3) / 2., p11 + q * dt * dt],
+        ];
+    }
+
+    /// corrects the state with a position measurement
+    pub fn update(&mut self, measured_pos: f64) {
+        let innovation = self.innovation(measured_pos);
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        let innovation_covariance = p00 + self.measurement_noise.powi(2);
+        let (k0, k1) = (p00 / innovation_covariance, p10 / innovation_covariance);
+
+        self.pos += k0 * innovation;
+        self.vel += k1 * innovation;
+        // P = (I - K * H) * P, with H = [1, 0]
+        self.covariance = [
+            [(1. - k0) * p00, (1. - k0) * p01],
+            [p10 - k1 * p00, p11 - k1 * p01],
+        ];
+    }
+
+    /// difference between a measurement and the current position estimate
+    pub fn innovation(&self, measured_pos: f64) -> f64 {
+        measured_pos - self.pos
+    }
+
+    pub fn get_pos(&self) -> f64 {
+        self.pos
+    }
+
+    /// overrides the position estimate without touching the covariance (e.g. to wrap angles)
+    pub fn set_pos(&mut self, pos: f64) {
+        self.pos = pos;
+    }
+
+    pub fn get_vel(&self) -> f64 {
+        self.vel
+    }
+
+    pub fn get_covariance(&self) -> [[f64; 2]; 2] {
+        self.covariance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// deterministic measurement noise of about `amplitude`
+    fn noise(i: usize, amplitude: f64) -> f64 {
+        amplitude * (i as f64 * 1.7).sin()
+    }
+
+    #[test]
+    fn converges_to_constant_velocity() {
+        let (pos0, vel) = (1., 2.);
+        let dt = 1. / 60.;
+        let mut kalman = Kalman1d::new(pos0, 10., 0.005);
+        let initial_variance = kalman.get_covariance()[1][1];
+        for i in 1..=120 {
+            kalman.predict(dt);
+            kalman.update(pos0 + vel * dt * i as f64 + noise(i, 0.005));
+        }
+        let t = 120. * dt;
+        assert!((kalman.get_pos() - (pos0 + vel * t)).abs() < 0.01);
+        assert!((kalman.get_vel() - vel).abs() < 0.1, "{}", kalman.get_vel());
+        assert!(kalman.get_covariance()[1][1] < initial_variance);
+    }
+
+    #[test]
+    fn prediction_follows_the_velocity() {
+        let dt = 1. / 60.;
+        let mut kalman = Kalman1d::new(0., 10., 0.005);
+        for i in 1..=120 {
+            kalman.predict(dt);
+            kalman.update(-dt * i as f64);
+        }
+        let pos = kalman.get_pos();
+        let variance = kalman.get_covariance()[0][0];
+        kalman.predict(0.5);
+        assert!((kalman.get_pos() - (pos - 0.5)).abs() < 0.01);
+        // less sure of where it is without measurements
+        assert!(kalman.get_covariance()[0][0] > variance);
+    }
+
+    #[test]
+    fn reset_forgets_the_velocity() {
+        let mut kalman = Kalman1d::new(0., 10., 0.005);
+        for i in 1..=60 {
+            kalman.predict(0.01);
+            kalman.update(0.01 * i as f64);
+        }
+        kalman.reset(5.);
+        assert_eq!(kalman.get_pos(), 5.);
+        assert_eq!(kalman.get_vel(), 0.);
+        assert_eq!(
+            kalman.get_covariance(),
+            Kalman1d::new(5., 10., 0.005).get_covariance()
+        );
+    }
+}
@@ -0,0 +1,14 @@
+//! Filtering of the raw vision detections.
+//!
+//! Vision is noisy and sometimes plain wrong (false detections, reflections), the trackers
+//! in this module turn the detections into smooth positions & velocities for the `World`.
+
+pub mod ball;
+pub mod camera_fusion;
+pub mod kalman;
+pub mod robot;
+
+pub use ball::*;
+pub use camera_fusion::*;
+pub use kalman::*;
+pub use robot::*;
@@ -0,0 +1,182 @@
+use tracing::trace;
+
+use crate::math::{angle_difference, wrap_angle, Point2, Vec2};
+
+use super::Kalman1d;
+
+/// standard deviation of the robot's acceleration in [m/s^2]
+const ROBOT_PROCESS_NOISE: f64 = 5.;
+
+/// standard deviation of the vision's robot position in [m]
+const ROBOT_MEASUREMENT_NOISE: f64 = 0.003;
+
+/// standard deviation of the robot's angular acceleration in [rad/s^2]
+const ROBOT_ANGULAR_PROCESS_NOISE: f64 = 20.;
+
+/// standard deviation of the vision's robot orientation in [rad]
+const ROBOT_ANGULAR_MEASUREMENT_NOISE: f64 = 0.02;
+
+/// after this much time in [s] without detection, the filter restarts from the next detection
+const ROBOT_RESET_TIMEOUT: f64 = 0.5;
+
+/// Tracks a robot's pose from the vision detections with constant velocity Kalman filters on x, y and theta.
+///
+/// Orientation measurements are unwrapped around the current estimate, so crossing ±PI
+/// doesn't produce a huge angular velocity.
+#[derive(Debug, Clone, Copy)]
+pub struct RobotTracker {
+    x: Kalman1d,
+    y: Kalman1d,
+    theta: Kalman1d,
+    /// `t_capture` of the last detection used by the filter
+    last_t: Option<f64>,
+}
+
+impl Default for RobotTracker {
+    fn default() -> Self {
+        Self {
+            x: Kalman1d::new(0., ROBOT_PROCESS_NOISE, ROBOT_MEASUREMENT_NOISE),
+            y: Kalman1d::new(0., ROBOT_PROCESS_NOISE, ROBOT_MEASUREMENT_NOISE),
+            theta: Kalman1d::new(
+                0.,
+                ROBOT_ANGULAR_PROCESS_NOISE,
+                ROBOT_ANGULAR_MEASUREMENT_NOISE,
+            ),
+            last_t: None,
+        }
+    }
+}
+
+impl RobotTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the filters with a detection captured at `t_capture`.
+    /// Detections older than the last one are ignored.
+    pub fn update(&mut self, pos: Point2, orientation: f64, t_capture: f64) {
+        match self.last_t {
+            Some(last_t) if t_capture - last_t < ROBOT_RESET_TIMEOUT => {
+                let dt = t_capture - last_t;
+                if dt < 0. {
+                    trace!(dt, "ignoring out of order robot detection");
+                    return;
+                }
+                self.x.predict(dt);
+                self.y.predict(dt);
+                self.theta.predict(dt);
+
+                self.x.update(pos.x);
+                self.y.update(pos.y);
+                // unwrap the measurement around the estimate, then wrap the estimate back
+                let theta = self.theta.get_pos();
+                self.theta
+                    .update(theta + angle_difference(wrap_angle(orientation), theta));
+                self.theta.set_pos(wrap_angle(self.theta.get_pos()));
+            }
+            _ => {
+                self.x.reset(pos.x);
+                self.y.reset(pos.y);
+                self.theta.reset(wrap_angle(orientation));
+            }
+        }
+        self.last_t = Some(t_capture);
+    }
+
+    pub fn get_last_update(&self) -> Option<f64> {
+        self.last_t
+    }
+
+    pub fn get_pos(&self) -> Point2 {
+        Point2::new(self.x.get_pos(), self.y.get_pos())
+    }
+
+    pub fn get_vel(&self) -> Vec2 {
+        Vec2::new(self.x.get_vel(), self.y.get_vel())
+    }
+
+    pub fn get_orientation(&self) -> f64 {
+        self.theta.get_pos()
+    }
+
+    pub fn get_angular_vel(&self) -> f64 {
+        self.theta.get_vel()
+    }
+
+    /// the underlying (x, y, theta) filters, for debugging
+    pub fn get_filters(&self) -> (Kalman1d, Kalman1d, Kalman1d) {
+        (self.x, self.y, self.theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    const FRAME_PERIOD: f64 = 1. / 60.;
+
+    #[test]
+    fn turning_across_pi_keeps_a_bounded_angular_vel() {
+        let angular_vel = 2.;
+        let mut tracker = RobotTracker::new();
+        for i in 0..120 {
+            let t = i as f64 * FRAME_PERIOD;
+            // crosses +PI after ~0.3s, & -PI backwards
+            tracker.update(Point2::zero(), wrap_angle(2.5 + angular_vel * t), t);
+            if t > 0.2 {
+                assert!(
+                    (tracker.get_angular_vel() - angular_vel).abs() < 0.5,
+                    "{} [rad/s] at {t}s",
+                    tracker.get_angular_vel()
+                );
+            }
+            assert!(tracker.get_orientation().abs() <= PI);
+        }
+        let t = 119. * FRAME_PERIOD;
+        let expected = wrap_angle(2.5 + angular_vel * t);
+        assert!(angle_difference(tracker.get_orientation(), expected).abs() < 0.02);
+
+        let mut tracker = RobotTracker::new();
+        for i in 0..120 {
+            let t = i as f64 * FRAME_PERIOD;
+            tracker.update(Point2::zero(), wrap_angle(-2.5 - angular_vel * t), t);
+            if t > 0.2 {
+                assert!((tracker.get_angular_vel() + angular_vel).abs() < 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn still_robot_jittering_across_pi() {
+        let mut tracker = RobotTracker::new();
+        for i in 0..120 {
+            let t = i as f64 * FRAME_PERIOD;
+            let jitter = if i % 2 == 0 { 0.01 } else { -0.01 };
+            tracker.update(Point2::zero(), wrap_angle(PI + jitter), t);
+            // the jitter alone is ~1 [rad/s] between 2 frames, a wrong unwrapping would be ~TAU / FRAME_PERIOD
+            assert!(
+                tracker.get_angular_vel().abs() < 2.,
+                "{} [rad/s] at {t}s",
+                tracker.get_angular_vel()
+            );
+        }
+        assert!(angle_difference(tracker.get_orientation(), PI).abs() < 0.02);
+    }
+
+    #[test]
+    fn restarts_after_a_timeout() {
+        let mut tracker = RobotTracker::new();
+        for i in 0..60 {
+            let t = i as f64 * FRAME_PERIOD;
+            tracker.update(Point2::new(t, 0.), 0., t);
+        }
+        let t = 59. * FRAME_PERIOD + ROBOT_RESET_TIMEOUT;
+        tracker.update(Point2::new(-2., 1.), 1., t);
+        assert_eq!(tracker.get_pos(), Point2::new(-2., 1.));
+        assert_eq!(tracker.get_vel(), Vec2::zero());
+        assert_eq!(tracker.get_orientation(), 1.);
+        assert_eq!(tracker.get_last_update(), Some(t));
+    }
+}
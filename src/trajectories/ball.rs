@@ -0,0 +1,529 @@
+//! Ball trajectories following the league's ball models
+//! (see `SslBallModelStraightTwoPhase` & `SslBallModelChipFixedLoss` in the vision protocol).
+
+use std::cmp::Ordering;
+
+use crate::{
+    math::{Point2, Vec2},
+    world::{ChipFixedLossModel, StraightTwoPhaseModel},
+};
+
+use super::Trajectory;
+
+/// in [m/s^2]
+pub const GRAVITY: f64 = 9.81;
+
+/// a chipped ball stops bouncing (and starts rolling) when its vertical velocity in [m/s] drops under this
+pub const CHIP_MIN_VEL_Z: f64 = 0.1;
+
+/// max number of hops of a chipped ball, in case of weird damping factors
+const CHIP_MAX_HOPS: usize = 10;
+
+/// A ball moving on the ground: it slides until its speed drops to `switch_speed`, then rolls until it stops.
+#[derive(Debug, Clone, Copy)]
+pub struct StraightBallTrajectory {
+    initial_pos: Point2,
+    /// unit vector, zero if the ball isn't moving
+    direction: Vec2,
+    initial_speed: f64,
+    /// speed at which the ball switches from sliding to rolling
+    switch_speed: f64,
+    /// in [m/s^2] (positive)
+    slide_decel: f64,
+    /// in [m/s^2] (positive)
+    roll_decel: f64,
+}
+
+impl StraightBallTrajectory {
+    /// `switch_speed` is `k_switch` times the speed at which the ball was kicked, its current speed for a ball already rolling.
+    pub fn new(
+        initial_pos: Point2,
+        initial_vel: Vec2,
+        switch_speed: f64,
+        model: StraightTwoPhaseModel,
+    ) -> Self {
+        let initial_speed = initial_vel.norm();
+        let direction = if initial_speed > 0. {
+            initial_vel / initial_speed
+        } else {
+            Vec2::zero()
+        };
+        Self {
+            initial_pos,
+            direction,
+            initial_speed,
+            switch_speed: switch_speed.min(initial_speed),
+            slide_decel: model.acc_slide.abs(),
+            roll_decel: model.acc_roll.abs(),
+        }
+    }
+
+    /// trajectory of a ball which was just kicked at `kick_vel`
+    pub fn from_kick(initial_pos: Point2, kick_vel: Vec2, model: StraightTwoPhaseModel) -> Self {
+        Self::new(
+            initial_pos,
+            kick_vel,
+            model.k_switch * kick_vel.norm(),
+            model,
+        )
+    }
+
+    /// trajectory of a ball which is already rolling
+    pub fn rolling(initial_pos: Point2, initial_vel: Vec2, model: StraightTwoPhaseModel) -> Self {
+        Self::new(initial_pos, initial_vel, initial_vel.norm(), model)
+    }
+
+    fn get_slide_duration(&self) -> f64 {
+        if self.slide_decel > 0. {
+            (self.initial_speed - self.switch_speed) / self.slide_decel
+        } else {
+            0.
+        }
+    }
+
+    fn get_roll_duration(&self) -> f64 {
+        if self.switch_speed == 0. {
+            0.
+        } else if self.roll_decel > 0. {
+            self.switch_speed / self.roll_decel
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// (distance travelled, speed) at `t`
+    fn get_distance_and_speed(&self, t: f64) -> (f64, f64) {
+        let t = t.max(0.);
+        let t_slide = self.get_slide_duration();
+        if t < t_slide {
+            return (
+                self.initial_speed * t - 0.5 * self.slide_decel * t * t,
+                self.initial_speed - self.slide_decel * t,
+            );
+        }
+        let slide_distance = (self.initial_speed + self.switch_speed) / 2. * t_slide;
+        let t_roll = (t - t_slide).min(self.get_roll_duration());
+        (
+            slide_distance + self.switch_speed * t_roll - 0.5 * self.roll_decel * t_roll * t_roll,
+            self.switch_speed - self.roll_decel * t_roll,
+        )
+    }
+
+    pub fn get_stop_position(&self) -> Point2 {
+        self.get_position(self.get_total_runtime())
+    }
+}
+
+impl Trajectory<Point2, Vec2> for StraightBallTrajectory {
+    fn get_position(&self, t: f64) -> Point2 {
+        let (distance, _) = self.get_distance_and_speed(t);
+        self.initial_pos + self.direction * distance
+    }
+
+    fn get_velocity(&self, t: f64) -> Vec2 {
+        let (_, speed) = self.get_distance_and_speed(t);
+        self.direction * speed
+    }
+
+    fn get_acceleration(&self, t: f64) -> Vec2 {
+        let decel = match t {
+            t if t < 0. => 0.,
+            t if t < self.get_slide_duration() => self.slide_decel,
+            t if t < self.get_total_runtime() => self.roll_decel,
+            _ => 0.,
+        };
+        self.direction * -decel
+    }
+
+    fn get_total_runtime(&self) -> f64 {
+        self.get_slide_duration() + self.get_roll_duration()
+    }
+
+    fn get_max_speed(&self) -> Option<f64> {
+        Some(self.initial_speed)
+    }
+
+    fn get_time_sections(&self) -> impl Iterator<Item = f64> {
+        [self.get_slide_duration(), self.get_total_runtime()].into_iter()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Hop {
+    start_time: f64,
+    start_pos: Point2,
+    vel: Vec2,
+    vel_z: f64,
+}
+
+impl Hop {
+    fn get_duration(&self) -> f64 {
+        2. * self.vel_z / GRAVITY
+    }
+}
+
+/// A chipped ball: it flies & bounces, losing a fixed fraction of its velocity at each hop, then rolls.
+#[derive(Debug, Clone)]
+pub struct ChipBallTrajectory {
+    hops: Vec<Hop>,
+    rolling: StraightBallTrajectory,
+    /// start time of the rolling phase
+    rolling_start_time: f64,
+}
+
+impl ChipBallTrajectory {
+    /// trajectory of a ball chipped from `initial_pos` at `kick_vel` on the ground plane and `kick_vel_z` vertically
+    pub fn new(
+        initial_pos: Point2,
+        kick_vel: Vec2,
+        kick_vel_z: f64,
+        chip_model: ChipFixedLossModel,
+        straight_model: StraightTwoPhaseModel,
+    ) -> Self {
+        let mut hops = Vec::new();
+        let (mut t, mut pos, mut vel, mut vel_z) = (0., initial_pos, kick_vel, kick_vel_z);
+        while vel_z > CHIP_MIN_VEL_Z && hops.len() < CHIP_MAX_HOPS {
+            let hop = Hop {
+                start_time: t,
+                start_pos: pos,
+                vel,
+                vel_z,
+            };
+            t += hop.get_duration();
+            pos += vel * hop.get_duration();
+            vel = vel
+                * if hops.is_empty() {
+                    chip_model.damping_xy_first_hop
+                } else {
+                    chip_model.damping_xy_other_hops
+                };
+            vel_z *= chip_model.damping_z;
+            hops.push(hop);
+        }
+        Self {
+            hops,
+            rolling: StraightBallTrajectory::rolling(pos, vel, straight_model),
+            rolling_start_time: t,
+        }
+    }
+
+    fn get_hop(&self, t: f64) -> Option<&Hop> {
+        self.hops
+            .iter()
+            .rev()
+            .find(|h| h.start_time <= t && t < self.rolling_start_time)
+    }
+
+    /// height of the ball in [m] at `t`
+    pub fn get_height(&self, t: f64) -> f64 {
+        match self.get_hop(t) {
+            Some(hop) => {
+                let dt = t - hop.start_time;
+                (hop.vel_z * dt - 0.5 * GRAVITY * dt * dt).max(0.)
+            }
+            None => 0.,
+        }
+    }
+
+    pub fn get_stop_position(&self) -> Point2 {
+        self.rolling.get_stop_position()
+    }
+}
+
+impl Trajectory<Point2, Vec2> for ChipBallTrajectory {
+    fn get_position(&self, t: f64) -> Point2 {
+        let t = t.max(0.);
+        match self.get_hop(t) {
+            Some(hop) => hop.start_pos + hop.vel * (t - hop.start_time),
+            None => self.rolling.get_position(t - self.rolling_start_time),
+        }
+    }
+
+    fn get_velocity(&self, t: f64) -> Vec2 {
+        match self.get_hop(t.max(0.)) {
+            Some(hop) => hop.vel,
+            None => self.rolling.get_velocity(t - self.rolling_start_time),
+        }
+    }
+
+    /// acceleration on the ground plane (no drag while flying)
+    fn get_acceleration(&self, t: f64) -> Vec2 {
+        match self.get_hop(t.max(0.)) {
+            Some(_) => Vec2::zero(),
+            None => self.rolling.get_acceleration(t - self.rolling_start_time),
+        }
+    }
+
+    fn get_total_runtime(&self) -> f64 {
+        self.rolling_start_time + self.rolling.get_total_runtime()
+    }
+
+    fn get_max_speed(&self) -> Option<f64> {
+        self.hops
+            .first()
+            .map(|h| h.vel.norm())
+            .or(self.rolling.get_max_speed())
+    }
+
+    fn get_time_sections(&self) -> impl Iterator<Item = f64> {
+        let rolling_start_time = self.rolling_start_time;
+        self.hops
+            .iter()
+            .skip(1)
+            .map(|h| h.start_time)
+            .chain(
+                self.rolling
+                    .get_time_sections()
+                    .map(move |t| rolling_start_time + t),
+            )
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+/// Trajectory of the ball, `t` being the time since the last vision update.
+#[derive(Debug, Clone)]
+pub enum BallTrajectory {
+    Straight(StraightBallTrajectory),
+    Chip {
+        trajectory: ChipBallTrajectory,
+        /// time since the chip at the last vision update
+        elapsed: f64,
+    },
+}
+
+impl BallTrajectory {
+    /// height of the ball in [m] at `t`
+    pub fn get_height(&self, t: f64) -> f64 {
+        match self {
+            BallTrajectory::Straight(_) => 0.,
+            BallTrajectory::Chip {
+                trajectory,
+                elapsed,
+            } => trajectory.get_height(elapsed + t),
+        }
+    }
+
+    pub fn get_stop_position(&self) -> Point2 {
+        match self {
+            BallTrajectory::Straight(trajectory) => trajectory.get_stop_position(),
+            BallTrajectory::Chip { trajectory, .. } => trajectory.get_stop_position(),
+        }
+    }
+
+    /// Time in [s] for the ball to reach the closest point of its path to `point`.
+    /// None if the ball stops or goes past that point before (or if it isn't moving).
+    pub fn time_to_reach(&self, point: Point2) -> Option<f64> {
+        let start = self.get_position(0.);
+        let runtime = self.get_total_runtime();
+        // a ball which doesn't decelerate never stops
+        let (path, path_length) = if runtime.is_finite() {
+            let path = self.get_stop_position() - start;
+            (path, path.norm())
+        } else {
+            (self.get_velocity(0.), f64::INFINITY)
+        };
+        if path.norm() == 0. {
+            return None;
+        }
+        let direction = path / path.norm();
+        let target_distance = (point - start).dot(direction);
+        if !(0. ..=path_length).contains(&target_distance) {
+            return None;
+        }
+        let distance_at = |t: f64| (self.get_position(t) - start).dot(direction);
+
+        let mut t_max = runtime;
+        if !runtime.is_finite() {
+            t_max = 1.;
+            while distance_at(t_max) < target_distance {
+                t_max *= 2.;
+            }
+        }
+        // the distance travelled along the path only increases with time
+        let mut t_min = 0.;
+        for _ in 0..50 {
+            let t = (t_min + t_max) / 2.;
+            if distance_at(t) < target_distance {
+                t_min = t;
+            } else {
+                t_max = t;
+            }
+        }
+        Some(t_max)
+    }
+}
+
+impl Trajectory<Point2, Vec2> for BallTrajectory {
+    fn get_position(&self, t: f64) -> Point2 {
+        match self {
+            BallTrajectory::Straight(trajectory) => trajectory.get_position(t),
+            BallTrajectory::Chip {
+                trajectory,
+                elapsed,
+            } => trajectory.get_position(elapsed + t),
+        }
+    }
+
+    fn get_velocity(&self, t: f64) -> Vec2 {
+        match self {
+            BallTrajectory::Straight(trajectory) => trajectory.get_velocity(t),
+            BallTrajectory::Chip {
+                trajectory,
+                elapsed,
+            } => trajectory.get_velocity(elapsed + t),
+        }
+    }
+
+    fn get_acceleration(&self, t: f64) -> Vec2 {
+        match self {
+            BallTrajectory::Straight(trajectory) => trajectory.get_acceleration(t),
+            BallTrajectory::Chip {
+                trajectory,
+                elapsed,
+            } => trajectory.get_acceleration(elapsed + t),
+        }
+    }
+
+    fn get_total_runtime(&self) -> f64 {
+        match self {
+            BallTrajectory::Straight(trajectory) => trajectory.get_total_runtime(),
+            BallTrajectory::Chip {
+                trajectory,
+                elapsed,
+            } => (trajectory.get_total_runtime() - elapsed).max(0.),
+        }
+    }
+
+    fn get_max_speed(&self) -> Option<f64> {
+        self.get_time_sections()
+            .chain([0.])
+            .map(|t| self.get_velocity(t).norm())
+            .max_by(|s1, s2| s1.partial_cmp(s2).unwrap_or(Ordering::Equal))
+    }
+
+    fn get_time_sections(&self) -> impl Iterator<Item = f64> {
+        match self {
+            BallTrajectory::Straight(trajectory) => {
+                trajectory.get_time_sections().collect::<Vec<_>>()
+            }
+            BallTrajectory::Chip {
+                trajectory,
+                elapsed,
+            } => trajectory
+                .get_time_sections()
+                .map(|t| t - elapsed)
+                .filter(|t| *t >= 0.)
+                .collect(),
+        }
+        .into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: StraightTwoPhaseModel = StraightTwoPhaseModel {
+        acc_slide: -3.,
+        acc_roll: -0.26,
+        k_switch: 0.69,
+    };
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{a} != {b}");
+    }
+
+    #[test]
+    fn kicked_ball_slides_then_rolls_to_a_stop() {
+        let kick_speed = 4.;
+        let trajectory =
+            StraightBallTrajectory::from_kick(Point2::zero(), Vec2::new(kick_speed, 0.), MODEL);
+
+        let switch_speed = MODEL.k_switch * kick_speed;
+        let t_switch = (kick_speed - switch_speed) / MODEL.acc_slide.abs();
+        assert_close(trajectory.get_velocity(t_switch).x, switch_speed);
+        assert_close(
+            trajectory.get_acceleration(t_switch - 1e-3).x,
+            MODEL.acc_slide,
+        );
+        assert_close(
+            trajectory.get_acceleration(t_switch + 1e-3).x,
+            MODEL.acc_roll,
+        );
+
+        let slide_distance =
+            (kick_speed.powi(2) - switch_speed.powi(2)) / (2. * MODEL.acc_slide.abs());
+        let roll_distance = switch_speed.powi(2) / (2. * MODEL.acc_roll.abs());
+        assert_close(
+            trajectory.get_stop_position().x,
+            slide_distance + roll_distance,
+        );
+        assert_close(
+            trajectory.get_total_runtime(),
+            t_switch + switch_speed / MODEL.acc_roll.abs(),
+        );
+        assert_close(
+            trajectory.get_velocity(trajectory.get_total_runtime()).x,
+            0.,
+        );
+    }
+
+    #[test]
+    fn rolling_ball_stops_sooner_than_a_sliding_one() {
+        let vel = Vec2::new(0., -2.);
+        let rolling = StraightBallTrajectory::rolling(Point2::zero(), vel, MODEL);
+        assert_close(
+            rolling.get_stop_position().y,
+            -(2_f64.powi(2)) / (2. * 0.26),
+        );
+        let sliding = StraightBallTrajectory::from_kick(Point2::zero(), vel, MODEL);
+        assert!(sliding.get_stop_position().y > rolling.get_stop_position().y);
+    }
+
+    #[test]
+    fn ball_without_rolling_friction_is_reached() {
+        let model = StraightTwoPhaseModel {
+            acc_roll: 0.,
+            ..MODEL
+        };
+        let trajectory = BallTrajectory::Straight(StraightBallTrajectory::rolling(
+            Point2::zero(),
+            Vec2::new(2., 0.),
+            model,
+        ));
+        assert_eq!(trajectory.get_total_runtime(), f64::INFINITY);
+        let t = trajectory
+            .time_to_reach(Point2::new(5., 0.5))
+            .expect("the ball never stops");
+        assert!((t - 2.5).abs() < 1e-6, "{t}");
+        assert_eq!(trajectory.time_to_reach(Point2::new(-1., 0.)), None);
+    }
+
+    #[test]
+    fn chipped_ball_lands_after_its_first_hop() {
+        let (vel, vel_z) = (Vec2::new(2., 1.), 3.);
+        let chip_model = ChipFixedLossModel::default();
+        let trajectory = ChipBallTrajectory::new(Point2::zero(), vel, vel_z, chip_model, MODEL);
+
+        let landing_t = 2. * vel_z / GRAVITY;
+        assert_close(
+            trajectory.get_height(landing_t / 2.),
+            vel_z.powi(2) / (2. * GRAVITY),
+        );
+        assert_close(trajectory.get_height(landing_t - 1e-9), 0.);
+        let landing = trajectory.get_position(landing_t);
+        assert_close(landing.x, vel.x * landing_t);
+        assert_close(landing.y, vel.y * landing_t);
+        // the second hop is damped
+        let second_hop = trajectory.get_velocity(landing_t + 1e-6);
+        assert_close(second_hop.x, vel.x * chip_model.damping_xy_first_hop);
+        let second_hop_height =
+            trajectory.get_height(landing_t + vel_z * chip_model.damping_z / GRAVITY);
+        assert_close(
+            second_hop_height,
+            (vel_z * chip_model.damping_z).powi(2) / (2. * GRAVITY),
+        );
+        assert!(trajectory.get_stop_position().x > landing.x);
+    }
+}
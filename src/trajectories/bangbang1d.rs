@@ -2,7 +2,7 @@
 
 use std::cmp::Ordering;
 
-use super::Trajectory;
+use super::{slowed_down, Trajectory};
 
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
 struct BangBangPart {
@@ -96,6 +96,143 @@ impl BangBang1d {
         }
     }
 
+    /// Reaches `target_position` at `final_vel` (within `[-max_vel, max_vel]`) as fast as possible,
+    /// instead of stopping there. Past its runtime, the trajectory continues at `final_vel`.
+    pub fn with_final_velocity(
+        initial_position: f64,
+        initial_vel: f64,
+        target_position: f64,
+        final_vel: f64,
+        max_vel: f64,
+        max_accel: f64,
+    ) -> Self {
+        Self::with_cruise_vel(
+            initial_position,
+            initial_vel,
+            target_position,
+            final_vel.clamp(-max_vel, max_vel),
+            max_vel,
+            max_accel,
+        )
+    }
+
+    /// Reaches `target_position` at `final_vel` after `arrival_time` in [s], by lowering its cruise velocity.
+    /// The trajectory arrives as close to `arrival_time` as it can, check `get_total_runtime`.
+    pub fn with_arrival_time(
+        initial_position: f64,
+        initial_vel: f64,
+        target_position: f64,
+        final_vel: f64,
+        arrival_time: f64,
+        max_vel: f64,
+        max_accel: f64,
+    ) -> Self {
+        let final_vel = final_vel.clamp(-max_vel, max_vel);
+        slowed_down(arrival_time, |scale| {
+            Self::with_cruise_vel(
+                initial_position,
+                initial_vel,
+                target_position,
+                final_vel,
+                max_vel * scale,
+                max_accel,
+            )
+        })
+    }
+
+    /// Reaches `target_position` at `final_vel`, not going faster than `max_cruise_vel`
+    /// unless the initial or final velocity is.
+    pub(super) fn with_cruise_vel(
+        initial_position: f64,
+        initial_vel: f64,
+        target_position: f64,
+        final_vel: f64,
+        max_cruise_vel: f64,
+        max_accel: f64,
+    ) -> Self {
+        if target_position < initial_position {
+            // planned towards the positive positions, then mirrored
+            return Self::with_cruise_vel(
+                -initial_position,
+                -initial_vel,
+                -target_position,
+                -final_vel,
+                max_cruise_vel,
+                max_accel,
+            )
+            .mirrored();
+        }
+        let distance = target_position - initial_position;
+        let mean_square_vel = 0.5 * (initial_vel.powi(2) + final_vel.powi(2));
+
+        let vel = if distance >= distance_to_reach(initial_vel, final_vel, max_accel) {
+            // accelerate to a peak over both velocities, then to the final velocity
+            let peak_vel = (mean_square_vel + max_accel * distance).sqrt();
+            if peak_vel <= max_cruise_vel {
+                peak_vel
+            } else {
+                // capped by the max velocity, the rest of the distance is cruised
+                let cruise_distance = distance
+                    - distance_to_reach(initial_vel, max_cruise_vel, max_accel)
+                    - distance_to_reach(max_cruise_vel, final_vel, max_accel);
+                if cruise_distance >= 0. {
+                    return calc_parts(
+                        initial_position,
+                        initial_vel,
+                        max_cruise_vel,
+                        cruise_distance / max_cruise_vel,
+                        final_vel,
+                        max_accel,
+                    );
+                }
+                // the initial or final velocity is over the max, and the target too close to get under it:
+                // going under both velocities (if they're positive) is slower than the peak, but closer to the max
+                let under_vel = (mean_square_vel - max_accel * distance).sqrt();
+                if under_vel <= initial_vel.min(final_vel) {
+                    under_vel
+                } else {
+                    peak_vel
+                }
+            }
+        } else {
+            // the target is too close to get to the final velocity straight away,
+            // overshoot it (going under both velocities) and come back
+            let peak_vel = -(mean_square_vel - max_accel * distance).sqrt();
+            if peak_vel >= -max_cruise_vel {
+                peak_vel
+            } else {
+                let cruise_distance = distance
+                    - distance_to_reach(initial_vel, -max_cruise_vel, max_accel)
+                    - distance_to_reach(-max_cruise_vel, final_vel, max_accel);
+                return calc_parts(
+                    initial_position,
+                    initial_vel,
+                    -max_cruise_vel,
+                    cruise_distance / -max_cruise_vel,
+                    final_vel,
+                    max_accel,
+                );
+            }
+        };
+        calc_parts(initial_position, initial_vel, vel, 0., final_vel, max_accel)
+    }
+
+    /// The same trajectory, towards the opposite positions.
+    fn mirrored(mut self) -> Self {
+        for part in &mut self.bangbang_parts {
+            part.initial_pos = -part.initial_pos;
+            part.initial_vel = -part.initial_vel;
+            part.accel = -part.accel;
+        }
+        self
+    }
+
+    fn get_final_vel(&self) -> f64 {
+        let last_part = self.bangbang_parts[self.n_parts - 1];
+        let t = last_part.end_time - self.bangbang_parts[self.n_parts - 2].end_time;
+        last_part.initial_vel + last_part.accel * t
+    }
+
     fn find_part_idx(&self, t: f64) -> usize {
         for i in 0..self.n_parts {
             if t < self.bangbang_parts[i].end_time {
@@ -110,6 +247,43 @@ impl BangBang1d {
     }
 }
 
+/// distance covered to change the velocity from `v0` to `v1` at the max acceleration
+fn distance_to_reach(v0: f64, v1: f64, a_max: f64) -> f64 {
+    if v0 == v1 {
+        return 0.;
+    }
+    let a = if v1 > v0 { a_max } else { -a_max };
+    (v1 * v1 - v0 * v0) / (2. * a)
+}
+
+/// from `v0` to `v1` at the max acceleration, at `v1` for `t2`, then to `v3` at the max acceleration
+fn calc_parts(s0: f64, v0: f64, v1: f64, t2: f64, v3: f64, a_max: f64) -> BangBang1d {
+    let a1 = if v1 > v0 { a_max } else { -a_max };
+    let a3 = if v3 > v1 { a_max } else { -a_max };
+    let t1 = (v1 - v0) / a1;
+    let t3 = (v3 - v1) / a3;
+    let s1 = s0 + (0.5 * (v0 + v1) * t1);
+    let s2 = s1 + v1 * t2;
+
+    let mut parts = [BangBangPart::default(); 3];
+    parts[0].end_time = t1;
+    parts[0].accel = a1;
+    parts[0].initial_vel = v0;
+    parts[0].initial_pos = s0;
+    parts[1].end_time = t1 + t2;
+    parts[1].accel = 0.;
+    parts[1].initial_vel = v1;
+    parts[1].initial_pos = s1;
+    parts[2].end_time = t1 + t2 + t3;
+    parts[2].accel = a3;
+    parts[2].initial_vel = v1;
+    parts[2].initial_pos = s2;
+    BangBang1d {
+        bangbang_parts: parts,
+        n_parts: 3,
+    }
+}
+
 fn calc_tri(s0: f64, v0: f64, s2: f64, a: f64) -> BangBang1d {
     let sq = if a > 0. {
         // + -
@@ -174,11 +348,13 @@ impl Trajectory<f64, f64> for BangBang1d {
 
         if traj_time >= self.get_total_runtime() {
             // requested time beyond final element
+            // continuing at the final velocity
             let last_part = self.bangbang_parts[self.n_parts - 1];
             let t = last_part.end_time - self.bangbang_parts[self.n_parts - 2].end_time;
             return last_part.initial_pos
                 + (last_part.initial_vel * t)
-                + (0.5 * last_part.accel * t * t);
+                + (0.5 * last_part.accel * t * t)
+                + self.get_final_vel() * (traj_time - self.get_total_runtime());
         }
 
         let piece_idx = self.find_part_idx(traj_time);
@@ -197,7 +373,7 @@ impl Trajectory<f64, f64> for BangBang1d {
 
         if traj_time >= self.get_total_runtime() {
             // requested time beyond final element
-            return 0.0;
+            return self.get_final_vel();
         }
 
         let piece_idx = self.find_part_idx(traj_time);
@@ -242,3 +418,114 @@ impl Trajectory<f64, f64> for BangBang1d {
             .max_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_VEL: f64 = 2.;
+    const MAX_ACC: f64 = 3.;
+
+    fn assert_reaches(trajectory: &BangBang1d, pos: f64, vel: f64) {
+        let runtime = trajectory.get_total_runtime();
+        assert!(runtime.is_finite() && runtime >= 0., "{trajectory:?}");
+        assert!(
+            (trajectory.get_position(runtime) - pos).abs() < 1e-6,
+            "ends at {} instead of {pos}: {trajectory:?}",
+            trajectory.get_position(runtime)
+        );
+        assert!(
+            (trajectory.get_velocity(runtime) - vel).abs() < 1e-6,
+            "ends at {} [m/s] instead of {vel}: {trajectory:?}",
+            trajectory.get_velocity(runtime)
+        );
+    }
+
+    #[test]
+    fn final_velocity_forward() {
+        let trajectory = BangBang1d::with_final_velocity(0., 0., 0.5, 1., MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, 0.5, 1.);
+
+        // long enough to cruise at the max velocity
+        let trajectory = BangBang1d::with_final_velocity(0., 0., 10., 1., MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, 10., 1.);
+        assert!(trajectory
+            .get_max_speed()
+            .is_some_and(|v| v <= MAX_VEL + 1e-9));
+    }
+
+    #[test]
+    fn final_velocity_backward() {
+        let trajectory = BangBang1d::with_final_velocity(1., 0., -0.5, -1., MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, -0.5, -1.);
+
+        let trajectory = BangBang1d::with_final_velocity(1., 0.5, -9., -1., MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, -9., -1.);
+        assert!(trajectory.get_velocity(0.5) >= -MAX_VEL - 1e-9);
+    }
+
+    #[test]
+    fn final_velocity_overshoot() {
+        // too fast to stop before the target, it's passed then reached again
+        let trajectory = BangBang1d::with_final_velocity(0., 2., 0.1, 0., MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, 0.1, 0.);
+        assert!(trajectory.get_position(2. / MAX_ACC) > 0.1);
+
+        // too close to reach the final velocity, it backs up to get a run-up
+        let trajectory = BangBang1d::with_final_velocity(0., 0., 0.1, 2., MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, 0.1, 2.);
+        assert!(trajectory.get_velocity(0.1) < 0.);
+    }
+
+    #[test]
+    fn final_velocity_from_above_max_vel() {
+        let trajectory = BangBang1d::with_final_velocity(0., 4., 5., 1., MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, 5., 1.);
+        // brakes down to the max velocity, then cruises
+        let braking_time = (4. - MAX_VEL) / MAX_ACC;
+        assert!((trajectory.get_velocity(braking_time + 0.1) - MAX_VEL).abs() < 1e-6);
+
+        // too close to get under the max velocity
+        let trajectory = BangBang1d::with_final_velocity(0., 4., 0.5, 1., MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, 0.5, 1.);
+    }
+
+    #[test]
+    fn final_velocity_is_clamped() {
+        let trajectory = BangBang1d::with_final_velocity(0., 0., 5., 3., MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, 5., MAX_VEL);
+    }
+
+    #[test]
+    fn continues_at_the_final_velocity() {
+        let trajectory = BangBang1d::with_final_velocity(0., 0., 2., 1., MAX_VEL, MAX_ACC);
+        let runtime = trajectory.get_total_runtime();
+        assert!((trajectory.get_position(runtime + 1.) - 3.).abs() < 1e-6);
+        assert_eq!(trajectory.get_acceleration(runtime + 1.), 0.);
+    }
+
+    #[test]
+    fn arrival_time() {
+        let fastest = BangBang1d::with_final_velocity(0., 0., 2., 0.5, MAX_VEL, MAX_ACC);
+        let arrival_time = 3.;
+        assert!(fastest.get_total_runtime() < arrival_time);
+
+        let trajectory =
+            BangBang1d::with_arrival_time(0., 0., 2., 0.5, arrival_time, MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, 2., 0.5);
+        assert!((trajectory.get_total_runtime() - arrival_time).abs() < 0.01);
+
+        let trajectory =
+            BangBang1d::with_arrival_time(0., 0., -2., -0.5, arrival_time, MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, -2., -0.5);
+        assert!((trajectory.get_total_runtime() - arrival_time).abs() < 0.01);
+    }
+
+    #[test]
+    fn arrival_time_too_early() {
+        let fastest = BangBang1d::with_final_velocity(0., 0., 2., 0.5, MAX_VEL, MAX_ACC);
+        let trajectory = BangBang1d::with_arrival_time(0., 0., 2., 0.5, 0.1, MAX_VEL, MAX_ACC);
+        assert_reaches(&trajectory, 2., 0.5);
+        assert_eq!(trajectory.get_total_runtime(), fastest.get_total_runtime());
+    }
+}
@@ -4,14 +4,22 @@ use std::{cmp::Ordering, f64::consts::PI};
 
 use crate::math::{Point2, Vec2};
 
-use super::{bangbang1d::BangBang1d, Trajectory};
+use super::{bangbang1d::BangBang1d, slowed_down, Trajectory};
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub struct BangBang2d {
     x: BangBang1d,
     y: BangBang1d,
 }
 
+fn clamp_norm(v: Vec2, max_norm: f64) -> Vec2 {
+    if v.norm() > max_norm {
+        v.normalized() * max_norm
+    } else {
+        v
+    }
+}
+
 impl BangBang2d {
     pub fn new(
         initial_pos: Point2,
@@ -21,50 +29,140 @@ impl BangBang2d {
         max_accel: f64,
         accuracy: f64,
     ) -> Self {
-        let initial_posx = initial_pos.x;
-        let initial_posy = initial_pos.y;
-        let target_posx = target_pos.x;
-        let target_posy = target_pos.y;
-        let initial_velx = initial_vel.x;
-        let initial_vely = initial_vel.y;
+        Self::synchronized(
+            |max_vel, max_accel| {
+                BangBang1d::new(
+                    initial_pos.x,
+                    initial_vel.x,
+                    target_pos.x,
+                    max_vel,
+                    max_accel,
+                )
+            },
+            |max_vel, max_accel| {
+                BangBang1d::new(
+                    initial_pos.y,
+                    initial_vel.y,
+                    target_pos.y,
+                    max_vel,
+                    max_accel,
+                )
+            },
+            max_vel,
+            max_accel,
+            accuracy,
+        )
+    }
+
+    /// Reaches `target_pos` at `final_vel` (its norm within `max_vel`) instead of stopping there,
+    /// see `BangBang1d::with_final_velocity`.
+    /// The x and y trajectories end within `accuracy` in [s] of each other, the one ending first continues at its final velocity.
+    pub fn with_final_velocity(
+        initial_pos: Point2,
+        initial_vel: Vec2,
+        target_pos: Point2,
+        final_vel: Vec2,
+        max_vel: f64,
+        max_accel: f64,
+        accuracy: f64,
+    ) -> Self {
+        Self::with_cruise_vel(
+            initial_pos,
+            initial_vel,
+            target_pos,
+            clamp_norm(final_vel, max_vel),
+            max_vel,
+            max_accel,
+            accuracy,
+        )
+    }
+
+    /// Reaches `target_pos` at `final_vel` after `arrival_time` in [s], by lowering its cruise velocity.
+    /// The trajectory arrives as close to `arrival_time` as it can, check `get_total_runtime`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_arrival_time(
+        initial_pos: Point2,
+        initial_vel: Vec2,
+        target_pos: Point2,
+        final_vel: Vec2,
+        arrival_time: f64,
+        max_vel: f64,
+        max_accel: f64,
+        accuracy: f64,
+    ) -> Self {
+        let final_vel = clamp_norm(final_vel, max_vel);
+        slowed_down(arrival_time, |scale| {
+            Self::with_cruise_vel(
+                initial_pos,
+                initial_vel,
+                target_pos,
+                final_vel,
+                max_vel * scale,
+                max_accel,
+                accuracy,
+            )
+        })
+    }
 
+    /// Reaches `target_pos` at `final_vel`, see `BangBang1d::with_cruise_vel`.
+    fn with_cruise_vel(
+        initial_pos: Point2,
+        initial_vel: Vec2,
+        target_pos: Point2,
+        final_vel: Vec2,
+        max_cruise_vel: f64,
+        max_accel: f64,
+        accuracy: f64,
+    ) -> Self {
+        Self::synchronized_quickest(
+            |max_vel, max_accel| {
+                BangBang1d::with_cruise_vel(
+                    initial_pos.x,
+                    initial_vel.x,
+                    target_pos.x,
+                    final_vel.x,
+                    max_vel,
+                    max_accel,
+                )
+            },
+            |max_vel, max_accel| {
+                BangBang1d::with_cruise_vel(
+                    initial_pos.y,
+                    initial_vel.y,
+                    target_pos.y,
+                    final_vel.y,
+                    max_vel,
+                    max_accel,
+                )
+            },
+            max_cruise_vel,
+            max_accel,
+            accuracy,
+        )
+    }
+
+    /// Splits the max velocity & acceleration between the x and y trajectories (made by `x_with` & `y_with`
+    /// from their max velocity & acceleration), so that they take the same time.
+    fn synchronized(
+        x_with: impl Fn(f64, f64) -> BangBang1d,
+        y_with: impl Fn(f64, f64) -> BangBang1d,
+        max_vel: f64,
+        max_accel: f64,
+        accuracy: f64,
+    ) -> Self {
         let mut inc = PI / 8.0;
         let mut alpha = PI / 4.0;
 
-        let mut x: BangBang1d = BangBang1d::new(
-            initial_posx,
-            initial_velx,
-            target_posx,
-            max_vel * alpha.cos(),
-            max_accel * alpha.cos(),
-        ); // TODO: don't do that
-        let mut y: BangBang1d = BangBang1d::new(
-            initial_posy,
-            initial_vely,
-            target_posy,
-            max_vel * alpha.sin(),
-            max_accel * alpha.sin(),
-        );
+        let mut x = x_with(max_vel * alpha.cos(), max_accel * alpha.cos()); // TODO: don't do that
+        let mut y = y_with(max_vel * alpha.sin(), max_accel * alpha.sin());
 
         // binary search, some iterations (fixed)
         while inc > 1e-7 {
             let s_a = alpha.sin();
             let c_a = alpha.cos();
 
-            x = BangBang1d::new(
-                initial_posx,
-                initial_velx,
-                target_posx,
-                max_vel * c_a,
-                max_accel * c_a,
-            );
-            y = BangBang1d::new(
-                initial_posy,
-                initial_vely,
-                target_posy,
-                max_vel * s_a,
-                max_accel * s_a,
-            );
+            x = x_with(max_vel * c_a, max_accel * c_a);
+            y = y_with(max_vel * s_a, max_accel * s_a);
 
             let diff = (x.get_total_runtime() - y.get_total_runtime()).abs();
             if diff < accuracy {
@@ -83,6 +181,65 @@ impl BangBang2d {
     }
 }
 
+impl BangBang2d {
+    /// Like `synchronized`, for the trajectories whose runtime doesn't only decrease with their max velocity
+    /// (e.g. with a final velocity): the split of the limits giving the quickest trajectory is kept,
+    /// then the quicker of the x and y trajectories is slowed down to end with the other
+    /// (otherwise it would continue at its final velocity, away from the target).
+    fn synchronized_quickest(
+        x_with: impl Fn(f64, f64) -> BangBang1d,
+        y_with: impl Fn(f64, f64) -> BangBang1d,
+        max_vel: f64,
+        max_accel: f64,
+        accuracy: f64,
+    ) -> Self {
+        let with_split = |alpha: f64| {
+            let (s_a, c_a) = alpha.sin_cos();
+            Self {
+                x: x_with(max_vel * c_a, max_accel * c_a),
+                y: y_with(max_vel * s_a, max_accel * s_a),
+            }
+        };
+
+        let mut inc = PI / 8.0;
+        let mut alpha = PI / 4.0;
+        let mut quickest = (alpha, with_split(alpha));
+        while inc > 1e-7 {
+            let trajectory = with_split(alpha);
+            if trajectory.get_total_runtime() < quickest.1.get_total_runtime() {
+                quickest = (alpha, trajectory);
+            }
+            let (x_runtime, y_runtime) = (
+                trajectory.x.get_total_runtime(),
+                trajectory.y.get_total_runtime(),
+            );
+            if (x_runtime - y_runtime).abs() < accuracy {
+                break;
+            }
+            if x_runtime > y_runtime {
+                alpha -= inc;
+            } else {
+                alpha += inc;
+            }
+            inc *= 0.5;
+        }
+
+        let (alpha, Self { mut x, mut y }) = quickest;
+        let (s_a, c_a) = alpha.sin_cos();
+        let runtime = x.get_total_runtime().max(y.get_total_runtime());
+        if x.get_total_runtime() < runtime {
+            x = slowed_down(runtime, |scale| {
+                x_with(max_vel * c_a * scale, max_accel * c_a * scale)
+            });
+        } else if y.get_total_runtime() < runtime {
+            y = slowed_down(runtime, |scale| {
+                y_with(max_vel * s_a * scale, max_accel * s_a * scale)
+            });
+        }
+        Self { x, y }
+    }
+}
+
 impl Trajectory<Point2, Vec2> for BangBang2d {
     fn get_position(&self, t: f64) -> Point2 {
         Point2::new(self.x.get_position(t), self.y.get_position(t))
@@ -111,3 +268,156 @@ impl Trajectory<Point2, Vec2> for BangBang2d {
         self.x.get_time_sections().chain(self.y.get_time_sections())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_VEL: f64 = 2.;
+    const MAX_ACC: f64 = 3.;
+    const ACCURACY: f64 = 0.01;
+
+    fn assert_reaches(trajectory: &BangBang2d, pos: Point2, vel: Vec2) {
+        let runtime = trajectory.get_total_runtime();
+        assert!(runtime.is_finite(), "{trajectory:?}");
+        assert!(
+            (trajectory.get_position(runtime) - pos).norm() < 1e-3,
+            "ends at {:?} instead of {pos:?}",
+            trajectory.get_position(runtime)
+        );
+        assert!(
+            (trajectory.get_velocity(runtime) - vel).norm() < 1e-3,
+            "ends at {:?} [m/s] instead of {vel:?}",
+            trajectory.get_velocity(runtime)
+        );
+        // x & y end together
+        let x_runtime = trajectory.x.get_total_runtime();
+        let y_runtime = trajectory.y.get_total_runtime();
+        assert!(
+            (x_runtime - y_runtime).abs() < ACCURACY + 1e-3,
+            "x ends at {x_runtime}s, y at {y_runtime}s"
+        );
+    }
+
+    #[test]
+    fn final_velocity() {
+        let target = Point2::new(2., 1.);
+        let final_vel = Vec2::new(1., 0.5);
+        let trajectory = BangBang2d::with_final_velocity(
+            Point2::zero(),
+            Vec2::zero(),
+            target,
+            final_vel,
+            MAX_VEL,
+            MAX_ACC,
+            ACCURACY,
+        );
+        assert_reaches(&trajectory, target, final_vel);
+        assert!(trajectory
+            .get_max_speed()
+            .is_some_and(|v| v <= MAX_VEL + 1e-6));
+    }
+
+    #[test]
+    fn final_velocity_backward() {
+        let target = Point2::new(-3., -0.5);
+        let final_vel = Vec2::new(-1., 0.);
+        let trajectory = BangBang2d::with_final_velocity(
+            Point2::new(1., 1.),
+            Vec2::new(0.5, 0.),
+            target,
+            final_vel,
+            MAX_VEL,
+            MAX_ACC,
+            ACCURACY,
+        );
+        assert_reaches(&trajectory, target, final_vel);
+    }
+
+    #[test]
+    fn synchronizes_a_quicker_axis() {
+        // y alone would be much quicker than x, it's slowed down to end with it
+        let target = Point2::new(4., 0.2);
+        let final_vel = Vec2::new(0., 1.);
+        let trajectory = BangBang2d::with_final_velocity(
+            Point2::zero(),
+            Vec2::zero(),
+            target,
+            final_vel,
+            MAX_VEL,
+            MAX_ACC,
+            ACCURACY,
+        );
+        assert_reaches(&trajectory, target, final_vel);
+    }
+
+    #[test]
+    fn final_velocity_overshoot() {
+        // too fast to stop before the target
+        let target = Point2::new(0.1, 0.);
+        let trajectory = BangBang2d::with_final_velocity(
+            Point2::zero(),
+            Vec2::new(2., 0.5),
+            target,
+            Vec2::zero(),
+            MAX_VEL,
+            MAX_ACC,
+            ACCURACY,
+        );
+        assert_reaches(&trajectory, target, Vec2::zero());
+    }
+
+    #[test]
+    fn final_velocity_from_above_max_vel() {
+        let target = Point2::new(4., 1.);
+        let final_vel = Vec2::new(1., 0.);
+        let trajectory = BangBang2d::with_final_velocity(
+            Point2::zero(),
+            Vec2::new(4., 0.),
+            target,
+            final_vel,
+            MAX_VEL,
+            MAX_ACC,
+            ACCURACY,
+        );
+        assert_reaches(&trajectory, target, final_vel);
+    }
+
+    #[test]
+    fn final_velocity_is_clamped() {
+        let trajectory = BangBang2d::with_final_velocity(
+            Point2::zero(),
+            Vec2::zero(),
+            Point2::new(5., 5.),
+            Vec2::new(3., 3.),
+            MAX_VEL,
+            MAX_ACC,
+            ACCURACY,
+        );
+        let final_vel = trajectory.get_velocity(trajectory.get_total_runtime());
+        assert!((final_vel.norm() - MAX_VEL).abs() < 1e-3, "{final_vel:?}");
+    }
+
+    #[test]
+    fn arrival_time() {
+        let target = Point2::new(2., -1.);
+        let final_vel = Vec2::new(0.5, 0.);
+        let arrival_time = 4.;
+        let trajectory = BangBang2d::with_arrival_time(
+            Point2::zero(),
+            Vec2::zero(),
+            target,
+            final_vel,
+            arrival_time,
+            MAX_VEL,
+            MAX_ACC,
+            ACCURACY,
+        );
+        assert_reaches(&trajectory, target, final_vel);
+        assert!(
+            (trajectory.get_total_runtime() - arrival_time).abs() < 0.02,
+            "arrives after {}s",
+            trajectory.get_total_runtime()
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::f64::consts::TAU;
+
+use crate::math::{angle_difference, wrap_angle};
+
+use super::{bangbang1d::BangBang1d, Trajectory};
+
+/// A `BangBang1d` on an angle in [rad], turning the quickest way to the target orientation
+/// (the shortest way, or the other way around when the robot is already turning fast that way).
+/// Its positions are wrapped to [-PI, PI].
+#[derive(Debug, Clone, Copy)]
+pub struct BangBangOrientation {
+    trajectory: BangBang1d,
+}
+
+impl BangBangOrientation {
+    pub fn new(
+        initial_orientation: f64,
+        initial_angular_vel: f64,
+        target_orientation: f64,
+        max_angular_vel: f64,
+        max_angular_accel: f64,
+    ) -> Self {
+        let shortest = angle_difference(target_orientation, initial_orientation);
+        let other_way = if shortest > 0. {
+            shortest - TAU
+        } else {
+            shortest + TAU
+        };
+        let trajectory = [shortest, other_way]
+            .map(|diff| {
+                BangBang1d::new(
+                    initial_orientation,
+                    initial_angular_vel,
+                    initial_orientation + diff,
+                    max_angular_vel,
+                    max_angular_accel,
+                )
+            })
+            .into_iter()
+            .min_by(|a, b| a.get_total_runtime().total_cmp(&b.get_total_runtime()))
+            .expect("there are two candidates");
+        Self { trajectory }
+    }
+}
+
+impl Trajectory<f64, f64> for BangBangOrientation {
+    fn get_position(&self, t: f64) -> f64 {
+        wrap_angle(self.trajectory.get_position(t))
+    }
+
+    fn get_velocity(&self, t: f64) -> f64 {
+        self.trajectory.get_velocity(t)
+    }
+
+    fn get_acceleration(&self, t: f64) -> f64 {
+        self.trajectory.get_acceleration(t)
+    }
+
+    fn get_total_runtime(&self) -> f64 {
+        self.trajectory.get_total_runtime()
+    }
+
+    fn get_max_speed(&self) -> Option<f64> {
+        self.get_time_sections()
+            .map(|t| self.get_velocity(t).abs())
+            .max_by(f64::total_cmp)
+    }
+
+    fn get_time_sections(&self) -> impl Iterator<Item = f64> {
+        self.trajectory.get_time_sections()
+    }
+}
@@ -0,0 +1,168 @@
+use crate::math::{Point2, Vec2};
+
+use super::{
+    bangbang2d::BangBang2d, bangbang_orientation::BangBangOrientation, slowed_down, Trajectory,
+};
+
+/// Position & orientation of a robot.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Pose {
+    pub pos: Point2,
+    /// in [rad]
+    pub orientation: f64,
+}
+
+/// Velocity of a robot.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Twist {
+    /// in [m/s]
+    pub vel: Vec2,
+    /// in [rad/s]
+    pub angular_vel: f64,
+}
+
+/// x/y and orientation bang-bang trajectories, the quicker one is slowed down
+/// so that the robot reaches its position and its orientation at the same time.
+#[derive(Debug, Clone, Copy)]
+pub struct BangBangPose {
+    xy: BangBang2d,
+    orientation: BangBangOrientation,
+}
+
+impl BangBangPose {
+    pub fn new(
+        initial: Pose,
+        initial_vel: Twist,
+        target: Pose,
+        max_vel: f64,
+        max_accel: f64,
+        max_angular_vel: f64,
+        max_angular_accel: f64,
+    ) -> Self {
+        Self::synchronized(
+            |scale| {
+                BangBang2d::new(
+                    initial.pos,
+                    initial_vel.vel,
+                    target.pos,
+                    max_vel * scale,
+                    max_accel,
+                    0.1,
+                )
+            },
+            initial,
+            initial_vel,
+            target,
+            max_angular_vel,
+            max_angular_accel,
+        )
+    }
+
+    /// Passes by `target` at `final_vel` in [m/s] (e.g. through an intermediate waypoint)
+    /// instead of stopping there, see `BangBang2d::with_final_velocity`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_final_velocity(
+        initial: Pose,
+        initial_vel: Twist,
+        target: Pose,
+        final_vel: Vec2,
+        max_vel: f64,
+        max_accel: f64,
+        max_angular_vel: f64,
+        max_angular_accel: f64,
+    ) -> Self {
+        Self::synchronized(
+            |scale| {
+                BangBang2d::with_final_velocity(
+                    initial.pos,
+                    initial_vel.vel,
+                    target.pos,
+                    final_vel,
+                    max_vel * scale,
+                    max_accel,
+                    0.1,
+                )
+            },
+            initial,
+            initial_vel,
+            target,
+            max_angular_vel,
+            max_angular_accel,
+        )
+    }
+
+    /// Slows down the quicker of the x/y trajectory (made by `xy_at` from a velocity scale)
+    /// and the orientation one, so that they end together.
+    fn synchronized(
+        xy_at: impl Fn(f64) -> BangBang2d,
+        initial: Pose,
+        initial_vel: Twist,
+        target: Pose,
+        max_angular_vel: f64,
+        max_angular_accel: f64,
+    ) -> Self {
+        let orientation_at = |scale: f64| {
+            BangBangOrientation::new(
+                initial.orientation,
+                initial_vel.angular_vel,
+                target.orientation,
+                max_angular_vel * scale,
+                max_angular_accel,
+            )
+        };
+
+        let xy = xy_at(1.);
+        let orientation = orientation_at(1.);
+        if xy.get_total_runtime() > orientation.get_total_runtime() {
+            Self {
+                orientation: slowed_down(xy.get_total_runtime(), orientation_at),
+                xy,
+            }
+        } else {
+            Self {
+                xy: slowed_down(orientation.get_total_runtime(), xy_at),
+                orientation,
+            }
+        }
+    }
+}
+
+impl Trajectory<Pose, Twist> for BangBangPose {
+    fn get_position(&self, t: f64) -> Pose {
+        Pose {
+            pos: self.xy.get_position(t),
+            orientation: self.orientation.get_position(t),
+        }
+    }
+
+    fn get_velocity(&self, t: f64) -> Twist {
+        Twist {
+            vel: self.xy.get_velocity(t),
+            angular_vel: self.orientation.get_velocity(t),
+        }
+    }
+
+    fn get_acceleration(&self, t: f64) -> Twist {
+        Twist {
+            vel: self.xy.get_acceleration(t),
+            angular_vel: self.orientation.get_acceleration(t),
+        }
+    }
+
+    fn get_total_runtime(&self) -> f64 {
+        self.xy
+            .get_total_runtime()
+            .max(self.orientation.get_total_runtime())
+    }
+
+    /// of the x/y part in [m/s]
+    fn get_max_speed(&self) -> Option<f64> {
+        self.xy.get_max_speed()
+    }
+
+    fn get_time_sections(&self) -> impl Iterator<Item = f64> {
+        self.xy
+            .get_time_sections()
+            .chain(self.orientation.get_time_sections())
+    }
+}
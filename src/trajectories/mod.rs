@@ -1,5 +1,8 @@
+pub mod ball;
 pub mod bangbang1d;
 pub mod bangbang2d;
+pub mod bangbang_orientation;
+pub mod bangbang_pose;
 
 pub trait Trajectory<P, V> {
     fn get_position(&self, t: f64) -> P;
@@ -10,3 +13,46 @@ pub trait Trajectory<P, V> {
     fn get_max_speed(&self) -> Option<f64>;
     fn get_time_sections(&self) -> impl Iterator<Item = f64>;
 }
+
+/// the slowest a trajectory is slowed down to, as a fraction of its max velocity
+const MIN_SPEED_SCALE: f64 = 0.01;
+/// the runtime doesn't always increase when slowing down, the scales are first scanned from the fastest
+const SLOW_DOWN_SCAN_STEPS: usize = 20;
+const SLOW_DOWN_ITERATIONS: usize = 12;
+
+/// The trajectory at the first velocity scale (from 1 down to `MIN_SPEED_SCALE`) which takes `runtime`,
+/// the fastest one if it's too slow already and the slowest one if it's still too fast.
+pub(crate) fn slowed_down<P, V, T: Trajectory<P, V>>(
+    runtime: f64,
+    trajectory_at: impl Fn(f64) -> T,
+) -> T {
+    let fastest = trajectory_at(1.);
+    if fastest.get_total_runtime() >= runtime {
+        return fastest;
+    }
+
+    let step = (1. - MIN_SPEED_SCALE) / SLOW_DOWN_SCAN_STEPS as f64;
+    let mut high = 1.;
+    let mut low = None;
+    for i in 1..=SLOW_DOWN_SCAN_STEPS {
+        let scale = 1. - i as f64 * step;
+        if trajectory_at(scale).get_total_runtime() > runtime {
+            low = Some(scale);
+            break;
+        }
+        high = scale;
+    }
+    let Some(mut low) = low else {
+        return trajectory_at(high);
+    };
+
+    for _ in 0..SLOW_DOWN_ITERATIONS {
+        let scale = (low + high) / 2.;
+        if trajectory_at(scale).get_total_runtime() > runtime {
+            low = scale;
+        } else {
+            high = scale;
+        }
+    }
+    trajectory_at(high)
+}
@@ -167,9 +167,9 @@ pub fn start_drawing(o: ViewerObject) -> ViewerObjectGuard {
     ViewerObjectGuard { id: uuid }
 }
 
-/// Starts the viewer server and the new frame thread.
-pub async fn init() {
-    let addr = SocketAddrV4::new(VIEWER_IP, VIEWER_PORT);
+/// Starts the viewer server (on `VIEWER_PORT` by default) and the new frame thread.
+pub async fn init(custom_port: Option<u16>) {
+    let addr = SocketAddrV4::new(VIEWER_IP, custom_port.unwrap_or(VIEWER_PORT));
     let new_frame_notify = Arc::new(Notify::new());
 
     // Create the event loop and TCP listener we'll accept connections on.
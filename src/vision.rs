@@ -1,5 +1,6 @@
 use crate::league_protocols::vision_packet::SslWrapperPacket;
 use crate::net::multicast_receiver::MulticastUdpReceiver;
+use crate::replay::PacketSource;
 use std::net::Ipv4Addr;
 
 const DEFAULT_VISION_IP: Ipv4Addr = Ipv4Addr::new(224, 5, 23, 2);
@@ -8,13 +9,14 @@ const DEFAULT_VISION_PORT_SIM: u16 = 10020;
 
 // TODO: Document
 pub struct Vision {
-    socket: MulticastUdpReceiver,
+    source: PacketSource<SslWrapperPacket>,
 }
 
 impl Vision {
     pub fn new(
         custom_vision_ip: Option<Ipv4Addr>,
         custom_vision_port: Option<u16>,
+        custom_interface: Option<Ipv4Addr>,
         real: bool,
     ) -> Self {
         let vision_ip = match custom_vision_ip {
@@ -30,13 +32,21 @@ impl Vision {
             DEFAULT_VISION_PORT_SIM
         };
 
-        Self {
-            socket: MulticastUdpReceiver::new(vision_ip, port)
-                .expect("Failed to create vision receiver"),
-        }
+        Self::from_source(PacketSource::Network(Box::new(
+            MulticastUdpReceiver::new(
+                vision_ip,
+                port,
+                custom_interface.unwrap_or(Ipv4Addr::UNSPECIFIED),
+            )
+            .expect("Failed to create vision receiver"),
+        )))
+    }
+
+    pub(crate) fn from_source(source: PacketSource<SslWrapperPacket>) -> Self {
+        Self { source }
     }
 
     pub async fn receive(&mut self) -> Result<SslWrapperPacket, crate::net::ReceiveError> {
-        self.socket.receive::<SslWrapperPacket>().await
+        self.source.receive().await
     }
 }
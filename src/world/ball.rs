@@ -1,14 +1,128 @@
 use crate::{
+    league_protocols::vision_packet::{
+        SslBallModelChipFixedLoss, SslBallModelStraightTwoPhase, SslGeometryModels,
+    },
     math::{Point2, Reactive, Vec2},
+    trajectories::{
+        ball::{BallTrajectory, ChipBallTrajectory, StraightBallTrajectory},
+        Trajectory,
+    },
     IgnoreMutexErr,
 };
 use std::sync::{Arc, Mutex};
 
+use super::{RobotId, TeamColor};
+
+/// under this speed in [m/s] the ball is considered still, its next acceleration is a new kick
+const BALL_STILL_SPEED: f64 = 0.1;
+
+/// A kick, as detected by a league tracker
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BallKick {
+    /// position in [m] from which the ball was kicked
+    pub start_pos: Point2,
+    /// initial velocity on the ground plane in [m/s]
+    pub start_vel: Vec2,
+    /// initial vertical velocity in [m/s] (non zero for chips)
+    pub start_vel_z: f64,
+    /// timestamp in [s] of the kick
+    pub start_time: f64,
+    /// predicted timestamp in [s] when the ball will stop
+    pub stop_time: Option<f64>,
+    /// predicted position in [m] where the ball will stop
+    pub stop_pos: Option<Point2>,
+    /// robot which kicked the ball
+    pub kicker: Option<(TeamColor, RobotId)>,
+}
+
+/// Two-phase model for straight kicked balls: the ball slides until its speed drops to
+/// `k_switch` times its initial speed, then rolls.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StraightTwoPhaseModel {
+    /// sliding acceleration in [m/s^2] (negative)
+    pub acc_slide: f64,
+    /// rolling acceleration in [m/s^2] (negative)
+    pub acc_roll: f64,
+    pub k_switch: f64,
+}
+
+impl Default for StraightTwoPhaseModel {
+    fn default() -> Self {
+        Self {
+            acc_slide: -3.,
+            acc_roll: -0.26,
+            k_switch: 0.69,
+        }
+    }
+}
+
+impl From<SslBallModelStraightTwoPhase> for StraightTwoPhaseModel {
+    fn from(model: SslBallModelStraightTwoPhase) -> Self {
+        Self {
+            acc_slide: model.acc_slide,
+            acc_roll: model.acc_roll,
+            k_switch: model.k_switch,
+        }
+    }
+}
+
+/// Fixed-loss model for chipped balls: velocity is damped by fixed factors at each hop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChipFixedLossModel {
+    pub damping_xy_first_hop: f64,
+    pub damping_xy_other_hops: f64,
+    pub damping_z: f64,
+}
+
+impl Default for ChipFixedLossModel {
+    fn default() -> Self {
+        Self {
+            damping_xy_first_hop: 0.75,
+            damping_xy_other_hops: 0.95,
+            damping_z: 0.6,
+        }
+    }
+}
+
+impl From<SslBallModelChipFixedLoss> for ChipFixedLossModel {
+    fn from(model: SslBallModelChipFixedLoss) -> Self {
+        Self {
+            damping_xy_first_hop: model.damping_xy_first_hop,
+            damping_xy_other_hops: model.damping_xy_other_hops,
+            damping_z: model.damping_z,
+        }
+    }
+}
+
+/// The league's ball models, sent by the vision along with the geometry.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BallModels {
+    pub straight_two_phase: StraightTwoPhaseModel,
+    pub chip_fixed_loss: ChipFixedLossModel,
+}
+
+impl BallModels {
+    /// models missing from the packet are left untouched
+    pub fn update_from_packet(&mut self, packet: SslGeometryModels) {
+        if let Some(model) = packet.straight_two_phase {
+            self.straight_two_phase = model.into();
+        }
+        if let Some(model) = packet.chip_fixed_loss {
+            self.chip_fixed_loss = model.into();
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Ball {
     pos: Arc<Mutex<Point2>>,
     vel: Arc<Mutex<Vec2>>,
     last_update: Arc<Mutex<Option<f64>>>,
+    kick: Arc<Mutex<Option<BallKick>>>,
+    lost: Arc<Mutex<bool>>,
+    models: Arc<Mutex<BallModels>>,
+    /// highest speed in [m/s] since the ball was last still, the kick speed when there's no kick information
+    peak_speed: Arc<Mutex<f64>>,
 }
 
 impl Default for Ball {
@@ -23,6 +137,10 @@ impl Ball {
             pos: Arc::new(Mutex::new(pos)),
             vel: Arc::new(Mutex::new(vel)),
             last_update: Arc::new(Mutex::new(None)),
+            kick: Arc::new(Mutex::new(None)),
+            lost: Arc::new(Mutex::new(true)), // until we see it
+            models: Default::default(),
+            peak_speed: Arc::new(Mutex::new(vel.norm())),
         }
     }
 
@@ -49,6 +167,88 @@ impl Ball {
 
     pub fn set_vel(&self, vel: Vec2) {
         *self.vel.lock().unwrap_ignore_poison() = vel;
+        let speed = vel.norm();
+        let mut peak_speed = self.peak_speed.lock().unwrap_ignore_poison();
+        *peak_speed = if speed < BALL_STILL_SPEED {
+            0.
+        } else {
+            peak_speed.max(speed)
+        };
+    }
+
+    /// true if the ball wasn't detected recently, its position is the last known one
+    pub fn is_lost(&self) -> bool {
+        *self.lost.lock().unwrap_ignore_poison()
+    }
+
+    pub fn set_lost(&self, lost: bool) {
+        *self.lost.lock().unwrap_ignore_poison() = lost;
+    }
+
+    /// the ongoing kick, only available when using a tracker which detects kicks
+    pub fn get_kick(&self) -> Option<BallKick> {
+        *self.kick.lock().unwrap_ignore_poison()
+    }
+
+    pub fn set_kick(&self, kick: Option<BallKick>) {
+        *self.kick.lock().unwrap_ignore_poison() = kick;
+    }
+
+    pub fn get_models(&self) -> BallModels {
+        *self.models.lock().unwrap_ignore_poison()
+    }
+
+    pub fn set_models(&self, models: BallModels) {
+        *self.models.lock().unwrap_ignore_poison() = models;
+    }
+
+    /// Predicted trajectory of the ball from its last update, using the league's ball models.
+    /// Without kick information (see `get_kick`), the ball is assumed to have been kicked at
+    /// its highest speed since it was last still: it slides until it slows down enough to roll.
+    pub fn get_trajectory(&self) -> BallTrajectory {
+        let models = self.get_models();
+        let (pos, vel) = (self.get_pos(), self.get_vel());
+        match self.get_kick() {
+            Some(kick) if kick.start_vel_z > 0. => BallTrajectory::Chip {
+                trajectory: ChipBallTrajectory::new(
+                    kick.start_pos,
+                    kick.start_vel,
+                    kick.start_vel_z,
+                    models.chip_fixed_loss,
+                    models.straight_two_phase,
+                ),
+                elapsed: self
+                    .get_last_update()
+                    .map_or(0., |t| (t - kick.start_time).max(0.)),
+            },
+            Some(kick) => BallTrajectory::Straight(StraightBallTrajectory::new(
+                pos,
+                vel,
+                models.straight_two_phase.k_switch * kick.start_vel.norm(),
+                models.straight_two_phase,
+            )),
+            None => BallTrajectory::Straight(StraightBallTrajectory::new(
+                pos,
+                vel,
+                models.straight_two_phase.k_switch * *self.peak_speed.lock().unwrap_ignore_poison(),
+                models.straight_two_phase,
+            )),
+        }
+    }
+
+    /// predicted position in `t` [s] from the last update
+    pub fn predict(&self, t: f64) -> Point2 {
+        self.get_trajectory().get_position(t)
+    }
+
+    /// position where the ball will come to a stop
+    pub fn get_stop_position(&self) -> Point2 {
+        self.get_trajectory().get_stop_position()
+    }
+
+    /// time in [s] from the last update until the ball passes by `point`, None if it won't
+    pub fn time_to_reach(&self, point: Point2) -> Option<f64> {
+        self.get_trajectory().time_to_reach(point)
     }
 }
 
@@ -63,3 +263,29 @@ impl AsRef<Ball> for Ball {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ball_without_kick_slides_after_speeding_up() {
+        let model = StraightTwoPhaseModel::default();
+        let ball = Ball::new(Point2::zero(), Vec2::zero());
+        ball.set_vel(Vec2::new(4., 0.));
+        let just_kicked = ball.get_trajectory();
+        let expected = StraightBallTrajectory::from_kick(Point2::zero(), Vec2::new(4., 0.), model);
+        assert!((just_kicked.get_stop_position().x - expected.get_stop_position().x).abs() < 1e-9);
+
+        // slowed down past the switch speed: rolling
+        ball.set_vel(Vec2::new(2., 0.));
+        let rolling = StraightBallTrajectory::rolling(Point2::zero(), Vec2::new(2., 0.), model);
+        assert!((ball.get_stop_position().x - rolling.get_stop_position().x).abs() < 1e-9);
+
+        // stopped then kicked again, slower
+        ball.set_vel(Vec2::zero());
+        ball.set_vel(Vec2::new(1., 0.));
+        let kicked = StraightBallTrajectory::from_kick(Point2::zero(), Vec2::new(1., 0.), model);
+        assert!((ball.get_stop_position().x - kicked.get_stop_position().x).abs() < 1e-9);
+    }
+}
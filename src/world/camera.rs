@@ -0,0 +1,116 @@
+use crate::{
+    league_protocols::vision_packet::SslGeometryCameraCalibration,
+    math::{Point2, Rect},
+    DETECTION_SCALING_FACTOR,
+};
+
+/// Calibration of a vision camera, as sent by ssl-vision.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraCalibration {
+    pub camera_id: u32,
+    /// in pixels
+    pub focal_length: f64,
+    /// in pixels
+    pub principal_point: Point2,
+    pub distortion: f64,
+    /// world to camera rotation as a (x, y, z, w) quaternion
+    pub rotation: [f64; 4],
+    /// world to camera translation in [m]
+    pub translation: [f64; 3],
+    /// camera position in the world in [m], as computed by ssl-vision
+    pub world_pos: Option<[f64; 3]>,
+    /// (width, height) in pixels
+    pub image_size: Option<(u32, u32)>,
+}
+
+impl From<SslGeometryCameraCalibration> for CameraCalibration {
+    fn from(calib: SslGeometryCameraCalibration) -> Self {
+        let mm_to_m = |mm: f32| mm as f64 / DETECTION_SCALING_FACTOR;
+        let world_pos = match (
+            calib.derived_camera_world_tx,
+            calib.derived_camera_world_ty,
+            calib.derived_camera_world_tz,
+        ) {
+            (Some(x), Some(y), Some(z)) => Some([mm_to_m(x), mm_to_m(y), mm_to_m(z)]),
+            _ => None,
+        };
+        Self {
+            camera_id: calib.camera_id,
+            focal_length: calib.focal_length as f64,
+            principal_point: Point2::new(
+                calib.principal_point_x as f64,
+                calib.principal_point_y as f64,
+            ),
+            distortion: calib.distortion as f64,
+            rotation: [calib.q0, calib.q1, calib.q2, calib.q3].map(f64::from),
+            translation: [calib.tx, calib.ty, calib.tz].map(mm_to_m),
+            world_pos,
+            image_size: calib.pixel_image_width.zip(calib.pixel_image_height),
+        }
+    }
+}
+
+impl CameraCalibration {
+    /// camera position in the world in [m], computed from the extrinsics if ssl-vision didn't send it
+    pub fn get_world_pos(&self) -> [f64; 3] {
+        self.world_pos.unwrap_or_else(|| {
+            // camera center is -R^T * t
+            let [x, y, z, w] = self.rotation;
+            let r = [
+                [
+                    1. - 2. * (y * y + z * z),
+                    2. * (x * y - z * w),
+                    2. * (x * z + y * w),
+                ],
+                [
+                    2. * (x * y + z * w),
+                    1. - 2. * (x * x + z * z),
+                    2. * (y * z - x * w),
+                ],
+                [
+                    2. * (x * z - y * w),
+                    2. * (y * z + x * w),
+                    1. - 2. * (x * x + y * y),
+                ],
+            ];
+            let t = self.translation;
+            [0, 1, 2].map(|i| -(r[0][i] * t[0] + r[1][i] * t[1] + r[2][i] * t[2]))
+        })
+    }
+
+    /// camera position projected on the ground in [m]
+    pub fn get_ground_pos(&self) -> Point2 {
+        let [x, y, _] = self.get_world_pos();
+        Point2::new(x, y)
+    }
+
+    /// camera height in [m]
+    pub fn get_height(&self) -> f64 {
+        self.get_world_pos()[2]
+    }
+
+    /// Position of an object at `height` in [m] which ssl-vision detected at `detected_pos` on the ground.
+    /// Used for chipped balls: the vision projects them on the ground, further away from the camera than they are.
+    pub fn project_at_height(&self, detected_pos: Point2, height: f64) -> Point2 {
+        let camera_pos = self.get_ground_pos();
+        let camera_height = self.get_height();
+        if camera_height <= height {
+            return detected_pos;
+        }
+        camera_pos + (detected_pos - camera_pos) * ((camera_height - height) / camera_height)
+    }
+
+    /// Approximate area of the ground seen by the camera, assuming it looks straight down.
+    /// None if ssl-vision didn't send the image size.
+    pub fn get_coverage(&self) -> Option<Rect> {
+        let (width, height) = self.image_size?;
+        let meters_per_pixel = self.get_height() / self.focal_length;
+        let half_width = width as f64 / 2. * meters_per_pixel;
+        let half_height = height as f64 / 2. * meters_per_pixel;
+        let center = self.get_ground_pos();
+        Some(Rect::new(
+            Point2::new(center.x - half_width, center.y - half_height),
+            Point2::new(center.x + half_width, center.y + half_height),
+        ))
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// battery voltage in [V] under which the battery should be changed (4S LiPo at 3.5V per cell)
+pub const BATTERY_LOW_VOLTAGE: f64 = 14.;
+
+/// Hardware problems reported by a robot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RobotErrors {
+    pub motors: bool,
+    pub kicker: bool,
+    pub dribbler: bool,
+    pub ball_sensor: bool,
+}
+
+impl RobotErrors {
+    pub fn any(&self) -> bool {
+        self.motors || self.kicker || self.dribbler || self.ball_sensor
+    }
+}
+
+/// What a robot tells us about itself, whatever the backend (simulator or real robots).
+/// Values a backend doesn't report are `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RobotFeedback {
+    /// time in [s] since the UNIX epoch at which the feedback was received
+    pub timestamp: f64,
+    /// ball sensor (IR barrier, dribbler contact..)
+    pub has_ball: bool,
+    /// in [V]
+    pub battery_voltage: Option<f64>,
+    /// kicker capacitors voltage in [V]
+    pub kicker_voltage: Option<f64>,
+    /// capacitors are charged enough to kick
+    pub kicker_ready: Option<bool>,
+    /// wheel speeds, as reported by the robot
+    pub wheel_speeds: Option<[f64; 4]>,
+    pub errors: RobotErrors,
+}
+
+impl RobotFeedback {
+    /// feedback received now
+    pub fn now() -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0., |d| d.as_secs_f64()),
+            ..Default::default()
+        }
+    }
+
+    /// robots which don't report their kicker state are assumed to always be ready
+    pub fn is_kicker_ready(&self) -> bool {
+        self.kicker_ready.unwrap_or(true) && !self.errors.kicker
+    }
+
+    /// false if the robot doesn't report its battery voltage
+    pub fn is_battery_low(&self) -> bool {
+        self.battery_voltage
+            .is_some_and(|voltage| voltage < BATTERY_LOW_VOLTAGE)
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    league_protocols::vision_packet::{
+        SslFieldCircularArc, SslFieldLineSegment, SslFieldShapeType, SslGeometryFieldSize, Vector2f,
+    },
+    math::{Line, Point2, Rect},
+    IgnoreMutexErr, DETECTION_SCALING_FACTOR,
+};
+
+use super::TeamColor;
+
+fn mm_to_m(mm: f32) -> f64 {
+    mm as f64 / DETECTION_SCALING_FACTOR
+}
+
+fn vector_to_point(v: Vector2f) -> Point2 {
+    Point2::new(mm_to_m(v.x), mm_to_m(v.y))
+}
+
+/// Half of the field, along the x axis.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldSide {
+    Positive,
+    Negative,
+}
+
+impl FromStr for FieldSide {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "positive" => Ok(FieldSide::Positive),
+            "negative" => Ok(FieldSide::Negative),
+            _ => Err(format!(
+                "unknown field side '{s}', expected 'positive' or 'negative'"
+            )),
+        }
+    }
+}
+
+/// A named line segment of the field markings, in meters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldLine {
+    pub name: String,
+    pub shape: SslFieldShapeType,
+    pub p1: Point2,
+    pub p2: Point2,
+    pub thickness: f64,
+}
+
+impl FieldLine {
+    pub fn as_line(&self) -> Line {
+        Line::new(self.p1, self.p2)
+    }
+}
+
+impl From<SslFieldLineSegment> for FieldLine {
+    fn from(segment: SslFieldLineSegment) -> Self {
+        Self {
+            shape: segment.r#type(),
+            name: segment.name,
+            p1: vector_to_point(segment.p1),
+            p2: vector_to_point(segment.p2),
+            thickness: mm_to_m(segment.thickness),
+        }
+    }
+}
+
+/// A named circular arc of the field markings, in meters, angles in radians (counter-clockwise from a1 to a2).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldArc {
+    pub name: String,
+    pub shape: SslFieldShapeType,
+    pub center: Point2,
+    pub radius: f64,
+    pub a1: f64,
+    pub a2: f64,
+    pub thickness: f64,
+}
+
+impl From<SslFieldCircularArc> for FieldArc {
+    fn from(arc: SslFieldCircularArc) -> Self {
+        Self {
+            shape: arc.r#type(),
+            name: arc.name,
+            center: vector_to_point(arc.center),
+            radius: mm_to_m(arc.radius),
+            a1: arc.a1 as f64,
+            a2: arc.a2 as f64,
+            thickness: mm_to_m(arc.thickness),
+        }
+    }
+}
+
+/// The field geometry as sent by the vision, all lengths in meters.
+/// The field's center is (0, 0) and the goals are on the x axis.
+#[derive(Clone)]
+pub struct Field {
+    /// field's length in meters
+    field_length: Arc<Mutex<f64>>,
+    /// field's width in meters
+    field_width: Arc<Mutex<f64>>,
+    /// Goal width (distance between inner edges of goal posts) in m
+    goal_width: Arc<Mutex<f64>>,
+    /// Goal depth (distance from outer goal line edge to inner goal back) in m
+    goal_depth: Arc<Mutex<f64>>,
+    /// in m
+    goal_height: Arc<Mutex<f64>>,
+    /// distance from touch/goal line centers to boundary walls in m
+    boundary_width: Arc<Mutex<f64>>,
+    /// depth of the defense area (measured between line centers) in m
+    defense_area_depth: Arc<Mutex<f64>>,
+    /// width of the defense area (measured between line centers) in m
+    defense_area_width: Arc<Mutex<f64>>,
+    /// in m
+    center_circle_radius: Arc<Mutex<f64>>,
+    /// in m
+    line_thickness: Arc<Mutex<f64>>,
+    /// distance between the goal center and the center of the penalty mark in m
+    goal_center_to_penalty_mark: Arc<Mutex<f64>>,
+    /// in m
+    ball_radius: Arc<Mutex<f64>>,
+    /// max allowed robot radius in m
+    max_robot_radius: Arc<Mutex<f64>>,
+    /// field markings, empty until the vision sends the geometry
+    lines: Arc<Mutex<Vec<FieldLine>>>,
+    arcs: Arc<Mutex<Vec<FieldArc>>>,
+    /// side of the field, given by the game controller
+    blue_on_positive_half: Arc<Mutex<bool>>,
+}
+
+impl Default for Field {
+    /// defaults to div B size
+    fn default() -> Self {
+        Field {
+            field_length: Arc::new(Mutex::new(9.)),
+            field_width: Arc::new(Mutex::new(6.)),
+            goal_width: Arc::new(Mutex::new(1.)),
+            goal_depth: Arc::new(Mutex::new(0.18)),
+            goal_height: Arc::new(Mutex::new(0.155)),
+            boundary_width: Arc::new(Mutex::new(0.3)),
+            defense_area_depth: Arc::new(Mutex::new(1.)),
+            defense_area_width: Arc::new(Mutex::new(2.)),
+            center_circle_radius: Arc::new(Mutex::new(0.5)),
+            line_thickness: Arc::new(Mutex::new(0.01)),
+            goal_center_to_penalty_mark: Arc::new(Mutex::new(6.)),
+            ball_radius: Arc::new(Mutex::new(0.0215)),
+            max_robot_radius: Arc::new(Mutex::new(0.09)),
+            lines: Default::default(),
+            arcs: Default::default(),
+            blue_on_positive_half: Default::default(),
+        }
+    }
+}
+
+impl Field {
+    /// Updates the geometry, optional values that aren't in the packet are left untouched.
+    pub fn update_from_packet(&mut self, packet: SslGeometryFieldSize) {
+        let set = |value: &Arc<Mutex<f64>>, mm: f64| {
+            *value.lock().unwrap_ignore_poison() = mm / DETECTION_SCALING_FACTOR;
+        };
+        set(&self.field_length, packet.field_length as f64);
+        set(&self.field_width, packet.field_width as f64);
+        set(&self.goal_width, packet.goal_width as f64);
+        set(&self.goal_depth, packet.goal_depth as f64);
+        set(&self.boundary_width, packet.boundary_width as f64);
+        let optional_values = [
+            (&self.goal_height, packet.goal_height.map(f64::from)),
+            (
+                &self.defense_area_depth,
+                packet.penalty_area_depth.map(f64::from),
+            ),
+            (
+                &self.defense_area_width,
+                packet.penalty_area_width.map(f64::from),
+            ),
+            (
+                &self.center_circle_radius,
+                packet.center_circle_radius.map(f64::from),
+            ),
+            (&self.line_thickness, packet.line_thickness.map(f64::from)),
+            (
+                &self.goal_center_to_penalty_mark,
+                packet.goal_center_to_penalty_mark.map(f64::from),
+            ),
+            (&self.ball_radius, packet.ball_radius.map(f64::from)),
+            (
+                &self.max_robot_radius,
+                packet.max_robot_radius.map(f64::from),
+            ),
+        ];
+        for (value, mm) in optional_values {
+            if let Some(mm) = mm {
+                set(value, mm);
+            }
+        }
+
+        *self.lines.lock().unwrap_ignore_poison() = packet
+            .field_lines
+            .into_iter()
+            .map(FieldLine::from)
+            .collect();
+        *self.arcs.lock().unwrap_ignore_poison() =
+            packet.field_arcs.into_iter().map(FieldArc::from).collect();
+    }
+
+    pub fn get_field_length(&self) -> f64 {
+        *self.field_length.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_field_width(&self) -> f64 {
+        *self.field_width.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_goal_depth(&self) -> f64 {
+        *self.goal_depth.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_goal_width(&self) -> f64 {
+        *self.goal_width.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_goal_height(&self) -> f64 {
+        *self.goal_height.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_boundary_width(&self) -> f64 {
+        *self.boundary_width.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_defense_area_depth(&self) -> f64 {
+        *self.defense_area_depth.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_defense_area_width(&self) -> f64 {
+        *self.defense_area_width.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_center_circle_radius(&self) -> f64 {
+        *self.center_circle_radius.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_line_thickness(&self) -> f64 {
+        *self.line_thickness.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_goal_center_to_penalty_mark(&self) -> f64 {
+        *self
+            .goal_center_to_penalty_mark
+            .lock()
+            .unwrap_ignore_poison()
+    }
+
+    pub fn get_ball_radius(&self) -> f64 {
+        *self.ball_radius.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_max_robot_radius(&self) -> f64 {
+        *self.max_robot_radius.lock().unwrap_ignore_poison()
+    }
+
+    pub fn get_lines(&self) -> Vec<FieldLine> {
+        self.lines.lock().unwrap_ignore_poison().clone()
+    }
+
+    pub fn get_arcs(&self) -> Vec<FieldArc> {
+        self.arcs.lock().unwrap_ignore_poison().clone()
+    }
+
+    /// finds a field marking by its name (e.g. "LeftGoalLine")
+    pub fn get_line(&self, name: &str) -> Option<FieldLine> {
+        self.lines
+            .lock()
+            .unwrap_ignore_poison()
+            .iter()
+            .find(|l| l.name == name)
+            .cloned()
+    }
+
+    /// finds a field marking by its name (e.g. "CenterCircle")
+    pub fn get_arc(&self, name: &str) -> Option<FieldArc> {
+        self.arcs
+            .lock()
+            .unwrap_ignore_poison()
+            .iter()
+            .find(|a| a.name == name)
+            .cloned()
+    }
+
+    pub fn is_blue_on_positive_half(&self) -> bool {
+        *self.blue_on_positive_half.lock().unwrap_ignore_poison()
+    }
+
+    pub fn set_blue_on_positive_half(&self, blue_on_positive_half: bool) {
+        *self.blue_on_positive_half.lock().unwrap_ignore_poison() = blue_on_positive_half;
+    }
+
+    /// sets the half of the field where the `color` team's goal is
+    pub fn set_side(&self, color: TeamColor, side: FieldSide) {
+        self.set_blue_on_positive_half((color == TeamColor::Blue) == (side == FieldSide::Positive));
+    }
+
+    /// 1 if the team's goal is on the positive x side of the field, -1 otherwise
+    fn side_sign(&self, color: TeamColor) -> f64 {
+        if (color == TeamColor::Blue) == self.is_blue_on_positive_half() {
+            1.
+        } else {
+            -1.
+        }
+    }
+
+    /// the field of play, inside the touch & goal lines
+    pub fn get_bounding_box(&self) -> Rect {
+        Rect::new(
+            Point2::new(self.get_field_length() / 2., -self.get_field_width() / 2.),
+            Point2::new(-self.get_field_length() / 2., self.get_field_width() / 2.),
+        )
+    }
+
+    /// the field of play and its boundary, up to the walls
+    pub fn get_boundary_bounding_box(&self) -> Rect {
+        self.get_bounding_box().grow(self.get_boundary_width())
+    }
+
+    pub fn is_inside_field(&self, p: Point2) -> bool {
+        self.get_bounding_box().contains(p)
+    }
+
+    pub fn is_inside_center_circle(&self, p: Point2) -> bool {
+        (p - Point2::zero()).norm() <= self.get_center_circle_radius()
+    }
+
+    /// center of the team's goal line
+    pub fn get_goal_center(&self, color: TeamColor) -> Point2 {
+        Point2::new(self.side_sign(color) * self.get_field_length() / 2., 0.)
+    }
+
+    /// the inside of the team's goal, behind its goal line
+    pub fn get_goal_bounding_box(&self, color: TeamColor) -> Rect {
+        let goal_center = self.get_goal_center(color);
+        Rect::new(
+            Point2::new(goal_center.x, self.get_goal_width() / 2.),
+            Point2::new(
+                goal_center.x + self.side_sign(color) * self.get_goal_depth(),
+                -self.get_goal_width() / 2.,
+            ),
+        )
+    }
+
+    /// the defense area in front of the team's goal
+    pub fn get_defense_area(&self, color: TeamColor) -> Rect {
+        let goal_center = self.get_goal_center(color);
+        Rect::new(
+            Point2::new(goal_center.x, self.get_defense_area_width() / 2.),
+            Point2::new(
+                goal_center.x - self.side_sign(color) * self.get_defense_area_depth(),
+                -self.get_defense_area_width() / 2.,
+            ),
+        )
+    }
+
+    pub fn is_inside_defense_area(&self, color: TeamColor, p: Point2) -> bool {
+        self.get_defense_area(color).contains(p)
+    }
+
+    /// the penalty mark used to shoot on the team's goal
+    pub fn get_penalty_mark(&self, color: TeamColor) -> Point2 {
+        let goal_center = self.get_goal_center(color);
+        Point2::new(
+            goal_center.x - self.side_sign(color) * self.get_goal_center_to_penalty_mark(),
+            0.,
+        )
+    }
+
+    pub fn get_yellow_goal_bounding_box(&self) -> Rect {
+        self.get_goal_bounding_box(TeamColor::Yellow)
+    }
+
+    pub fn get_blue_goal_bounding_box(&self) -> Rect {
+        self.get_goal_bounding_box(TeamColor::Blue)
+    }
+}
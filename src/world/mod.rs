@@ -1,31 +1,60 @@
 mod ball;
+mod camera;
+mod feedback;
+mod field;
 mod robot;
+mod specs;
 
 // EXPORTS
 pub use ball::*;
+pub use camera::*;
+pub use feedback::*;
+pub use field::*;
 pub use robot::*;
+pub use specs::*;
 
 use serde::{Deserialize, Serialize};
-use tokio::{sync::Notify, time::sleep};
-use tracing::warn;
+use tokio::{
+    sync::{broadcast, Notify},
+    time::{sleep, Instant},
+};
+use tracing::{debug, warn};
 
 use crate::{
-    league_protocols::vision_packet::SslGeometryFieldSize,
-    math::{Point2, Rect},
+    game_state::{GameEvent, GameState},
+    league_protocols::{game_controller_packet::Referee, vision_packet::SslGeometryData},
+    math::{Point2, ReactivePoint2Ext, Rect},
     IgnoreMutexErr,
 };
 use std::{
     collections::HashMap,
+    str::FromStr,
     sync::{Arc, Mutex},
     time::{Duration, SystemTime},
 };
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TeamColor {
+    #[serde(alias = "blue")]
     Blue,
+    #[serde(alias = "yellow")]
     Yellow,
 }
 
+impl FromStr for TeamColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "blue" => Ok(TeamColor::Blue),
+            "yellow" => Ok(TeamColor::Yellow),
+            _ => Err(format!(
+                "unknown team color '{s}', expected 'blue' or 'yellow'"
+            )),
+        }
+    }
+}
+
 impl TeamColor {
     pub fn opposite(&self) -> TeamColor {
         match self {
@@ -35,6 +64,41 @@ impl TeamColor {
     }
 }
 
+/// Time without detection after which robots & ball are considered lost.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VisibilityPolicy {
+    /// in [s]
+    pub robot_lost_after: f64,
+    /// in [s]
+    pub ball_lost_after: f64,
+}
+
+impl Default for VisibilityPolicy {
+    fn default() -> Self {
+        Self {
+            robot_lost_after: 1.,
+            ball_lost_after: 0.5,
+        }
+    }
+}
+
+/// Changes in the world which aren't simple state updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorldEvent {
+    /// a robot started being detected (newly added, or detected again after being lost)
+    RobotFound {
+        color: TeamColor,
+        id: RobotId,
+    },
+    /// a robot wasn't detected for `VisibilityPolicy::robot_lost_after` (removed from the field, occluded..)
+    RobotLost {
+        color: TeamColor,
+        id: RobotId,
+    },
+    BallFound,
+    BallLost,
+}
+
 #[derive(Clone)]
 pub struct World {
     creation_time: SystemTime,
@@ -44,6 +108,18 @@ pub struct World {
     pub ball: Ball,   // already has light cloning because internal arcs
     pub team: Arc<Mutex<HashMap<RobotId, AllyRobot>>>,
     pub ennemies: Arc<Mutex<HashMap<RobotId, EnnemyRobot>>>,
+    game_state: Arc<Mutex<GameState>>,
+    game_state_notifier: Arc<Notify>,
+    last_referee: Arc<Mutex<Option<Referee>>>,
+    cameras: Arc<Mutex<HashMap<u32, CameraCalibration>>>,
+    pub visibility_policy: VisibilityPolicy,
+    /// specs given to our robots when they're first detected
+    pub fleet_specs: FleetSpecs,
+    /// velocity cap of our robots in [m/s], see `set_team_max_vel`
+    team_max_vel: Arc<Mutex<Option<f64>>>,
+    /// vision time of the last `update_visibility` & when it happened
+    last_visibility_update: Arc<Mutex<Option<(f64, Instant)>>>,
+    events: broadcast::Sender<WorldEvent>,
 }
 
 impl World {
@@ -68,96 +144,316 @@ impl World {
             ball: Ball::default(),
             team: Default::default(),
             ennemies: Default::default(),
+            game_state: Default::default(),
+            game_state_notifier: Arc::new(Notify::new()),
+            last_referee: Default::default(),
+            cameras: Default::default(),
+            visibility_policy: Default::default(),
+            fleet_specs: Default::default(),
+            team_max_vel: Default::default(),
+            last_visibility_update: Default::default(),
+            events: broadcast::channel(64).0,
         }
     }
 
-    pub fn get_ennemy_goal_bounding_box(&self) -> Rect {
-        match self.team_color {
-            TeamColor::Blue => self.field.get_yellow_goal_bounding_box(),
-            TeamColor::Yellow => self.field.get_blue_goal_bounding_box(),
+    /// Updates the field, the cameras' calibration and the ball models.
+    pub fn update_from_geometry(&mut self, geometry: SslGeometryData) {
+        self.field.update_from_packet(geometry.field);
+        {
+            let mut cameras = self.cameras.lock().unwrap_ignore_poison();
+            for calib in geometry.calib {
+                cameras.insert(calib.camera_id, calib.into());
+            }
+        }
+        if let Some(models) = geometry.models {
+            let mut ball_models = self.ball.get_models();
+            ball_models.update_from_packet(models);
+            self.ball.set_models(ball_models);
         }
     }
 
-    pub async fn allies_detection(&self) {
-        while self.team.lock().unwrap_ignore_poison().is_empty() {
-            warn!("not detecting any ally robots yet, waiting 1s.");
-            sleep(Duration::from_secs(1)).await;
+    pub fn get_camera_calibration(&self, camera_id: u32) -> Option<CameraCalibration> {
+        self.cameras
+            .lock()
+            .unwrap_ignore_poison()
+            .get(&camera_id)
+            .copied()
+    }
+
+    pub fn get_camera_calibrations(&self) -> Vec<CameraCalibration> {
+        self.cameras
+            .lock()
+            .unwrap_ignore_poison()
+            .values()
+            .copied()
+            .collect()
+    }
+
+    /// Returns a receiver for the `WorldEvent`s emitted from now on.
+    pub fn subscribe_to_events(&self) -> broadcast::Receiver<WorldEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, event: WorldEvent) {
+        debug!(?event, "world event");
+        let _ = self.events.send(event); // it's fine if no one is listening
+    }
+
+    /// Marks robots & ball as lost/found according to the `visibility_policy`, `now` being the current vision time.
+    pub fn update_visibility(&self, now: f64) {
+        *self.last_visibility_update.lock().unwrap_ignore_poison() = Some((now, Instant::now()));
+        self.update_visibility_at(now);
+    }
+
+    /// Same as `update_visibility`, the vision time being extrapolated from the last update.
+    /// Called periodically, it loses everything when the vision stops sending frames.
+    pub fn update_visibility_from_clock(&self) {
+        let last_update = *self.last_visibility_update.lock().unwrap_ignore_poison();
+        if let Some((t, instant)) = last_update {
+            self.update_visibility_at(t + instant.elapsed().as_secs_f64());
         }
     }
-}
 
-#[derive(Clone)]
-pub struct Field {
-    /// field's length in meters
-    field_length: Arc<Mutex<f64>>,
-    /// field's width in meters
-    field_width: Arc<Mutex<f64>>,
-    // /// Goal width (distance between inner edges of goal posts) in m
-    goal_width: Arc<Mutex<f64>>,
-    /// Goal depth (distance from outer goal line edge to inner goal back) in m
-    goal_depth: Arc<Mutex<f64>>,
-}
+    fn update_visibility_at(&self, now: f64) {
+        let is_visible = |last_update: Option<f64>, lost_after: f64| {
+            last_update.is_some_and(|t| now - t <= lost_after)
+        };
 
-impl Default for Field {
-    /// defaults to div B size
-    fn default() -> Self {
-        Field {
-            field_length: Arc::new(Mutex::new(9.)),
-            field_width: Arc::new(Mutex::new(6.)),
-            goal_width: Arc::new(Mutex::new(1.)),
-            goal_depth: Arc::new(Mutex::new(0.18)),
+        self.update_robots_visibility(&self.team, |robot| {
+            is_visible(
+                robot.get_last_update(),
+                self.visibility_policy.robot_lost_after,
+            )
+        });
+        self.update_robots_visibility(&self.ennemies, |robot| {
+            is_visible(
+                robot.get_last_update(),
+                self.visibility_policy.robot_lost_after,
+            )
+        });
+
+        let ball_visible = is_visible(
+            self.ball.get_last_update(),
+            self.visibility_policy.ball_lost_after,
+        );
+        if ball_visible == self.ball.is_lost() {
+            self.ball.set_lost(!ball_visible);
+            self.emit(if ball_visible {
+                WorldEvent::BallFound
+            } else {
+                WorldEvent::BallLost
+            });
         }
     }
-}
 
-impl Field {
-    pub fn update_from_packet(&mut self, packet: SslGeometryFieldSize) {
-        *self.field_length.lock().unwrap_ignore_poison() = packet.field_length as f64 / 1000.;
-        *self.field_width.lock().unwrap_ignore_poison() = packet.field_width as f64 / 1000.;
+    fn update_robots_visibility<D: RobotData>(
+        &self,
+        robots: &Mutex<HashMap<RobotId, Robot<D>>>,
+        is_visible: impl Fn(&Robot<D>) -> bool,
+    ) {
+        for robot in robots.lock().unwrap_ignore_poison().values_mut() {
+            let visible = is_visible(robot);
+            if visible != robot.is_active() {
+                robot.set_active(visible);
+                let (color, id) = (robot.get_color(), robot.get_id());
+                self.emit(if visible {
+                    WorldEvent::RobotFound { color, id }
+                } else {
+                    WorldEvent::RobotLost { color, id }
+                });
+            }
+        }
     }
 
-    pub fn get_field_length(&self) -> f64 {
-        *self.field_length.lock().unwrap_ignore_poison()
+    pub fn get_game_state(&self) -> GameState {
+        *self.game_state.lock().unwrap_ignore_poison()
     }
 
-    pub fn get_field_width(&self) -> f64 {
-        *self.field_width.lock().unwrap_ignore_poison()
+    /// Sets the game state and wakes up the tasks waiting for a game state change (only if it changed).
+    /// Our robots are slowed down to `STOP_MAX_VEL` while the game state is speed limited (see `GameState::is_speed_limited`).
+    pub fn set_game_state(&self, game_state: GameState) {
+        self.update_game_state(|_| game_state);
     }
 
-    pub fn get_goal_depth(&self) -> f64 {
-        *self.goal_depth.lock().unwrap_ignore_poison()
+    /// feeds an event to the game state machine, returns the new game state
+    pub fn apply_game_event(&self, event: GameEvent) -> GameState {
+        self.update_game_state(|game_state| game_state.update(event, self.team_color))
     }
 
-    pub fn get_goal_width(&self) -> f64 {
-        *self.goal_width.lock().unwrap_ignore_poison()
+    fn update_game_state(&self, update: impl FnOnce(GameState) -> GameState) -> GameState {
+        let mut game_state = self.game_state.lock().unwrap_ignore_poison();
+        let new_game_state = update(*game_state);
+        if *game_state == new_game_state {
+            return new_game_state;
+        }
+        let was_speed_limited = game_state.is_speed_limited();
+        *game_state = new_game_state;
+        drop(game_state);
+        if new_game_state.is_speed_limited() != was_speed_limited {
+            self.set_team_max_vel(new_game_state.is_speed_limited().then_some(STOP_MAX_VEL));
+        }
+        self.game_state_notifier.notify_waiters();
+        new_game_state
     }
 
-    pub fn get_bounding_box(&self) -> Rect {
-        Rect::new(
-            Point2::new(self.get_field_width() / 2., -self.get_field_length() / 2.),
-            Point2::new(-self.get_field_width() / 2.0, self.get_field_length() / 2.0),
-        )
+    pub fn get_game_state_notifier(&self) -> Arc<Notify> {
+        self.game_state_notifier.clone()
     }
 
-    pub fn get_yellow_goal_bounding_box(&self) -> Rect {
-        let x_outer_line = self.get_field_length() / 2.;
-        Rect::new(
-            Point2::new(x_outer_line, self.get_goal_width() / 2.),
-            Point2::new(
-                x_outer_line + self.get_goal_depth(),
-                -self.get_goal_width() / 2.,
-            ),
-        )
+    /// Waits for the next game state change, a change happening before the call is missed
+    /// (to read the state then wait, enable a `Notified` of `get_game_state_notifier` first).
+    pub async fn next_game_state_change(&self) {
+        self.game_state_notifier.notified().await
+    }
+
+    /// last packet received from the game controller, if any
+    pub fn get_last_referee(&self) -> Option<Referee> {
+        self.last_referee.lock().unwrap_ignore_poison().clone()
+    }
+
+    pub fn set_last_referee(&self, referee: Referee) {
+        if let Some(blue_on_positive_half) = referee.blue_team_on_positive_half {
+            self.field.set_blue_on_positive_half(blue_on_positive_half);
+        }
+        *self.last_referee.lock().unwrap_ignore_poison() = Some(referee);
     }
 
-    pub fn get_blue_goal_bounding_box(&self) -> Rect {
-        let x_outer_line = -self.get_field_length() / 2.;
-        Rect::new(
-            Point2::new(
-                x_outer_line - self.get_goal_depth(),
-                self.get_goal_width() / 2.,
-            ),
-            Point2::new(x_outer_line, -self.get_goal_width() / 2.),
-        )
+    /// position where the ball has to be placed during a ball placement, in meters
+    pub fn get_designated_position(&self) -> Option<Point2> {
+        self.last_referee
+            .lock()
+            .unwrap_ignore_poison()
+            .as_ref()
+            .and_then(|r| r.designated_position)
+            .map(|p| Point2::new(p.x as f64 / 1000., p.y as f64 / 1000.))
+    }
+
+    pub fn get_ennemy_goal_bounding_box(&self) -> Rect {
+        self.field.get_goal_bounding_box(self.team_color.opposite())
+    }
+
+    pub fn get_ally_goal_bounding_box(&self) -> Rect {
+        self.field.get_goal_bounding_box(self.team_color)
+    }
+
+    pub fn get_ally_defense_area(&self) -> Rect {
+        self.field.get_defense_area(self.team_color)
+    }
+
+    pub fn get_ennemy_defense_area(&self) -> Rect {
+        self.field.get_defense_area(self.team_color.opposite())
+    }
+
+    pub fn is_inside_ally_defense_area(&self, p: Point2) -> bool {
+        self.field.is_inside_defense_area(self.team_color, p)
+    }
+
+    pub fn is_inside_ennemy_defense_area(&self, p: Point2) -> bool {
+        self.field
+            .is_inside_defense_area(self.team_color.opposite(), p)
+    }
+
+    /// penalty mark from which we shoot our penalties
+    pub fn get_ennemy_penalty_mark(&self) -> Point2 {
+        self.field.get_penalty_mark(self.team_color.opposite())
+    }
+
+    /// Caps the velocity of our robots to `max_vel` in [m/s] (e.g. `STOP_MAX_VEL`), or lifts the cap if `None`.
+    /// Robots detected afterwards are capped too, the robots' limits overrides are kept.
+    pub fn set_team_max_vel(&self, max_vel: Option<f64>) {
+        *self.team_max_vel.lock().unwrap_ignore_poison() = max_vel;
+        for robot in self.team.lock().unwrap_ignore_poison().values() {
+            self.apply_team_max_vel(robot);
+        }
+    }
+
+    fn apply_team_max_vel(&self, robot: &AllyRobot) {
+        robot.set_team_max_vel(*self.team_max_vel.lock().unwrap_ignore_poison());
+    }
+
+    /// a newly detected ally, with its specs & the team's velocity cap
+    pub fn new_ally(&self, id: RobotId) -> AllyRobot {
+        let robot = AllyRobot::default_with_id(id, self.team_color);
+        robot.set_specs(self.fleet_specs.get_specs(id));
+        self.apply_team_max_vel(&robot);
+        robot
+    }
+
+    /// the active ally closest to `p`, if any
+    pub fn get_closest_ally_to(&self, p: Point2) -> Option<AllyRobot> {
+        self.team
+            .lock()
+            .unwrap_ignore_poison()
+            .values()
+            .filter(|r| r.is_active())
+            .min_by(|a, b| a.distance_to(&p).total_cmp(&b.distance_to(&p)))
+            .cloned()
+    }
+
+    pub async fn allies_detection(&self) {
+        while self.team.lock().unwrap_ignore_poison().is_empty() {
+            warn!("not detecting any ally robots yet, waiting 1s.");
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_state::{RunningState, StoppedState};
+
+    use super::*;
+
+    fn world_with_ally(id: RobotId) -> (World, AllyRobot) {
+        let world = World::default_with_team_color(TeamColor::Blue);
+        let robot = world.new_ally(id);
+        world
+            .team
+            .lock()
+            .unwrap_ignore_poison()
+            .insert(id, robot.clone());
+        (world, robot)
+    }
+
+    #[test]
+    fn stop_caps_the_velocity() {
+        let (world, robot) = world_with_ally(0);
+        world.set_game_state(GameState::Stopped(StoppedState::Stop));
+        assert_eq!(robot.get_limits().max_vel, STOP_MAX_VEL);
+        // still capped once detected during the STOP
+        let new_robot = world.new_ally(1);
+        assert_eq!(new_robot.get_limits().max_vel, STOP_MAX_VEL);
+
+        world.set_game_state(GameState::Running(RunningState::Run));
+        assert_eq!(robot.get_limits(), robot.get_specs().limits);
+    }
+
+    #[test]
+    fn own_ball_placement_isnt_capped() {
+        let (world, robot) = world_with_ally(0);
+        world.set_game_state(GameState::Stopped(StoppedState::BallPlacementUs));
+        assert_eq!(robot.get_limits(), robot.get_specs().limits);
+        world.set_game_state(GameState::Stopped(StoppedState::BallPlacementThem));
+        assert_eq!(robot.get_limits().max_vel, STOP_MAX_VEL);
+    }
+
+    #[test]
+    fn limits_override_survives_a_stop() {
+        let (world, robot) = world_with_ally(0);
+        let slowed = robot.get_specs().limits.with_max_vel(1.);
+        robot.set_limits_override(Some(slowed));
+
+        world.set_game_state(GameState::Stopped(StoppedState::Stop));
+        // the slowest of the override & the cap
+        assert_eq!(robot.get_limits(), slowed);
+        world.set_game_state(GameState::Running(RunningState::Run));
+        assert_eq!(robot.get_limits(), slowed);
+
+        let faster = robot.get_specs().limits.with_max_vel(3.);
+        robot.set_limits_override(Some(faster));
+        world.set_game_state(GameState::Stopped(StoppedState::Stop));
+        assert_eq!(robot.get_limits(), faster.with_max_vel(STOP_MAX_VEL));
+        world.set_game_state(GameState::Running(RunningState::Run));
+        assert_eq!(robot.get_limits(), faster);
     }
 }
@@ -2,8 +2,11 @@ use tokio::{select, time::sleep};
 use tracing::{debug, instrument, trace};
 
 use crate::{
-    league_protocols::vision_packet::SslDetectionRobot,
+    league_protocols::{tracked_vision_packet::TrackedRobot, vision_packet::SslDetectionRobot},
     math::{angle_difference, Point2, Reactive, ReactivePoint2Ext, ReactiveVec2Ext, Vec2},
+    motion::MotionTarget,
+    obstacles::{AvoidanceRules, Obstacles},
+    tracking::RobotTracker,
     trajectories::{bangbang2d::BangBang2d, Trajectory},
     viewer::{self, ViewerObject, ViewerObjectGuard},
     world::World,
@@ -15,35 +18,45 @@ use std::{
     time::{Duration, Instant},
 };
 
-use super::{Ball, TeamColor};
+use super::{Ball, KinematicLimits, RobotFeedback, RobotSpecs, TeamColor};
 
 pub type RobotId = u8;
 
 /// distance in [m] to consider the robot arrived at a position
 const IS_CLOSE_EPSILON: f64 = 0.05;
 
-/// max number of iterations for RRT
-const RRT_MAX_TRIES: usize = 1_000;
+/// speed of the passes in [m/s]
+const PASS_KICK_SPEED: f64 = 5.;
 
-/// robot's max velocity in [m/s]
-const MAX_VEL: f64 = 5.;
+/// speed at which the robot passes by the intermediate waypoints of its paths, as a fraction of its max velocity,
+/// when it goes on straight ahead (it's slower for sharper turns)
+const WAYPOINT_SPEED_RATIO: f64 = 0.5;
 
-/// robot's max acceleration in [m/s^2]
-const MAX_ACC: f64 = 4.;
-
-const GOTO_ANGULAR_SPEED: f64 = 1.5;
+/// max number of iterations for RRT
+const RRT_MAX_TRIES: usize = 1_000;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Kick requested to a robot, its speed in [m/s] is clamped to the robot's `max_kick_speed`.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Kick {
-    Straight,
-    Chip,
+    Straight { speed: f64 },
+    Chip { speed: f64 },
 }
 
+impl Kick {
+    /// in [m/s]
+    pub fn get_speed(&self) -> f64 {
+        match self {
+            Kick::Straight { speed } | Kick::Chip { speed } => *speed,
+        }
+    }
+}
+
+/// Presets of `AvoidanceRules`.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum AvoidanceMode {
     /// can collide with everything
     None,
-    /// can collide with ball, can't collide with robots
+    /// can collide with ball, can't collide with robots nor go where the field & game state forbid
     AvoidRobots,
     /// can't collide
     AvoidRobotsAndBall,
@@ -62,6 +75,11 @@ pub struct Robot<D: RobotData> {
     orientation: Arc<Mutex<f64>>,
     has_ball: Arc<Mutex<bool>>,
     last_update: Arc<Mutex<Option<f64>>>,
+    /// confidence of the last detection
+    confidence: Arc<Mutex<f32>>,
+    /// false when the robot hasn't been detected for a while (see `World::update_visibility`)
+    active: Arc<Mutex<bool>>,
+    tracker: Arc<Mutex<RobotTracker>>,
     drawing: Arc<Mutex<ViewerObjectGuard>>,
     internal_data: D,
 }
@@ -72,6 +90,16 @@ pub struct AllyData {
     target_angular_vel: Arc<Mutex<f64>>,
     should_dribble: Arc<Mutex<bool>>,
     should_kick: Arc<Mutex<Option<Kick>>>,
+    feedback: Arc<Mutex<Option<RobotFeedback>>>,
+    /// followed by the motion control, which then sets the target velocities
+    motion_target: Arc<Mutex<Option<MotionTarget>>>,
+    /// when the target velocities were last set, on the tokio clock
+    last_command: Arc<Mutex<Option<tokio::time::Instant>>>,
+    specs: Arc<Mutex<RobotSpecs>>,
+    /// replaces the limits of the specs when set (e.g. a robot slowed down on purpose)
+    limits_override: Arc<Mutex<Option<KinematicLimits>>>,
+    /// velocity cap in [m/s] of the whole team (e.g. during a STOP), applied over the limits
+    team_max_vel: Arc<Mutex<Option<f64>>>,
 }
 
 impl RobotData for AllyData {}
@@ -101,6 +129,9 @@ impl<D: RobotData> Robot<D> {
             orientation: Default::default(),
             has_ball: Default::default(),
             last_update: Arc::new(Mutex::new(None)),
+            confidence: Default::default(),
+            active: Default::default(),
+            tracker: Default::default(),
             drawing: Arc::new(Mutex::new(viewer::start_drawing(ViewerObject::Robot {
                 id,
                 color,
@@ -168,6 +199,28 @@ impl<D: RobotData> Robot<D> {
         *self.last_update.lock().unwrap_ignore_poison() = Some(last_update);
     }
 
+    pub fn get_confidence(&self) -> f32 {
+        *self.confidence.lock().unwrap_ignore_poison()
+    }
+
+    pub fn set_confidence(&mut self, confidence: f32) {
+        *self.confidence.lock().unwrap_ignore_poison() = confidence;
+    }
+
+    /// false if the robot wasn't detected recently (e.g. taken off the field), its state is the last known one
+    pub fn is_active(&self) -> bool {
+        *self.active.lock().unwrap_ignore_poison()
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        *self.active.lock().unwrap_ignore_poison() = active;
+    }
+
+    /// state of the pose filter, for debugging
+    pub fn get_tracker(&self) -> RobotTracker {
+        *self.tracker.lock().unwrap_ignore_poison()
+    }
+
     pub fn update_from_packet(
         &mut self,
         detection: SslDetectionRobot,
@@ -179,16 +232,17 @@ impl<D: RobotData> Robot<D> {
             detection.y as f64 / DETECTION_SCALING_FACTOR,
         );
         let detectect_orientation = detection.orientation() as f64;
-        if let Some(last_t) = self.get_last_update() {
-            if last_t < t_capture {
-                let dt = t_capture - last_t;
-                self.set_vel((detected_pos - self.get_pos()) / dt); // TODO: remove f32 from the project :sob:
-                self.set_angular_vel((detectect_orientation - self.get_orientation()) / dt);
-            }
-        }
-        self.set_last_update(t_capture);
-        self.set_pos(detected_pos);
-        self.set_orientation(detectect_orientation);
+        let tracker = {
+            let mut tracker = self.tracker.lock().unwrap_ignore_poison();
+            tracker.update(detected_pos, detectect_orientation, t_capture);
+            *tracker
+        };
+        self.set_last_update(tracker.get_last_update().unwrap_or(t_capture));
+        self.set_confidence(detection.confidence);
+        self.set_pos(tracker.get_pos());
+        self.set_vel(tracker.get_vel());
+        self.set_orientation(tracker.get_orientation());
+        self.set_angular_vel(tracker.get_angular_vel());
 
         self.drawing
             .lock()
@@ -205,8 +259,23 @@ impl<D: RobotData> Robot<D> {
         // self.set_has_ball(has_ball); // handled by robot feedback for allies, TODO: find a way for ennemies
     }
 
-    fn collides_with_robot(&self, other_pos: Point2) -> bool {
-        self.distance_to(&other_pos) < 0.3 // a robot is 10cm radius => 0.3 leaves 10cm between robots
+    /// updates the robot from a league tracker's already filtered state, bypassing our own tracker
+    pub fn update_from_tracked_packet(&mut self, tracked: TrackedRobot, timestamp: f64) {
+        self.set_last_update(timestamp);
+        self.set_confidence(tracked.visibility.unwrap_or(1.));
+        self.set_pos(Point2::new(tracked.pos.x as f64, tracked.pos.y as f64));
+        self.set_orientation(tracked.orientation as f64);
+        if let Some(vel) = tracked.vel {
+            self.set_vel(Vec2::new(vel.x as f64, vel.y as f64));
+        }
+        if let Some(angular_vel) = tracked.vel_angular {
+            self.set_angular_vel(angular_vel as f64);
+        }
+
+        self.drawing
+            .lock()
+            .unwrap_ignore_poison()
+            .update(self.get_viewer_object());
     }
 
     pub fn pov(&self, pos_world: Point2) -> Point2 {
@@ -257,6 +326,70 @@ impl Robot<AllyData> {
         should_kick.replace(kick_type);
     }
 
+    /// latest feedback sent by the robot, None until the controller receives one
+    pub fn get_feedback(&self) -> Option<RobotFeedback> {
+        *self.internal_data.feedback.lock().unwrap_ignore_poison()
+    }
+
+    pub fn set_feedback(&mut self, feedback: RobotFeedback) {
+        self.set_has_ball(feedback.has_ball);
+        *self.internal_data.feedback.lock().unwrap_ignore_poison() = Some(feedback);
+    }
+
+    pub fn get_specs(&self) -> RobotSpecs {
+        *self.internal_data.specs.lock().unwrap_ignore_poison()
+    }
+
+    pub fn set_specs(&self, specs: RobotSpecs) {
+        *self.internal_data.specs.lock().unwrap_ignore_poison() = specs;
+    }
+
+    /// limits the robot moves with: the override if any, else the ones of its specs, under the team's velocity cap
+    pub fn get_limits(&self) -> KinematicLimits {
+        let limits = self
+            .internal_data
+            .limits_override
+            .lock()
+            .unwrap_ignore_poison()
+            .unwrap_or_else(|| self.get_specs().limits);
+        match *self
+            .internal_data
+            .team_max_vel
+            .lock()
+            .unwrap_ignore_poison()
+        {
+            Some(max_vel) => limits.with_max_vel(max_vel),
+            None => limits,
+        }
+    }
+
+    /// Replaces the limits of the robot's specs until it's reset with `None` (e.g. to slow it down on purpose).
+    /// The team's velocity cap still applies.
+    pub fn set_limits_override(&self, limits: Option<KinematicLimits>) {
+        *self
+            .internal_data
+            .limits_override
+            .lock()
+            .unwrap_ignore_poison() = limits;
+    }
+
+    /// Caps the velocity of the robot to `max_vel` in [m/s] whatever its limits, see `World::set_team_max_vel`.
+    pub(crate) fn set_team_max_vel(&self, max_vel: Option<f64>) {
+        *self
+            .internal_data
+            .team_max_vel
+            .lock()
+            .unwrap_ignore_poison() = max_vel;
+    }
+
+    pub fn is_kicker_ready(&self) -> bool {
+        self.get_feedback().is_some_and(|f| f.is_kicker_ready())
+    }
+
+    pub fn is_battery_low(&self) -> bool {
+        self.get_feedback().is_some_and(|f| f.is_battery_low())
+    }
+
     // return the should_kick state & resets it back to false (similar to Option::take)
     pub fn take_should_kick(&self) -> Option<Kick> {
         let mut should_kick = self.internal_data.should_kick.lock().unwrap_ignore_poison();
@@ -295,8 +428,11 @@ impl Robot<AllyData> {
         *self.internal_data.target_vel.lock().unwrap_ignore_poison()
     }
 
+    /// Drives the robot at `target_vel` in its own frame, instead of following its `MotionTarget`.
     pub fn set_target_vel(&self, target_vel: Vec2) {
         *self.internal_data.target_vel.lock().unwrap_ignore_poison() = target_vel;
+        self.clear_motion_target();
+        self.refresh_command();
     }
 
     pub fn get_target_angular_vel(&self) -> f64 {
@@ -307,63 +443,151 @@ impl Robot<AllyData> {
             .unwrap_ignore_poison()
     }
 
+    /// Turns the robot at `target_angular_vel` in [rad/s], instead of following its `MotionTarget`.
     pub fn set_target_angular_vel(&self, target_angular_vel: f64) {
         *self
             .internal_data
             .target_angular_vel
             .lock()
             .unwrap_ignore_poison() = target_angular_vel;
+        self.clear_motion_target();
+        self.refresh_command();
     }
 
-    fn is_free(&self, pos: Point2, world: &World, avoidance_mode: AvoidanceMode) -> bool {
-        if let AvoidanceMode::None = avoidance_mode {
-            return true;
-        }
+    /// Lets the motion control drive the robot to `destination`, facing `orientation` if any.
+    /// Like the target velocities, the target must be refreshed (see `COMMAND_TIMEOUT`).
+    pub fn move_to(&self, destination: Point2, orientation: Option<f64>) {
+        self.move_through(destination, orientation, Vec2::zero());
+    }
 
-        let is_colliding_with_allies = world
-            .team
+    /// Like `move_to`, but the robot passes by `waypoint` at `final_vel` in [m/s] instead of stopping there.
+    pub fn move_through(&self, waypoint: Point2, orientation: Option<f64>, final_vel: Vec2) {
+        *self
+            .internal_data
+            .motion_target
+            .lock()
+            .unwrap_ignore_poison() = Some(MotionTarget {
+            destination: waypoint,
+            orientation,
+            final_vel,
+        });
+        self.refresh_command();
+    }
+
+    pub fn get_motion_target(&self) -> Option<MotionTarget> {
+        *self
+            .internal_data
+            .motion_target
             .lock()
             .unwrap_ignore_poison()
-            .values()
-            .filter(|r| r.get_id() != self.get_id()) // can't collide with myself
-            .any(|r| r.collides_with_robot(pos));
-        let is_colliding_with_ennemies = world
-            .ennemies
+    }
+
+    fn clear_motion_target(&self) {
+        self.internal_data
+            .motion_target
             .lock()
             .unwrap_ignore_poison()
-            .values()
-            .any(|r| r.collides_with_robot(pos));
+            .take();
+    }
 
-        let is_colliding_with_a_robot = is_colliding_with_allies || is_colliding_with_ennemies;
-        if let AvoidanceMode::AvoidRobots = avoidance_mode {
-            return !is_colliding_with_a_robot;
-        }
+    /// Sets the target velocities computed by the motion control, the command isn't refreshed.
+    pub(crate) fn set_motion_command(&self, target_vel: Vec2, target_angular_vel: f64) {
+        *self.internal_data.target_vel.lock().unwrap_ignore_poison() = target_vel;
+        *self
+            .internal_data
+            .target_angular_vel
+            .lock()
+            .unwrap_ignore_poison() = target_angular_vel;
+    }
 
-        let is_colliding_with_ball = pos.distance_to(&world.ball) < 0.2;
-        !is_colliding_with_a_robot && !is_colliding_with_ball
+    fn refresh_command(&self) {
+        *self
+            .internal_data
+            .last_command
+            .lock()
+            .unwrap_ignore_poison() = Some(tokio::time::Instant::now());
+    }
+
+    /// Zeroes the target velocities & drops the `MotionTarget` if they weren't set for `timeout`
+    /// (e.g. the strategy driving the robot is stuck), returns whether the robot was still moving.
+    pub fn apply_command_watchdog(&self, timeout: Duration) -> bool {
+        let last_command = *self
+            .internal_data
+            .last_command
+            .lock()
+            .unwrap_ignore_poison();
+        if last_command.is_some_and(|t| t.elapsed() < timeout) {
+            return false;
+        }
+        // not through the setters, a stopped robot isn't a refreshed command
+        let mut target_vel = self.internal_data.target_vel.lock().unwrap_ignore_poison();
+        let mut target_angular_vel = self
+            .internal_data
+            .target_angular_vel
+            .lock()
+            .unwrap_ignore_poison();
+        let had_motion_target = self
+            .internal_data
+            .motion_target
+            .lock()
+            .unwrap_ignore_poison()
+            .take()
+            .is_some();
+        let was_moving =
+            had_motion_target || *target_vel != Vec2::zero() || *target_angular_vel != 0.;
+        *target_vel = Vec2::zero();
+        *target_angular_vel = 0.;
+        was_moving
     }
 
+    /// whether the trajectory, started in `start_t` [s], avoids the obstacles as they're expected to move
     pub fn is_a_valid_trajectory(
         &self,
         traj: &impl Trajectory<Point2, Vec2>,
-        world: &World,
-        avoidance_mode: AvoidanceMode,
+        obstacles: &Obstacles,
+        start_t: f64,
     ) -> bool {
         const TIME_STEP: f64 = 0.050; // 200ms as per tiger's tdp
         let n_points_to_check: usize = (traj.get_total_runtime() / TIME_STEP) as usize;
         for i in 0..n_points_to_check {
             let t = i as f64 * TIME_STEP;
             let p = traj.get_position(t);
-            if !self.is_free(p, world, avoidance_mode) {
-                trace!("[robot{}] collision at {}", self.get_id(), t);
+            if let Some(obstacle) = obstacles.get_collision(p, start_t + t) {
+                trace!(
+                    "[robot{}] collision with {:?} at {}",
+                    self.get_id(),
+                    obstacle.kind,
+                    t
+                );
                 return false;
             }
         }
         true
     }
 
-    fn make_bangbang2d_to(&self, dest: Point2) -> BangBang2d {
-        BangBang2d::new(self.get_pos(), self.get_vel(), dest, MAX_VEL, MAX_ACC, 0.1)
+    /// trajectory to `dest`, passing by it at `final_vel` in [m/s]
+    fn make_bangbang2d_to(&self, dest: Point2, final_vel: Vec2) -> BangBang2d {
+        let limits = self.get_limits();
+        BangBang2d::with_final_velocity(
+            self.get_pos(),
+            self.get_vel(),
+            dest,
+            final_vel,
+            limits.max_vel,
+            limits.get_trajectory_acc(),
+            0.1,
+        )
+    }
+
+    /// The velocity in [m/s] at which the robot passes by `waypoint` on its way from `from` to `to`:
+    /// towards `to`, slower for sharper turns, stopping if it turns back.
+    fn waypoint_vel(&self, from: Point2, waypoint: Point2, to: Point2) -> Vec2 {
+        let (incoming, outgoing) = (from.to(waypoint), waypoint.to(to));
+        if incoming.norm() < f64::EPSILON || outgoing.norm() < f64::EPSILON {
+            return Vec2::zero();
+        }
+        let straightness = incoming.normalized().dot(outgoing.normalized()).max(0.);
+        outgoing.normalized() * self.get_limits().max_vel * WAYPOINT_SPEED_RATIO * straightness
     }
 
     async fn goto_straight<T: Reactive<Point2>>(
@@ -388,15 +612,7 @@ impl Robot<AllyData> {
                 color: "red",
                 pos: destination.get_reactive(),
             });
-            let traj = self.make_bangbang2d_to(destination.get_reactive());
-            let v = self.pov_vec(traj.get_velocity(0.075));
-            self.set_target_vel(v);
-
-            if let Some(angle) = angle {
-                // TODO: find a way to use BangBang1d for orientation
-                let av = self.orientation_diff_to(angle) * GOTO_ANGULAR_SPEED;
-                self.set_target_angular_vel(av);
-            }
+            self.move_to(destination.get_reactive(), angle);
         }
     }
 
@@ -408,20 +624,30 @@ impl Robot<AllyData> {
     //     {
     //         // TODO: find a way to handle the angle
     //         self.set_target_angular_vel(
-    //             self.orientation_diff_to(self.to(destination).angle()) * GOTO_ANGULAR_SPEED,
+    //             self.orientation_diff_to(self.to(destination).angle()) * 1.5,
     //         );
     //         world.next_update().await;
     //     }
     // }
 
-    #[instrument(fields(robot_id = self.get_id(), pos = ?self.get_pos()), skip(self, world), level = "debug")]
-    fn simplify_path(
-        &self,
-        world: &World,
-        avoidance_mode: AvoidanceMode,
-        path: Vec<Point2>,
-    ) -> Vec<Point2> {
+    #[instrument(fields(robot_id = self.get_id(), pos = ?self.get_pos()), skip(self, obstacles), level = "debug")]
+    fn simplify_path(&self, obstacles: &Obstacles, path: Vec<Point2>) -> Vec<Point2> {
+        let limits = self.get_limits();
+        let make_segment = |start: Point2, start_vel: Vec2, end: Point2| {
+            BangBang2d::with_final_velocity(
+                start,
+                start_vel,
+                end,
+                Vec2::zero(),
+                limits.max_vel,
+                limits.get_trajectory_acc(),
+                0.1,
+            )
+        };
         let mut simplified_path = Vec::new();
+        // time in [s] at which the robot leaves the last point of the simplified path, & its velocity in [m/s] there
+        let mut segment_start_t = 0.;
+        let mut segment_start_vel = self.get_vel();
         let path_len = path.len();
         let mut last_p = self.get_pos();
         for (i, p, is_last) in path
@@ -429,21 +655,28 @@ impl Robot<AllyData> {
             .enumerate()
             .map(|(i, p)| (i, p, i == path_len - 1))
         {
-            let t = BangBang2d::new(
-                simplified_path.last().copied().unwrap_or(self.get_pos()),
-                Vec2::zero(),
-                p,
-                MAX_VEL,
-                MAX_ACC,
-                0.1,
-            );
-            let t_is_valid = self.is_a_valid_trajectory(&t, world, avoidance_mode);
+            let segment_start = simplified_path.last().copied().unwrap_or(self.get_pos());
+            let t = make_segment(segment_start, segment_start_vel, p);
+            let t_is_valid = self.is_a_valid_trajectory(&t, obstacles, segment_start_t);
             if is_last {
                 simplified_path.push(p);
             }
 
             if !t_is_valid {
                 trace!("skipped to {}", i);
+                // the robot passes by the waypoint towards the next point of the path
+                let waypoint_vel = self.waypoint_vel(segment_start, last_p, p);
+                segment_start_t += BangBang2d::with_final_velocity(
+                    segment_start,
+                    segment_start_vel,
+                    last_p,
+                    waypoint_vel,
+                    limits.max_vel,
+                    limits.get_trajectory_acc(),
+                    0.1,
+                )
+                .get_total_runtime();
+                segment_start_vel = waypoint_vel;
                 simplified_path.push(last_p);
                 last_p = p;
             } else {
@@ -454,21 +687,28 @@ impl Robot<AllyData> {
         simplified_path
     }
 
-    #[instrument(fields(robot_id = self.get_id()), skip(self, world, destination, angle), level = "debug")]
+    /// The robot's obstacles, except the ones it's already in (it must be able to leave them).
+    pub fn get_obstacles(&self, world: &World, rules: AvoidanceRules) -> Obstacles {
+        Obstacles::new(world, self, rules).without_obstacles_at(self.get_pos())
+    }
+
+    /// Drives the robot to `destination` around the obstacles of the `avoidance` rules (an `AvoidanceMode` or `AvoidanceRules`).
+    #[instrument(fields(robot_id = self.get_id()), skip(self, world, destination, angle, avoidance), level = "debug")]
     pub async fn goto<T: Reactive<Point2>>(
         &self,
         world: &World,
         destination: &T,
         angle: Option<f64>,
-        avoidance_mode: AvoidanceMode,
+        avoidance: impl Into<AvoidanceRules>,
     ) -> Result<(), GotoError> {
-        // if no avoidance_mode: fallback to Robot::goto
-        if let AvoidanceMode::None = avoidance_mode {
+        let rules = avoidance.into();
+        // if nothing to avoid: fallback to Robot::goto_straight
+        if rules.is_empty() {
             self.goto_straight(world, destination, angle).await;
             return Ok(());
         }
 
-        if !self.is_free(self.get_reactive(), world, avoidance_mode) {
+        if !Obstacles::new(world, self, rules).is_free(destination.get_reactive(), 0.) {
             return Err(GotoError::DestinationOccupiedError);
         }
 
@@ -490,7 +730,8 @@ impl Robot<AllyData> {
                 dest = ?destination.get_reactive(),
                 "trying to go to dest"
             );
-            let field = world.field.get_bounding_box(); // assume that the field won't change size during this path generation
+            let field = world.field.get_boundary_bounding_box(); // assume that the field won't change size during this path generation
+            let obstacles = self.get_obstacles(world, rules);
 
             // TODO: fix later
             // let traj = self.make_bangbang2d_to(destination.get_reactive());
@@ -505,7 +746,7 @@ impl Robot<AllyData> {
             let path = rrt::dual_rrt_connect(
                 &self.get_pos().to_vec(),
                 &destination.get_reactive().to_vec(),
-                |p| self.is_free(Point2::from_vec(p), world, avoidance_mode),
+                |p| obstacles.is_free(Point2::from_vec(p), 0.),
                 || field.sample_inside().to_vec(),
                 0.1,
                 RRT_MAX_TRIES,
@@ -520,8 +761,7 @@ impl Robot<AllyData> {
                 .skip(1)
                 .map(|p| Point2::from_vec(&p))
                 .collect();
-            let simplified_path =
-                self.simplify_path(world, avoidance_mode, path_without_current_pos);
+            let simplified_path = self.simplify_path(&obstacles, path_without_current_pos);
             let simplified_path_len = simplified_path.len();
 
             let mut _ps = vec![self.get_pos()];
@@ -540,39 +780,39 @@ impl Robot<AllyData> {
                 })
                 .collect();
 
+            let mut previous = self.get_pos();
             for (i, p) in simplified_path.iter().enumerate() {
                 trace!("going to point {}", i);
                 let is_last = i == simplified_path_len - 1;
-                let done_dst = if is_last {
-                    IS_CLOSE_EPSILON
-                } else {
-                    IS_CLOSE_EPSILON * 3.
+                let (done_dst, final_vel) = match simplified_path.get(i + 1) {
+                    Some(next) => (
+                        IS_CLOSE_EPSILON * 3.,
+                        self.waypoint_vel(previous, *p, *next),
+                    ),
+                    None => (IS_CLOSE_EPSILON, Vec2::zero()),
                 };
-                while !(self.get_pos().distance_to(p) < done_dst
+                previous = *p;
+                // passing by a waypoint, the robot may miss it by a bit, then it's behind it
+                let passed =
+                    || final_vel != Vec2::zero() && self.get_pos().to(*p).dot(final_vel) < 0.;
+                while !((self.get_pos().distance_to(p) < done_dst || passed())
                     && angle
                         .map(|a| self.orientation_diff_to(a).abs() < 0.02)
                         .unwrap_or(true)
                     && (!is_last || self.get_vel().norm() < 0.02))
                 {
                     world.next_update().await;
-                    let traj = self.make_bangbang2d_to(*p);
-                    if !self.is_a_valid_trajectory(&traj, world, avoidance_mode) {
+                    let traj = self.make_bangbang2d_to(*p, final_vel);
+                    if !self.is_a_valid_trajectory(&traj, &self.get_obstacles(world, rules), 0.) {
                         debug!("traj is now invalid, generating a new path!");
                         continue 'newpath;
                     }
-                    let v = self.pov_vec(traj.get_velocity(0.075));
-                    self.set_target_vel(v);
+                    self.move_through(*p, angle, final_vel);
                     path_drawing[0].update(ViewerObject::Segment {
                         color: "red",
                         start: self.get_pos(), // update the current segment of the path to start at robot pos
                         end: *p,
                     });
-
-                    if let Some(angle) = angle {
-                        // TODO: find a way to use BangBang1d for orientation
-                        let av = self.orientation_diff_to(angle) * GOTO_ANGULAR_SPEED;
-                        self.set_target_angular_vel(av);
-                    }
                 }
                 path_drawing.pop_front(); // when done with a point, we drop it to stop drawing it
             }
@@ -626,7 +866,9 @@ impl Robot<AllyData> {
         let mut kick_cooldown = tokio::time::interval(CONTROL_PERIOD);
         while self.has_ball() {
             kick_cooldown.tick().await;
-            self.kick(Kick::Straight);
+            self.kick(Kick::Straight {
+                speed: PASS_KICK_SPEED,
+            });
         }
         match tokio::time::timeout(Duration::from_secs(1), receiver.wait_until_has_ball()).await {
             Ok(_) => {
@@ -0,0 +1,114 @@
+use serde::Deserialize;
+
+use super::RobotId;
+
+/// max robot velocity in [m/s] allowed by the rules during a STOP & the ennemies' ball placement
+pub const STOP_MAX_VEL: f64 = 1.5;
+
+/// How fast a robot can move, used to plan its trajectories & clamp its commands.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct KinematicLimits {
+    /// in [m/s]
+    pub max_vel: f64,
+    /// in [m/s^2]
+    pub max_acc: f64,
+    /// braking acceleration in [m/s^2]
+    pub max_dec: f64,
+    /// in [rad/s]
+    pub max_angular_vel: f64,
+    /// in [rad/s^2]
+    pub max_angular_acc: f64,
+}
+
+impl Default for KinematicLimits {
+    fn default() -> Self {
+        Self {
+            max_vel: 5.,
+            max_acc: 4.,
+            max_dec: 4.,
+            max_angular_vel: 4.,
+            max_angular_acc: 15.,
+        }
+    }
+}
+
+impl KinematicLimits {
+    /// the same limits, going at most at `max_vel` in [m/s] (e.g. `STOP_MAX_VEL`)
+    pub fn with_max_vel(self, max_vel: f64) -> Self {
+        Self {
+            max_vel: self.max_vel.min(max_vel),
+            ..self
+        }
+    }
+
+    /// acceleration in [m/s^2] of the bang-bang trajectories, which speed up & brake alike
+    pub fn get_trajectory_acc(&self) -> f64 {
+        self.max_acc.min(self.max_dec)
+    }
+}
+
+/// Physical characteristics of one of our robots.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RobotSpecs {
+    /// in [m]
+    pub radius: f64,
+    /// in [m]
+    pub height: f64,
+    /// in [m]
+    pub dribbler_width: f64,
+    /// in [m/s]
+    pub max_kick_speed: f64,
+    pub limits: KinematicLimits,
+}
+
+impl Default for RobotSpecs {
+    fn default() -> Self {
+        Self {
+            radius: 0.09,
+            height: 0.15,
+            dribbler_width: 0.07,
+            max_kick_speed: 6.5,
+            limits: Default::default(),
+        }
+    }
+}
+
+/// Specs shared by some of our robots (e.g. a hardware generation).
+/// Values missing from a profile are the defaults of `RobotSpecs`, not the fleet's default ones.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpecsProfile {
+    pub ids: Vec<RobotId>,
+    pub specs: RobotSpecs,
+}
+
+/// Specs of our robots, given to them when they're first detected.
+///
+/// # Example
+/// ```toml
+/// [robots.default.limits]
+/// max_vel = 3.5
+///
+/// [[robots.profiles]]
+/// ids = [0, 1, 2]
+/// specs = { radius = 0.085, limits = { max_vel = 2.5, max_acc = 3.0 } }
+/// ```
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FleetSpecs {
+    /// specs of the robots which aren't in a profile
+    pub default: RobotSpecs,
+    pub profiles: Vec<SpecsProfile>,
+}
+
+impl FleetSpecs {
+    /// specs of the first profile with this id, or the default ones
+    pub fn get_specs(&self, id: RobotId) -> RobotSpecs {
+        self.profiles
+            .iter()
+            .find(|profile| profile.ids.contains(&id))
+            .map_or(self.default, |profile| profile.specs)
+    }
+}
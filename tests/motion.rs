@@ -0,0 +1,54 @@
+use crabe_async::{
+    math::Point2,
+    testing::{
+        scenario::{Scenario, ScenarioRobot},
+        simulator::Simulator,
+        synchronous_simulation::DelayedDetections,
+    },
+    world::{AllyRobot, AvoidanceMode, TeamColor, World},
+};
+
+fn ally(world: &World, id: u8) -> AllyRobot {
+    world.team.lock().unwrap()[&id].clone()
+}
+
+#[tokio::test(start_paused = true)]
+async fn goto_reaches_target_despite_vision_latency() {
+    let scenario = Scenario {
+        name: "goto_reaches_target_despite_vision_latency".to_string(),
+        allies: vec![ScenarioRobot::new(0, Point2::new(-2., -1.), 0.)],
+        ball_pos: Point2::new(0., 2.),
+        ..Default::default()
+    };
+    let target = Point2::new(2., 1.);
+    // 80ms between the commands & the detections
+    let simulator = DelayedDetections::new(Simulator::new(), 8);
+    let outcome = scenario
+        .run(simulator, |world| async move {
+            ally(&world, 0)
+                .goto(&world, &target, Some(0.), AvoidanceMode::None)
+                .await
+        })
+        .await
+        .unwrap();
+    outcome.assert_finished();
+    outcome.assert_robot_reached(0, target, 0.05);
+}
+
+#[tokio::test(start_paused = true)]
+#[should_panic(expected = "weren't detected")]
+async fn undetected_robots_fail_the_scenario() {
+    let scenario = Scenario {
+        name: "undetected_robots_fail_the_scenario".to_string(),
+        allies: vec![ScenarioRobot::new(0, Point2::new(-2., -1.), 0.)],
+        ..Default::default()
+    };
+    // the detections never show up within the detection timeout
+    let simulator = DelayedDetections::new(Simulator::new(), 1_000);
+    let outcome = scenario
+        .run(simulator, |world| async move { ally(&world, 0).get_pos() })
+        .await
+        .unwrap();
+    assert_eq!(outcome.get_undetected_robots(), [(TeamColor::Blue, 0)]);
+    outcome.assert_finished();
+}
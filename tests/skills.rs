@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use crabe_async::{
+    actions::{backwards_strike, place_ball, strike_alone},
+    math::{Point2, ReactivePoint2Ext},
+    testing::{
+        scenario::{Scenario, ScenarioRobot},
+        simulator::Simulator,
+    },
+    world::{AllyRobot, AvoidanceMode, World},
+};
+
+fn ally(world: &World, id: u8) -> AllyRobot {
+    world.team.lock().unwrap()[&id].clone()
+}
+
+#[tokio::test(start_paused = true)]
+async fn goto_reaches_target_without_collision() {
+    let scenario = Scenario {
+        name: "goto_reaches_target_without_collision".to_string(),
+        allies: vec![ScenarioRobot::new(0, Point2::new(-2., 0.), 0.)],
+        ennemies: vec![ScenarioRobot::new(0, Point2::new(0., 0.5), 0.)],
+        ball_pos: Point2::new(0., 2.),
+        ..Default::default()
+    };
+    let target = Point2::new(2., 0.);
+    let outcome = scenario
+        .run(Simulator::new(), |world| async move {
+            ally(&world, 0)
+                .goto(&world, &target, Some(0.), AvoidanceMode::AvoidRobots)
+                .await
+        })
+        .await
+        .unwrap();
+    outcome.assert_finished();
+    outcome.check(
+        outcome.get_result().is_some_and(|r| r.is_ok()),
+        "goto failed",
+    );
+    outcome.assert_robot_reached(0, target, 0.05);
+    outcome.assert_no_collision();
+}
+
+#[tokio::test(start_paused = true)]
+async fn goto_passes_by_waypoints_around_a_wall() {
+    let scenario = Scenario {
+        name: "goto_passes_by_waypoints_around_a_wall".to_string(),
+        allies: vec![ScenarioRobot::new(0, Point2::new(-2., 0.), 0.)],
+        ennemies: (0..4)
+            .map(|id| ScenarioRobot::new(id, Point2::new(0., -0.6 + 0.4 * id as f64), 0.))
+            .collect(),
+        ball_pos: Point2::new(0., 2.),
+        ..Default::default()
+    };
+    let target = Point2::new(2., 0.);
+    let outcome = scenario
+        .run(Simulator::new(), |world| async move {
+            ally(&world, 0)
+                .goto(&world, &target, Some(0.), AvoidanceMode::AvoidRobots)
+                .await
+        })
+        .await
+        .unwrap();
+    outcome.assert_finished();
+    outcome.check(
+        outcome.get_result().is_some_and(|r| r.is_ok()),
+        "goto failed",
+    );
+    outcome.assert_robot_reached(0, target, 0.05);
+    outcome.assert_no_collision();
+}
+
+#[tokio::test(start_paused = true)]
+async fn strike_alone_scores() {
+    let scenario = Scenario {
+        name: "strike_alone_scores".to_string(),
+        allies: vec![ScenarioRobot::new(0, Point2::new(0., -1.), 0.)],
+        ball_pos: Point2::new(2., 0.5),
+        timeout: Duration::from_secs(15),
+        ..Default::default()
+    };
+    let outcome = scenario
+        .run(Simulator::new(), |world| async move {
+            strike_alone(&world, &ally(&world, 0), &world.ball).await
+        })
+        .await
+        .unwrap();
+    outcome.assert_finished();
+    outcome.assert_ball_entered_ennemy_goal();
+    outcome.assert_no_collision();
+}
+
+#[tokio::test(start_paused = true)]
+async fn backwards_strike_scores() {
+    let scenario = Scenario {
+        name: "backwards_strike_scores".to_string(),
+        allies: vec![ScenarioRobot::new(0, Point2::new(1., 0.), 0.)],
+        ball_pos: Point2::new(2., 0.),
+        timeout: Duration::from_secs(20),
+        ..Default::default()
+    };
+    let outcome = scenario
+        .run(Simulator::new(), |world| async move {
+            backwards_strike(&world, &ally(&world, 0), &world.ball).await
+        })
+        .await
+        .unwrap();
+    outcome.assert_finished();
+    outcome.assert_ball_entered_ennemy_goal();
+    outcome.assert_no_collision();
+}
+
+#[tokio::test(start_paused = true)]
+async fn place_ball_puts_ball_on_target() {
+    let scenario = Scenario {
+        name: "place_ball_puts_ball_on_target".to_string(),
+        allies: vec![ScenarioRobot::new(0, Point2::new(-1., -1.), 0.)],
+        ball_pos: Point2::new(0., 0.),
+        timeout: Duration::from_secs(15),
+        ..Default::default()
+    };
+    let target = Point2::new(1., 1.);
+    let outcome = scenario
+        .run(Simulator::new(), |world| async move {
+            place_ball(&world, &ally(&world, 0), &world.ball, &target).await;
+            world.ball.get_pos()
+        })
+        .await
+        .unwrap();
+    outcome.assert_finished();
+    let ball_pos = outcome.get_world().ball.get_pos();
+    outcome.check(
+        ball_pos.distance_to(&target) < 0.1,
+        &format!("the ball ended at {ball_pos:?}"),
+    );
+    outcome.assert_no_collision();
+}